@@ -73,6 +73,8 @@ pub fn load_test_psm_csv(path: &str) -> Result<(Array2<f32>, Array1<i32>, PsmMet
         file_id: file_ids,
         spec_id: spec_ids,
         feature_names,
+        peptide: None,
+        proteins: None,
     };
 
     Ok((x, y, metadata))
@@ -1,10 +1,12 @@
 use std::collections::HashMap;
 use std::sync::Arc;
 
+use serde::{Deserialize, Serialize};
+
 use ndarray::{Array1, Array2, ArrayView2, Axis};
 use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
-use rand::{thread_rng, SeedableRng};
+use rand::SeedableRng;
 
 use crate::error::{ExperimentError, TdcError};
 use crate::stats::tdc;
@@ -17,12 +19,31 @@ pub struct PsmMetadata {
     pub file_id: Vec<usize>,
     /// Feature names
     pub feature_names: Vec<String>,
+    /// Peptide sequence (optional), used to keep PSMs of the same peptide in the same fold
+    pub peptide: Option<Vec<String>>,
+    /// Protein accession(s) of the peptide (optional), used for protein-level diagnostics
+    pub proteins: Option<Vec<String>>,
+}
+
+/// Strategy used to group PSMs when splitting them into cross-validation folds.
+///
+/// All PSMs of a group are assigned to the same fold, so that no information about
+/// a group can leak from the training set into the test set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum FoldGrouping {
+    /// Group PSMs by spectrum, i.e. by `(file_id, spec_id)`
+    #[default]
+    Spectrum,
+    /// Group PSMs sharing either a spectrum or a peptide sequence
+    Peptide,
 }
 
 #[derive(Debug, Clone)]
 pub struct Experiment {
     pub x: Array2<f32>,
     pub y: Array1<i32>,
+    /// Whether each PSM is a target, fixed at construction while `y` is relabeled during training
+    pub is_target: Array1<bool>,
     pub is_train: Array1<bool>,
     pub is_top_peak: Array1<bool>,
     pub tg_num_id: Array1<i32>,
@@ -52,11 +73,12 @@ impl Experiment {
         
         Ok(
             Experiment {
+                is_target: y.mapv(|v| v == 1),
                 x,
                 y,
                 is_train: Array1::from_elem(n_samples, false),
                 is_top_peak: Array1::from_elem(n_samples, false),
-                tg_num_id: Array1::from_elem(n_samples, 0),
+                tg_num_id: Array1::from_iter(0..n_samples as i32), // row index in the input data
                 classifier_score: Array1::from_elem(n_samples, 0.0),
                 psm_metadata,
             }
@@ -77,6 +99,9 @@ impl Experiment {
     /// FDR threshold.
     /// This method is adapted from MS2Rescore
     ///
+    /// Targets and decoys are taken from `is_target` rather than from the current labels, so
+    /// the labels can be updated repeatedly during iterative training.
+    ///
     /// # Arguments
     ///
     /// * `scores` - The scores used to rank the PSMs.
@@ -88,17 +113,13 @@ impl Experiment {
     /// An Array1<i32> where 1 indicates a positive example, -1 indicates a negative example,
     /// and 0 removes the PSM from training. Typically, 0 is reserved for targets below
     /// the specified FDR threshold.
-    pub fn update_labels(&self, scores: &Array1<f32>, eval_fdr: f32, desc: bool) -> Array1<i32> {
-        let targets = &self.y.mapv(|v| v == 1);
-        let qvals = tdc(scores, targets, desc).unwrap_or_else(|e| {
-            panic!(
-                "Fatal error in update_labels: {}. \
-                 Scores likely contained {} NaN values when computing qvalues with target-decoy competition. \
-                 This suggests a problem in the scoring function.",
-                e,
-                if let TdcError::NaNFound(n) = e { n } else { 0 }
-            );
-        });
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`TdcError`] if the scores contain NaN values, which suggests a problem in the scoring function.
+    pub fn update_labels(&self, scores: &Array1<f32>, eval_fdr: f32, desc: bool) -> Result<Array1<i32>, TdcError> {
+        let targets = &self.is_target;
+        let qvals = tdc(scores, targets, desc)?;
         
         let unlabeled = (&qvals.mapv(|v| v > eval_fdr)) & targets;
         
@@ -111,7 +132,7 @@ impl Experiment {
             }
         }
         
-        new_labels
+        Ok(new_labels)
     }
 
     /// Update the "rank" feature column based on new classifier scores.
@@ -244,7 +265,7 @@ impl Experiment {
     /// - Top peak flags `is_top_peak`
     /// - Target group identifiers `tg_num_id`
     /// - Classifier scores `classifier_score`
-    /// - PSM metadata: `spec_id`, `file_id` (feature names are retained as-is)
+    /// - PSM metadata: `spec_id`, `file_id`, `peptide`, `proteins` (feature names are retained as-is)
     ///
     /// # Arguments
     ///
@@ -267,6 +288,7 @@ impl Experiment {
         Experiment {
             x: self.x.select(Axis(0), &selected_indices),
             y: self.y.select(Axis(0), &selected_indices),
+            is_target: self.is_target.select(Axis(0), &selected_indices),
             is_train: self.is_train.select(Axis(0), &selected_indices),
             is_top_peak: self.is_top_peak.select(Axis(0), &selected_indices),
             tg_num_id: self.tg_num_id.select(Axis(0), &selected_indices),
@@ -275,13 +297,19 @@ impl Experiment {
                 spec_id: filter_vec(&self.psm_metadata.spec_id, &selected_indices),
                 file_id: filter_vec(&self.psm_metadata.file_id, &selected_indices),
                 feature_names: self.psm_metadata.feature_names.clone(), // not row-aligned
+                peptide: self.psm_metadata.peptide.as_ref().map(|p| filter_vec(p, &selected_indices)),
+                proteins: self.psm_metadata.proteins.as_ref().map(|p| filter_vec(p, &selected_indices)),
             },
         }
     }
     
 
-    pub fn split_for_xval(&mut self, fraction: f32, is_test: bool) {
-        let mut rng = thread_rng();
+    /// Mark a `fraction` of the PSMs as training PSMs, the rest are used for evaluation
+    ///
+    /// Unless `is_test` is set, the PSMs are shuffled with a random number generator seeded with
+    /// `seed`, so the split is reproducible.
+    pub fn split_for_xval(&mut self, fraction: f32, is_test: bool, seed: u64) {
+        let mut rng = StdRng::seed_from_u64(seed);
         let n_samples = self.x.nrows();
         let mut indices: Vec<usize> = (0..n_samples).collect();
         
@@ -314,10 +342,179 @@ impl Experiment {
 
         self.x = self.x.select(Axis(0), &keep);
         self.y = self.y.select(Axis(0), &keep);
+        self.is_target = self.is_target.select(Axis(0), &keep);
         self.is_train = self.is_train.select(Axis(0), &keep);
         self.is_top_peak = self.is_top_peak.select(Axis(0), &keep);
         self.tg_num_id = self.tg_num_id.select(Axis(0), &keep);
         self.classifier_score = self.classifier_score.select(Axis(0), &keep);
+        self.psm_metadata.spec_id = keep.iter().map(|&i| self.psm_metadata.spec_id[i].clone()).collect();
+        self.psm_metadata.file_id = keep.iter().map(|&i| self.psm_metadata.file_id[i]).collect();
+        if let Some(peptide) = &self.psm_metadata.peptide {
+            self.psm_metadata.peptide = Some(keep.iter().map(|&i| peptide[i].clone()).collect());
+        }
+        if let Some(proteins) = &self.psm_metadata.proteins {
+            self.psm_metadata.proteins = Some(keep.iter().map(|&i| proteins[i].clone()).collect());
+        }
+    }
+
+    /// Assign every PSM to one of `n_folds` disjoint cross-validation folds.
+    ///
+    /// PSMs are first grouped according to `grouping`, and whole groups are assigned to folds,
+    /// so that PSMs of the same spectrum (or peptide) never end up in both the training and the
+    /// test set. Groups are shuffled with a seeded RNG and assigned greedily to the fold with the
+    /// fewest PSMs, which makes the split deterministic and balanced.
+    ///
+    /// If `grouping` is [`FoldGrouping::Peptide`] but no peptide sequences are available in the
+    /// metadata, PSMs are grouped by spectrum only.
+    ///
+    /// # Arguments
+    ///
+    /// * `n_folds` - The number of folds
+    /// * `grouping` - How PSMs are grouped
+    /// * `seed` - Seed of the RNG used to shuffle the groups
+    ///
+    /// # Returns
+    ///
+    /// The fold index of each PSM
+    pub fn assign_folds(&self, n_folds: usize, grouping: FoldGrouping, seed: u64) -> Vec<usize> {
+        let n_samples = self.x.nrows();
+        let metadata = &self.psm_metadata;
+
+        // Union-find over PSMs, linking PSMs that share a spectrum (and a peptide if requested)
+        fn find(parent: &mut Vec<usize>, i: usize) -> usize {
+            let mut root = i;
+            while parent[root] != root {
+                root = parent[root];
+            }
+            let mut i = i;
+            while parent[i] != root {
+                let next = parent[i];
+                parent[i] = root;
+                i = next;
+            }
+            root
+        }
+
+        let mut parent: Vec<usize> = (0..n_samples).collect();
+        let mut link = |keys: Vec<String>| {
+            let mut first_seen: HashMap<String, usize> = HashMap::new();
+            for (i, key) in keys.into_iter().enumerate() {
+                match first_seen.get(&key) {
+                    Some(&j) => {
+                        let (ri, rj) = (find(&mut parent, i), find(&mut parent, j));
+                        if ri != rj {
+                            parent[ri.max(rj)] = ri.min(rj);
+                        }
+                    }
+                    None => {
+                        first_seen.insert(key, i);
+                    }
+                }
+            }
+        };
+
+        link(
+            (0..n_samples)
+                .map(|i| format!("{}\t{}", metadata.file_id[i], metadata.spec_id[i]))
+                .collect(),
+        );
+        if grouping == FoldGrouping::Peptide {
+            match &metadata.peptide {
+                Some(peptide) => link(peptide.clone()),
+                None => log::warn!("No peptide sequences in PSM metadata, grouping folds by spectrum only."),
+            }
+        }
+
+        // Collect the groups in order of first appearance, then shuffle them deterministically
+        let mut group_index: HashMap<usize, usize> = HashMap::new();
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        for i in 0..n_samples {
+            let root = find(&mut parent, i);
+            let idx = *group_index.entry(root).or_insert_with(|| {
+                groups.push(Vec::new());
+                groups.len() - 1
+            });
+            groups[idx].push(i);
+        }
+        let mut rng = StdRng::seed_from_u64(seed);
+        groups.shuffle(&mut rng);
+
+        let n_folds = n_folds.max(1);
+        let mut fold_sizes = vec![0usize; n_folds];
+        let mut folds = vec![0usize; n_samples];
+        for group in groups {
+            let fold = (0..n_folds).min_by_key(|&k| fold_sizes[k]).unwrap_or(0);
+            fold_sizes[fold] += group.len();
+            for i in group {
+                folds[i] = fold;
+            }
+        }
+
+        log::trace!("Assigned PSMs to {} folds of sizes {:?}", n_folds, fold_sizes);
+
+        folds
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn toy_experiment() -> Experiment {
+        // 4 spectra with 3 candidate PSMs each, peptides shared across spectra 0/1 and 2/3
+        let n = 12;
+        let x = Array2::from_shape_fn((n, 2), |(i, j)| (i * 2 + j) as f32);
+        let y = Array1::from_iter((0..n).map(|i| if i % 3 == 2 { -1 } else { 1 }));
+        let metadata = PsmMetadata {
+            spec_id: (0..n).map(|i| format!("scan={}", i / 3)).collect(),
+            file_id: vec![0; n],
+            feature_names: vec!["a".to_string(), "b".to_string()],
+            peptide: Some(
+                (0..n)
+                    .map(|i| if i % 3 == 0 { format!("SHARED{}", i / 6) } else { format!("PEPTIDE{}", i) })
+                    .collect(),
+            ),
+            proteins: None,
+        };
+        Experiment::new(x, y, metadata).unwrap()
+    }
+
+    #[test]
+    fn test_assign_folds_groups_spectra() {
+        let experiment = toy_experiment();
+        let folds = experiment.assign_folds(2, FoldGrouping::Spectrum, 42);
+
+        assert_eq!(folds.len(), 12);
+        for spectrum in 0..4 {
+            let fold = folds[spectrum * 3];
+            assert!((0..3).all(|k| folds[spectrum * 3 + k] == fold));
+        }
+        // Deterministic for a given seed
+        assert_eq!(folds, experiment.assign_folds(2, FoldGrouping::Spectrum, 42));
+    }
+
+    #[test]
+    fn test_assign_folds_groups_peptides() {
+        let experiment = toy_experiment();
+        let folds = experiment.assign_folds(2, FoldGrouping::Peptide, 7);
+
+        // Spectra 0 and 1 share a peptide, as do spectra 2 and 3
+        assert!((0..6).all(|i| folds[i] == folds[0]));
+        assert!((6..12).all(|i| folds[i] == folds[6]));
+        assert_ne!(folds[0], folds[6]);
+    }
+
+    #[test]
+    fn test_update_labels_keeps_targets_after_relabeling() {
+        let mut experiment = toy_experiment();
+        // No target passes at 0% FDR, so all targets become unlabeled
+        let scores = Array1::from_iter((0..12).map(|i| -(i as f32)));
+        experiment.y = experiment.update_labels(&scores, 0.0, true).unwrap();
+        assert_eq!(experiment.y.iter().filter(|&&l| l == 0).count(), 8);
+
+        // Relabeling must not turn unlabeled targets into decoys
+        let labels = experiment.update_labels(&scores, 1.0, true).unwrap();
+        assert_eq!(labels, experiment.is_target.mapv(|t| if t { 1 } else { -1 }));
+    }
+}
@@ -0,0 +1,429 @@
+//! Identification yields and diagnostics of a rescoring run.
+//!
+//! Yields are computed with target-decoy competition at the PSM, peptide and protein level.
+//! Peptide and protein scores are the best score of their PSMs, so they require the optional
+//! `peptide` and `proteins` fields of [`PsmMetadata`].
+
+use std::collections::HashMap;
+
+use ndarray::Array1;
+use serde::{Deserialize, Serialize};
+
+use crate::data_handling::PsmMetadata;
+use crate::error::TdcError;
+use crate::psm_scorer::SemiSupervisedLearner;
+use crate::stats::tdc;
+
+/// Number of identifications at a q-value threshold
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IdentificationCounts {
+    pub qvalue: f32,
+    pub psms: usize,
+    /// `None` if no peptide sequences are available
+    pub peptides: Option<usize>,
+    /// `None` if no protein accessions are available
+    pub proteins: Option<usize>,
+}
+
+/// Warning raised by [`RescoringDiagnostics`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum DiagnosticFlag {
+    /// The learned score identifies fewer PSMs than the best single feature
+    WorseThanBestFeature { learned: usize, best_feature: usize },
+    /// A larger fraction of decoys score above the median target with the learned score than
+    /// with the best single feature, i.e. decoys look more like targets after rescoring
+    DecoyScoreInflation { learned: f32, best_feature: f32 },
+    /// The held-out yield is much lower than the yield of the models on their own training PSMs
+    Overfitting { held_out: usize, in_sample: usize },
+}
+
+/// Configuration of the rescoring diagnostics
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiagnosticsConfig {
+    /// q-value thresholds at which identifications are counted
+    pub qvalue_thresholds: Vec<f32>,
+    /// q-value threshold used to compare scores and raise flags
+    pub eval_fdr: f32,
+    /// Flag overfitting if the held-out yield is below `(1 - overfitting_tolerance)` times the in-sample yield
+    pub overfitting_tolerance: f32,
+    /// Flag decoy score inflation if the fraction of decoys above the median target increases by more than this
+    pub decoy_inflation_tolerance: f32,
+}
+
+impl Default for DiagnosticsConfig {
+    fn default() -> Self {
+        DiagnosticsConfig {
+            qvalue_thresholds: vec![0.001, 0.01, 0.05, 0.1],
+            eval_fdr: 0.01,
+            overfitting_tolerance: 0.2,
+            decoy_inflation_tolerance: 0.05,
+        }
+    }
+}
+
+/// Count the targets at each q-value threshold, using the best PSM per group
+///
+/// PSMs are grouped by key, and also by target/decoy if `by_label` is true. A group without
+/// `by_label` takes the label of its best PSM, so a decoy outscoring its target wins the group.
+/// PSMs without a key are skipped.
+fn count_at_thresholds<'a>(
+    scores: &Array1<f32>,
+    labels: &Array1<i32>,
+    keys: impl Iterator<Item = Option<&'a str>>,
+    by_label: bool,
+    thresholds: &[f32],
+) -> Result<Vec<usize>, TdcError> {
+    let mut best: HashMap<(&str, Option<bool>), (f32, bool)> = HashMap::new();
+    for ((key, &score), &label) in keys.zip(scores.iter()).zip(labels.iter()) {
+        if let Some(key) = key {
+            let is_target = label == 1;
+            let group = (key, if by_label { Some(is_target) } else { None });
+            let entry = best.entry(group).or_insert((f32::NEG_INFINITY, is_target));
+            if score > entry.0 {
+                *entry = (score, is_target);
+            }
+        }
+    }
+    if best.is_empty() {
+        return Ok(vec![0; thresholds.len()]);
+    }
+
+    let (group_scores, group_targets): (Vec<f32>, Vec<bool>) = best.into_values().unzip();
+    let group_targets = Array1::from(group_targets);
+    let qvals = tdc(&Array1::from(group_scores), &group_targets, true)?;
+
+    Ok(thresholds
+        .iter()
+        .map(|&threshold| {
+            qvals
+                .iter()
+                .zip(group_targets.iter())
+                .filter(|(&q, &t)| t && q <= threshold)
+                .count()
+        })
+        .collect())
+}
+
+/// Compute the number of PSMs, peptides and proteins at q-value thresholds
+///
+/// Only the best-scoring PSM of each spectrum is counted, whether it is a target or a decoy.
+/// Target and decoy peptides and proteins are counted separately. Proteins are counted from PSMs
+/// of peptides mapping to a single protein.
+///
+/// # Arguments
+///
+/// * `scores` - The PSM scores, higher is better
+/// * `labels` - The labels, 1 for targets and -1 for decoys
+/// * `psm_metadata` - The PSM metadata, peptides and proteins are counted if available
+/// * `thresholds` - The q-value thresholds
+///
+/// # Returns
+///
+/// The identification counts at each threshold
+pub fn identification_yield(
+    scores: &Array1<f32>,
+    labels: &Array1<i32>,
+    psm_metadata: &PsmMetadata,
+    thresholds: &[f32],
+) -> Result<Vec<IdentificationCounts>, TdcError> {
+    if scores.len() != labels.len() {
+        return Err(TdcError::LengthMismatch);
+    }
+
+    let psm_keys: Vec<String> = psm_metadata
+        .file_id
+        .iter()
+        .zip(psm_metadata.spec_id.iter())
+        .map(|(file_id, spec_id)| format!("{}\t{}", file_id, spec_id))
+        .collect();
+    // Without metadata for every PSM, each PSM is counted on its own
+    let psms = if psm_keys.len() == scores.len() {
+        count_at_thresholds(scores, labels, psm_keys.iter().map(|k| Some(k.as_str())), false, thresholds)?
+    } else {
+        let keys: Vec<String> = (0..scores.len()).map(|i| i.to_string()).collect();
+        count_at_thresholds(scores, labels, keys.iter().map(|k| Some(k.as_str())), false, thresholds)?
+    };
+
+    let peptides = match &psm_metadata.peptide {
+        Some(peptide) => Some(count_at_thresholds(
+            scores,
+            labels,
+            peptide.iter().map(|p| Some(p.as_str())),
+            true,
+            thresholds,
+        )?),
+        None => None,
+    };
+
+    // Protein groups are separated by ';'
+    let proteins = match &psm_metadata.proteins {
+        Some(proteins) => Some(count_at_thresholds(
+            scores,
+            labels,
+            proteins
+                .iter()
+                .map(|p| if p.is_empty() || p.contains(';') { None } else { Some(p.as_str()) }),
+            true,
+            thresholds,
+        )?),
+        None => None,
+    };
+
+    Ok(thresholds
+        .iter()
+        .enumerate()
+        .map(|(i, &qvalue)| IdentificationCounts {
+            qvalue,
+            psms: psms[i],
+            peptides: peptides.as_ref().map(|p| p[i]),
+            proteins: proteins.as_ref().map(|p| p[i]),
+        })
+        .collect())
+}
+
+/// Fraction of decoys scoring above the median target score
+fn decoys_above_target_median(scores: &Array1<f32>, labels: &Array1<i32>) -> f32 {
+    let mut target_scores: Vec<f32> = scores
+        .iter()
+        .zip(labels.iter())
+        .filter(|(s, &l)| l == 1 && s.is_finite())
+        .map(|(&s, _)| s)
+        .collect();
+    let decoy_scores: Vec<f32> = scores
+        .iter()
+        .zip(labels.iter())
+        .filter(|(s, &l)| l != 1 && s.is_finite())
+        .map(|(&s, _)| s)
+        .collect();
+    if target_scores.is_empty() || decoy_scores.is_empty() {
+        return 0.0;
+    }
+    target_scores.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let median = target_scores[target_scores.len() / 2];
+    decoy_scores.iter().filter(|&&s| s > median).count() as f32 / decoy_scores.len() as f32
+}
+
+/// Diagnostics of a rescoring run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RescoringDiagnostics {
+    /// Identifications with the learned score
+    pub learned: Vec<IdentificationCounts>,
+    /// Name and identifications of the best single feature, if given
+    pub best_feature: Option<(String, Vec<IdentificationCounts>)>,
+    /// Fraction of decoys scoring above the median target with the learned score
+    pub learned_decoy_fraction: f32,
+    /// Fraction of decoys scoring above the median target with the best single feature
+    pub best_feature_decoy_fraction: Option<f32>,
+    /// Target PSMs at `eval_fdr` among training PSMs: (held-out scores, in-sample scores)
+    pub held_out_vs_in_sample: Option<(usize, usize)>,
+    /// Warnings about suspicious results
+    pub flags: Vec<DiagnosticFlag>,
+}
+
+impl RescoringDiagnostics {
+    /// Compute the diagnostics of a rescoring run
+    ///
+    /// # Arguments
+    ///
+    /// * `scores` - The learned (held-out) scores, higher is better
+    /// * `labels` - The labels, 1 for targets and -1 for decoys
+    /// * `psm_metadata` - The PSM metadata
+    /// * `best_feature` - The name and scores of the best single feature, oriented so that higher is better
+    /// * `in_sample_scores` - Scores of the PSMs from the models trained on them, NaN for PSMs not used for training
+    /// * `config` - The diagnostics configuration
+    pub fn new(
+        scores: &Array1<f32>,
+        labels: &Array1<i32>,
+        psm_metadata: &PsmMetadata,
+        best_feature: Option<(&str, &Array1<f32>)>,
+        in_sample_scores: Option<&Array1<f32>>,
+        config: &DiagnosticsConfig,
+    ) -> Result<Self, TdcError> {
+        let mut thresholds = config.qvalue_thresholds.clone();
+        if !thresholds.contains(&config.eval_fdr) {
+            thresholds.push(config.eval_fdr);
+        }
+        let eval_idx = thresholds.iter().position(|&t| t == config.eval_fdr).unwrap_or(0);
+
+        let learned = identification_yield(scores, labels, psm_metadata, &thresholds)?;
+        let learned_decoy_fraction = decoys_above_target_median(scores, labels);
+        let mut flags = Vec::new();
+
+        let mut best = None;
+        let mut best_feature_decoy_fraction = None;
+        if let Some((name, feature_scores)) = best_feature {
+            let counts = identification_yield(feature_scores, labels, psm_metadata, &thresholds)?;
+            let decoy_fraction = decoys_above_target_median(feature_scores, labels);
+
+            if learned[eval_idx].psms < counts[eval_idx].psms {
+                flags.push(DiagnosticFlag::WorseThanBestFeature {
+                    learned: learned[eval_idx].psms,
+                    best_feature: counts[eval_idx].psms,
+                });
+            }
+            if learned_decoy_fraction > decoy_fraction + config.decoy_inflation_tolerance {
+                flags.push(DiagnosticFlag::DecoyScoreInflation {
+                    learned: learned_decoy_fraction,
+                    best_feature: decoy_fraction,
+                });
+            }
+
+            best = Some((name.to_string(), counts));
+            best_feature_decoy_fraction = Some(decoy_fraction);
+        }
+
+        // Compare held-out and in-sample yields on the PSMs used for training
+        let mut held_out_vs_in_sample = None;
+        if let Some(in_sample) = in_sample_scores {
+            let rows: Vec<usize> = (0..in_sample.len()).filter(|&i| in_sample[i].is_finite()).collect();
+            if !rows.is_empty() {
+                let subset_labels = labels.select(ndarray::Axis(0), &rows);
+                let keys: Vec<String> = rows.iter().map(|i| i.to_string()).collect();
+                let held_out = count_at_thresholds(
+                    &scores.select(ndarray::Axis(0), &rows),
+                    &subset_labels,
+                    keys.iter().map(|k| Some(k.as_str())),
+                    false,
+                    &[config.eval_fdr],
+                )?[0];
+                let in_sample = count_at_thresholds(
+                    &in_sample.select(ndarray::Axis(0), &rows),
+                    &subset_labels,
+                    keys.iter().map(|k| Some(k.as_str())),
+                    false,
+                    &[config.eval_fdr],
+                )?[0];
+
+                if (held_out as f32) < (1.0 - config.overfitting_tolerance) * in_sample as f32 {
+                    flags.push(DiagnosticFlag::Overfitting { held_out, in_sample });
+                }
+                held_out_vs_in_sample = Some((held_out, in_sample));
+            }
+        }
+
+        for flag in &flags {
+            log::warn!("Rescoring diagnostics: {:?}", flag);
+        }
+
+        Ok(RescoringDiagnostics {
+            learned,
+            best_feature: best,
+            learned_decoy_fraction,
+            best_feature_decoy_fraction,
+            held_out_vs_in_sample,
+            flags,
+        })
+    }
+
+    /// Compute the diagnostics of a fitted learner
+    ///
+    /// The best single feature and the in-sample scores are taken from the learner's
+    /// [`FitHistory`](crate::psm_scorer::FitHistory).
+    ///
+    /// # Arguments
+    ///
+    /// * `learner` - The fitted learner
+    /// * `scores` - The scores returned by `fit`
+    /// * `labels` - The labels passed to `fit`, 1 for targets and -1 for decoys
+    /// * `psm_metadata` - The PSM metadata passed to `fit`
+    /// * `config` - The diagnostics configuration
+    pub fn from_learner(
+        learner: &SemiSupervisedLearner,
+        scores: &Array1<f32>,
+        labels: &Array1<i32>,
+        psm_metadata: &PsmMetadata,
+        config: &DiagnosticsConfig,
+    ) -> Result<Self, TdcError> {
+        let history = learner.history();
+        let best_feature = history
+            .initial_scores
+            .as_ref()
+            .map(|s| (history.initial_feature.as_deref().unwrap_or("best feature"), s));
+        Self::new(
+            scores,
+            labels,
+            psm_metadata,
+            best_feature,
+            history.in_sample_scores.as_ref(),
+            config,
+        )
+    }
+
+    /// Identifications with the learned score at a q-value threshold, if it was computed
+    pub fn learned_at(&self, qvalue: f32) -> Option<&IdentificationCounts> {
+        self.learned.iter().find(|c| c.qvalue == qvalue)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identification_yield_best_psm_per_spectrum() {
+        // Four spectra, the decoy of spectrum 2 outscores its target
+        let scores = Array1::from(vec![10.0, 9.0, 8.0, 9.5, 1.0]);
+        let labels = Array1::from(vec![1, 1, 1, -1, 1]);
+        let metadata = PsmMetadata {
+            spec_id: ["0", "1", "2", "2", "3"].iter().map(|s| s.to_string()).collect(),
+            file_id: vec![0; 5],
+            feature_names: Vec::new(),
+            peptide: None,
+            proteins: None,
+        };
+
+        // The target of spectrum 2 is not counted
+        let counts = identification_yield(&scores, &labels, &metadata, &[1.0]).unwrap();
+        assert_eq!(counts[0].psms, 3);
+    }
+
+    #[test]
+    fn test_rescoring_diagnostics() {
+        // 200 targets with two PSMs per peptide and 200 decoys
+        let n = 400;
+        let labels = Array1::from_iter((0..n).map(|i| if i < 200 { 1 } else { -1 }));
+        let learned = Array1::from_iter((0..n).map(|i| if i < 200 { 10.0 + i as f32 } else { (i - 200) as f32 / 100.0 }));
+        // The best feature mixes targets and decoys
+        let feature = Array1::from_iter((0..n).map(|i| (i % 200) as f32));
+        let metadata = PsmMetadata {
+            spec_id: (0..n).map(|i| format!("scan={}", i)).collect(),
+            file_id: vec![0; n],
+            feature_names: vec!["feature".to_string()],
+            peptide: Some((0..n).map(|i| if i < 200 { format!("PEP{}", i / 2) } else { format!("DECOY{}", i / 2) }).collect()),
+            proteins: Some((0..n).map(|i| if i < 200 { format!("PROT{}", i / 20) } else { format!("DECOY_PROT{}", i / 20) }).collect()),
+        };
+
+        let diagnostics = RescoringDiagnostics::new(
+            &learned,
+            &labels,
+            &metadata,
+            Some(("feature", &feature)),
+            None,
+            &DiagnosticsConfig::default(),
+        )
+        .unwrap();
+
+        let counts = diagnostics.learned_at(0.01).unwrap();
+        assert_eq!(counts.psms, 200);
+        assert_eq!(counts.peptides, Some(100));
+        // With 10 target proteins and no decoys, the lowest protein-level q-value is 1/10
+        assert_eq!(counts.proteins, Some(0));
+        assert_eq!(diagnostics.learned_at(0.1).unwrap().proteins, Some(10));
+        assert!(diagnostics.best_feature.as_ref().unwrap().1[1].psms < counts.psms);
+        assert!(diagnostics.flags.is_empty());
+
+        // Swapping the scores flags the learned score as worse and decoy-inflated
+        let diagnostics = RescoringDiagnostics::new(
+            &feature,
+            &labels,
+            &metadata,
+            Some(("feature", &learned)),
+            Some(&learned),
+            &DiagnosticsConfig::default(),
+        )
+        .unwrap();
+        assert!(diagnostics.flags.iter().any(|f| matches!(f, DiagnosticFlag::WorseThanBestFeature { .. })));
+        assert!(diagnostics.flags.iter().any(|f| matches!(f, DiagnosticFlag::DecoyScoreInflation { .. })));
+        assert!(diagnostics.flags.iter().any(|f| matches!(f, DiagnosticFlag::Overfitting { .. })));
+    }
+}
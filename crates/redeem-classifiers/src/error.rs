@@ -5,6 +5,9 @@ use std::fmt;
 pub enum ExperimentError {
     DimensionMismatch(usize, usize), // (expected, actual)
     SingleClass(bool), // true if only targets, false if only decoys
+    NoPsmsBelowFdr(f32), // FDR threshold that no PSM passed
+    NoFeatures,
+    Tdc(TdcError),
 }
 
 impl fmt::Display for ExperimentError {
@@ -20,11 +23,29 @@ impl fmt::Display for ExperimentError {
                     write!(f, "Only decoy class present in the dataset")
                 }
             }
+            ExperimentError::NoPsmsBelowFdr(fdr) => {
+                write!(f, "No PSMs found below the FDR threshold {}", fdr)
+            }
+            ExperimentError::NoFeatures => write!(f, "The dataset does not contain any feature"),
+            ExperimentError::Tdc(e) => write!(f, "Target-decoy competition failed: {}", e),
         }
     }
 }
 
-impl Error for ExperimentError {}
+impl Error for ExperimentError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ExperimentError::Tdc(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<TdcError> for ExperimentError {
+    fn from(e: TdcError) -> Self {
+        ExperimentError::Tdc(e)
+    }
+}
 
 /// Custom error type for TDC calculation failures
 #[derive(Debug)]
@@ -42,4 +63,26 @@ impl fmt::Display for TdcError {
     }
 }
 
-impl Error for TdcError {}
\ No newline at end of file
+impl Error for TdcError {}
+
+/// Custom error type for classifier model failures
+#[derive(Debug)]
+pub enum ModelError {
+    InvalidParams(String), // Description of the unexpected parameters
+    NotFitted,
+    Training(String), // Error raised by the underlying library while training
+    Prediction(String), // Error raised by the underlying library while predicting
+}
+
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ModelError::InvalidParams(msg) => write!(f, "Invalid model parameters: {}", msg),
+            ModelError::NotFitted => write!(f, "Model has not been fitted"),
+            ModelError::Training(msg) => write!(f, "Model training failed: {}", msg),
+            ModelError::Prediction(msg) => write!(f, "Model prediction failed: {}", msg),
+        }
+    }
+}
+
+impl Error for ModelError {}
@@ -0,0 +1,222 @@
+//! Feature importance for the semi-supervised models.
+//!
+//! Model-specific importances are reported through [`SemiSupervisedModel::feature_importance`]
+//! (gain and split counts for tree ensembles, weights for linear models). Permutation importance
+//! works with any model: each feature is shuffled in turn and the drop in the number of targets
+//! identified at a given FDR is measured.
+
+use std::fmt;
+
+use ndarray::{Array1, Array2};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+
+use crate::error::ModelError;
+use crate::psm_scorer::SemiSupervisedModel;
+use crate::stats::tdc;
+
+/// Kind of feature importance
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ImportanceType {
+    /// Total impurity decrease of the splits on each feature (tree models)
+    Gain,
+    /// Number of splits on each feature (tree models)
+    SplitCount,
+    /// Absolute weight of each feature (linear models)
+    Weight,
+    /// Decrease in targets identified at a given FDR when the feature is shuffled (any model)
+    Permutation,
+}
+
+impl fmt::Display for ImportanceType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportanceType::Gain => write!(f, "Gain"),
+            ImportanceType::SplitCount => write!(f, "Split count"),
+            ImportanceType::Weight => write!(f, "Weight"),
+            ImportanceType::Permutation => write!(f, "Permutation"),
+        }
+    }
+}
+
+/// Feature importances keyed by feature name
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeatureImportance {
+    pub importance_type: ImportanceType,
+    pub feature_names: Vec<String>,
+    pub values: Vec<f32>,
+}
+
+impl FeatureImportance {
+    /// Create a new FeatureImportance
+    ///
+    /// If fewer feature names than values are given, the missing names are set to `feature_{i}`.
+    pub fn new(importance_type: ImportanceType, feature_names: &[String], values: Vec<f32>) -> Self {
+        let feature_names = (0..values.len())
+            .map(|i| feature_names.get(i).cloned().unwrap_or_else(|| format!("feature_{}", i)))
+            .collect();
+        FeatureImportance {
+            importance_type,
+            feature_names,
+            values,
+        }
+    }
+
+    /// Features and importances sorted from most to least important
+    pub fn sorted(&self) -> Vec<(String, f32)> {
+        let mut sorted: Vec<(String, f32)> = self
+            .feature_names
+            .iter()
+            .cloned()
+            .zip(self.values.iter().cloned())
+            .collect();
+        sorted.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        sorted
+    }
+
+    /// Importance of a feature by name
+    pub fn get(&self, feature_name: &str) -> Option<f32> {
+        self.feature_names
+            .iter()
+            .position(|name| name == feature_name)
+            .map(|i| self.values[i])
+    }
+}
+
+/// Number of targets passing the FDR threshold when ranking by `scores`
+fn num_passing(scores: Vec<f32>, targets: &Array1<bool>, eval_fdr: f32) -> Result<usize, ModelError> {
+    let qvals = tdc(&Array1::from(scores), targets, true).map_err(|e| ModelError::Prediction(e.to_string()))?;
+    Ok(qvals
+        .iter()
+        .zip(targets.iter())
+        .filter(|(&q, &t)| t && q <= eval_fdr)
+        .count())
+}
+
+/// Compute permutation importance for any fitted model
+///
+/// # Arguments
+///
+/// * `model` - The fitted model
+/// * `x` - The features, shape (n_samples, n_features)
+/// * `y` - The labels, 1 for targets and -1 for decoys
+/// * `eval_fdr` - The FDR threshold at which targets are counted
+/// * `n_repeats` - The number of times each feature is shuffled
+/// * `seed` - The seed used to shuffle the features
+///
+/// # Returns
+///
+/// The mean decrease in the number of targets at `eval_fdr` for each feature
+pub fn permutation_importance(
+    model: &dyn SemiSupervisedModel,
+    x: &Array2<f32>,
+    y: &[i32],
+    eval_fdr: f32,
+    n_repeats: usize,
+    seed: u64,
+) -> Result<Vec<f32>, ModelError> {
+    permutation_importance_with(|x| model.predict(x), x, y, eval_fdr, n_repeats, seed)
+}
+
+/// Compute permutation importance for any scoring function, e.g. an ensemble of models
+///
+/// See [`permutation_importance`] for the arguments, `predict` returns one score per row of its input.
+pub fn permutation_importance_with<F>(
+    predict: F,
+    x: &Array2<f32>,
+    y: &[i32],
+    eval_fdr: f32,
+    n_repeats: usize,
+    seed: u64,
+) -> Result<Vec<f32>, ModelError>
+where
+    F: Fn(&Array2<f32>) -> Result<Vec<f32>, ModelError>,
+{
+    let targets: Array1<bool> = y.iter().map(|&l| l == 1).collect();
+    let baseline = num_passing(predict(x)?, &targets, eval_fdr)?;
+    let n_repeats = n_repeats.max(1);
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut importances = Vec::with_capacity(x.ncols());
+    let mut x_permuted = x.to_owned();
+
+    for j in 0..x.ncols() {
+        let mut column: Vec<f32> = x.column(j).to_vec();
+        let mut total_drop = 0.0;
+        for _ in 0..n_repeats {
+            column.shuffle(&mut rng);
+            x_permuted.column_mut(j).assign(&Array1::from(column.clone()));
+            let passing = num_passing(predict(&x_permuted)?, &targets, eval_fdr)?;
+            total_drop += baseline as f32 - passing as f32;
+        }
+        x_permuted.column_mut(j).assign(&x.column(j));
+        importances.push(total_drop / n_repeats as f32);
+    }
+
+    log::debug!("Permutation importance baseline: {} targets at {} FDR", baseline, eval_fdr);
+
+    Ok(importances)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::random_forest::RandomForestClassifier;
+    use crate::models::utils::{ModelParams, ModelType};
+
+    fn toy_data() -> (Array2<f32>, Vec<i32>) {
+        // Feature 1 separates targets and decoys, the other features are noise
+        let n = 200;
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut noise: Vec<f32> = (0..n).map(|i| (i % 17) as f32).collect();
+        let mut x = Array2::zeros((n, 3));
+        let mut y = Vec::with_capacity(n);
+        noise.shuffle(&mut rng);
+        for i in 0..n {
+            let is_target = i % 2 == 0;
+            x[[i, 0]] = noise[i];
+            x[[i, 1]] = if is_target { 1.0 + (i % 5) as f32 } else { -1.0 - (i % 5) as f32 };
+            x[[i, 2]] = (i % 3) as f32;
+            y.push(if is_target { 1 } else { -1 });
+        }
+        (x, y)
+    }
+
+    #[test]
+    fn test_feature_importance_random_forest() {
+        let (x, y) = toy_data();
+        let params = ModelParams {
+            learning_rate: 0.1,
+            model_type: ModelType::RandomForest {
+                n_trees: 20,
+                max_depth: 4,
+                min_samples_leaf: 1,
+                max_features: 1.0,
+                bootstrap: true,
+                seed: 42,
+            },
+        };
+        let mut model = RandomForestClassifier::new(params);
+        model.fit(&x, &y, None, None).unwrap();
+
+        let names = vec!["noise".to_string(), "signal".to_string(), "cycle".to_string()];
+
+        let gain = FeatureImportance::new(
+            ImportanceType::Gain,
+            &names,
+            model.feature_importance(ImportanceType::Gain).unwrap(),
+        );
+        assert_eq!(gain.sorted()[0].0, "signal");
+        assert!(model.feature_importance(ImportanceType::Weight).is_none());
+
+        let permutation = FeatureImportance::new(
+            ImportanceType::Permutation,
+            &names,
+            permutation_importance(&model, &x, &y, 0.05, 3, 42).unwrap(),
+        );
+        assert_eq!(permutation.sorted()[0].0, "signal");
+        assert!(permutation.get("signal").unwrap() > 0.0);
+    }
+}
@@ -0,0 +1,340 @@
+//! Univariate feature selection for target/decoy classification.
+//!
+//! The scoring functions take the feature matrix used by the learner (`f32`) and target/decoy
+//! labels (1 for targets, -1 for decoys), and return one score per feature where higher is better.
+//! Non-finite feature values are ignored when scoring a feature.
+
+use std::collections::HashMap;
+
+use ndarray::{Array1, Array2};
+use serde::{Deserialize, Serialize};
+use statrs::distribution::{ContinuousCDF, FisherSnedecor};
+
+use crate::stats::tdc;
+
+/// Finite values of a feature column, paired with their labels
+fn finite_column(x: &Array2<f32>, y: &Array1<i32>, col: usize) -> Vec<(f64, i32)> {
+    x.column(col)
+        .iter()
+        .zip(y.iter())
+        .filter(|(v, _)| v.is_finite())
+        .map(|(&v, &l)| (v as f64, l))
+        .collect()
+}
+
+/// ANOVA F-test between the classes for each feature.
+///
+/// # Arguments
+///
+/// * `x` - The features, shape (n_samples, n_features)
+/// * `y` - The class labels, shape (n_samples,)
+///
+/// # Returns
+///
+/// A tuple containing the F-statistics and the associated p-values for each feature. Features
+/// with no variance within or between classes get an F-statistic of 0 and a p-value of 1.
+pub fn f_classif(x: &Array2<f32>, y: &Array1<i32>) -> (Array1<f64>, Array1<f64>) {
+    let mut f_statistic = Array1::zeros(x.ncols());
+    let mut p_values = Array1::ones(x.ncols());
+
+    for col in 0..x.ncols() {
+        let values = finite_column(x, y, col);
+        let n = values.len() as f64;
+
+        // (sum, count) per class
+        let mut groups: HashMap<i32, (f64, f64)> = HashMap::new();
+        for &(v, l) in &values {
+            let entry = groups.entry(l).or_insert((0.0, 0.0));
+            entry.0 += v;
+            entry.1 += 1.0;
+        }
+        let k = groups.len() as f64;
+        if k < 2.0 || n <= k {
+            continue;
+        }
+
+        let grand_mean = values.iter().map(|(v, _)| v).sum::<f64>() / n;
+        let ss_between: f64 = groups
+            .values()
+            .map(|(sum, count)| count * (sum / count - grand_mean).powi(2))
+            .sum();
+        let ss_within: f64 = values
+            .iter()
+            .map(|(v, l)| {
+                let (sum, count) = groups[l];
+                (v - sum / count).powi(2)
+            })
+            .sum();
+
+        let (df_between, df_within) = (k - 1.0, n - k);
+        if ss_within <= f64::EPSILON {
+            if ss_between > f64::EPSILON {
+                f_statistic[col] = f64::MAX;
+                p_values[col] = 0.0;
+            }
+            continue;
+        }
+
+        let f = (ss_between / df_between) / (ss_within / df_within);
+        f_statistic[col] = f;
+        if let Ok(f_dist) = FisherSnedecor::new(df_between, df_within) {
+            p_values[col] = 1.0 - f_dist.cdf(f);
+        }
+    }
+
+    (f_statistic, p_values)
+}
+
+/// Mutual information between each feature and the class labels.
+///
+/// Features are discretized into `n_bins` equal-frequency bins, and the mutual information
+/// between the bins and the classes is computed in nats.
+///
+/// # Arguments
+///
+/// * `x` - The features, shape (n_samples, n_features)
+/// * `y` - The class labels, shape (n_samples,)
+/// * `n_bins` - The number of bins used to discretize each feature
+///
+/// # Returns
+///
+/// The mutual information for each feature
+pub fn mutual_info_classif(x: &Array2<f32>, y: &Array1<i32>, n_bins: usize) -> Array1<f64> {
+    let n_bins = n_bins.max(2);
+    let mut mutual_info = Array1::zeros(x.ncols());
+
+    for col in 0..x.ncols() {
+        let mut values = finite_column(x, y, col);
+        let n = values.len();
+        if n == 0 {
+            continue;
+        }
+        values.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+
+        // Assign equal-frequency bins, keeping tied values in the same bin
+        let mut joint: HashMap<(usize, i32), f64> = HashMap::new();
+        let mut bin_counts: HashMap<usize, f64> = HashMap::new();
+        let mut class_counts: HashMap<i32, f64> = HashMap::new();
+        let mut bin = 0;
+        for (i, &(v, l)) in values.iter().enumerate() {
+            if i > 0 && v != values[i - 1].0 {
+                bin = (i * n_bins / n).min(n_bins - 1);
+            }
+            *joint.entry((bin, l)).or_default() += 1.0;
+            *bin_counts.entry(bin).or_default() += 1.0;
+            *class_counts.entry(l).or_default() += 1.0;
+        }
+
+        let n = n as f64;
+        mutual_info[col] = joint
+            .iter()
+            .map(|(&(b, l), &count)| {
+                let p_joint = count / n;
+                p_joint * (p_joint / ((bin_counts[&b] / n) * (class_counts[&l] / n))).ln()
+            })
+            .sum::<f64>()
+            .max(0.0);
+    }
+
+    mutual_info
+}
+
+/// Area under the ROC curve of each feature used alone to separate targets from decoys.
+///
+/// The AUC is computed with the Mann-Whitney U statistic, using average ranks for ties. Since
+/// a feature can be better when lower, the returned score is `max(auc, 1 - auc)`.
+///
+/// # Arguments
+///
+/// * `x` - The features, shape (n_samples, n_features)
+/// * `y` - The labels, 1 for targets and -1 for decoys
+///
+/// # Returns
+///
+/// The direction-agnostic AUC for each feature, between 0.5 and 1
+pub fn auc_classif(x: &Array2<f32>, y: &Array1<i32>) -> Array1<f64> {
+    let mut auc = Array1::from_elem(x.ncols(), 0.5);
+
+    for col in 0..x.ncols() {
+        let mut values = finite_column(x, y, col);
+        values.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+
+        let n_pos = values.iter().filter(|(_, l)| *l == 1).count() as f64;
+        let n_neg = values.len() as f64 - n_pos;
+        if n_pos == 0.0 || n_neg == 0.0 {
+            continue;
+        }
+
+        let mut rank_sum_pos = 0.0;
+        let mut i = 0;
+        while i < values.len() {
+            let mut j = i;
+            while j + 1 < values.len() && values[j + 1].0 == values[i].0 {
+                j += 1;
+            }
+            let avg_rank = (i + j) as f64 / 2.0 + 1.0;
+            rank_sum_pos += values[i..=j].iter().filter(|(_, l)| *l == 1).count() as f64 * avg_rank;
+            i = j + 1;
+        }
+
+        let u = rank_sum_pos - n_pos * (n_pos + 1.0) / 2.0;
+        let a = u / (n_pos * n_neg);
+        auc[col] = a.max(1.0 - a);
+    }
+
+    auc
+}
+
+/// Number of targets identified at `eval_fdr` when ranking PSMs by each feature alone.
+///
+/// Both directions (higher and lower is better) are tried and the best yield is kept.
+/// Non-finite values are ranked last.
+///
+/// # Arguments
+///
+/// * `x` - The features, shape (n_samples, n_features)
+/// * `y` - The labels, 1 for targets and -1 for decoys
+/// * `eval_fdr` - The FDR threshold
+///
+/// # Returns
+///
+/// The number of targets passing the threshold for each feature
+pub fn tdc_yield_classif(x: &Array2<f32>, y: &Array1<i32>, eval_fdr: f32) -> Array1<f64> {
+    let targets: Array1<bool> = y.iter().map(|&l| l == 1).collect();
+
+    (0..x.ncols())
+        .map(|col| {
+            [true, false]
+                .iter()
+                .map(|&desc| {
+                    let worst = if desc { f32::MIN } else { f32::MAX };
+                    let scores = x.column(col).mapv(|v| if v.is_finite() { v } else { worst });
+                    match tdc(&scores, &targets, desc) {
+                        Ok(qvals) => qvals
+                            .iter()
+                            .zip(targets.iter())
+                            .filter(|(&q, &t)| t && q <= eval_fdr)
+                            .count(),
+                        Err(_) => 0,
+                    }
+                })
+                .max()
+                .unwrap_or(0) as f64
+        })
+        .collect()
+}
+
+/// Scoring function used to rank features for target/decoy classification
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ClassificationScorer {
+    /// ANOVA F-statistic, see [`f_classif`]
+    FClassif,
+    /// Mutual information with equal-frequency binning, see [`mutual_info_classif`]
+    MutualInfo { n_bins: usize },
+    /// Single-feature ROC AUC, see [`auc_classif`]
+    Auc,
+    /// Single-feature number of targets at an FDR threshold, see [`tdc_yield_classif`]
+    TdcYield { eval_fdr: f32 },
+}
+
+impl ClassificationScorer {
+    /// Score each feature, higher is better
+    pub fn score(&self, x: &Array2<f32>, y: &Array1<i32>) -> Array1<f64> {
+        match self {
+            ClassificationScorer::FClassif => f_classif(x, y).0,
+            ClassificationScorer::MutualInfo { n_bins } => mutual_info_classif(x, y, *n_bins),
+            ClassificationScorer::Auc => auc_classif(x, y),
+            ClassificationScorer::TdcYield { eval_fdr } => tdc_yield_classif(x, y, *eval_fdr),
+        }
+    }
+}
+
+/// A struct for selecting the k best features for target/decoy classification.
+///
+/// Unlike [`super::univariate_selection::SelectKBest`], which scores features with `f_regression`,
+/// this selector uses a [`ClassificationScorer`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SelectKBestClassif {
+    /// The number of top features to select.
+    pub k: usize,
+    /// The scoring function used to rank features.
+    pub scorer: ClassificationScorer,
+}
+
+impl SelectKBestClassif {
+    /// Creates a new SelectKBestClassif instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `k` - The number of top features to select.
+    /// * `scorer` - The scoring function used to rank features.
+    pub fn new(k: usize, scorer: ClassificationScorer) -> Self {
+        SelectKBestClassif { k, scorer }
+    }
+
+    /// Returns the indices of the k best features, in their original column order.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - The feature matrix (n_samples x n_features).
+    /// * `y` - The labels, 1 for targets and -1 for decoys.
+    pub fn fit(&self, x: &Array2<f32>, y: &Array1<i32>) -> Vec<usize> {
+        let scores = self.scorer.score(x, y);
+
+        let mut indices: Vec<usize> = (0..scores.len()).collect();
+        indices.sort_by(|&i, &j| scores[j].partial_cmp(&scores[i]).unwrap_or(std::cmp::Ordering::Equal));
+        indices.truncate(self.k);
+        indices.sort_unstable();
+        indices
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::{Array1, Array2};
+
+    #[test]
+    fn test_classification_scorers() {
+        // Features: [random, collinear with target, constant, collinear with feature 1, noise]
+        let x = Array2::from_shape_vec((10, 5), vec![
+            0.1,  1.0, 5.0,  0.2, -0.3,
+            0.4, -1.0, 5.0,  0.8,  0.1,
+            0.6,  1.0, 5.0,  1.2,  0.2,
+            0.9, -1.0, 5.0,  1.8, -0.1,
+            1.2,  1.0, 5.0,  2.4,  0.3,
+            1.5, -1.0, 5.0,  3.0,  0.0,
+            1.8,  1.0, 5.0,  3.6, -0.2,
+            2.1, -1.0, 5.0,  4.2,  0.4,
+            2.4,  1.0, 5.0,  4.8, -0.1,
+            2.7, -1.0, 5.0,  5.4,  f32::NAN,
+        ]).unwrap();
+        let y = Array1::from_vec(vec![1, -1, 1, -1, 1, -1, 1, -1, 1, -1]);
+
+        let (f_scores, p_values) = f_classif(&x, &y);
+        let mi = mutual_info_classif(&x, &y, 4);
+        let auc = auc_classif(&x, &y);
+        println!("F-scores: {:?}, p-values: {:?}", f_scores, p_values);
+        println!("MI: {:?}, AUC: {:?}", mi, auc);
+
+        for scores in [&f_scores, &mi, &auc] {
+            let best = scores
+                .iter()
+                .enumerate()
+                .max_by(|a, b| a.1.partial_cmp(b.1).unwrap())
+                .map(|(i, _)| i)
+                .unwrap();
+            assert_eq!(best, 1);
+        }
+        assert_eq!(f_scores[2], 0.0);
+        assert_eq!(p_values[2], 1.0);
+        assert!(mi[2].abs() < 1e-12);
+        assert_eq!(auc[1], 1.0);
+        assert_eq!(auc[2], 0.5);
+
+        let selected = SelectKBestClassif::new(2, ClassificationScorer::Auc).fit(&x, &y);
+        assert_eq!(selected.len(), 2);
+        assert!(selected.contains(&1));
+        assert!(!selected.contains(&2));
+    }
+}
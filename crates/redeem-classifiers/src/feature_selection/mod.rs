@@ -1 +1,3 @@
 pub mod univariate_selection;
+pub mod classification;
+pub mod rfe;
@@ -0,0 +1,179 @@
+//! Recursive feature elimination (RFE) with the semi-supervised learner.
+//!
+//! See: https://scikit-learn.org/stable/modules/feature_selection.html#recursive-feature-elimination
+
+use ndarray::{Array1, Array2, Axis};
+
+use crate::data_handling::PsmMetadata;
+use crate::feature_importance::ImportanceType;
+use crate::psm_scorer::SemiSupervisedLearner;
+
+/// Recursive feature elimination using a [`SemiSupervisedLearner`].
+///
+/// At each round a new learner is fitted on the remaining features, the features are ranked by
+/// importance and the `step` least important ones are removed, until `n_features_to_select`
+/// features remain. The model-specific importance of the requested kind is used when the model
+/// supports it, and permutation importance otherwise.
+///
+/// The `rank` feature is never eliminated, since the learner needs it to update PSM ranks.
+pub struct RecursiveFeatureElimination {
+    /// The number of features to keep.
+    n_features_to_select: usize,
+    /// The number of features removed at each round.
+    step: usize,
+    /// The kind of feature importance used to rank features.
+    importance_type: ImportanceType,
+    /// The FDR threshold used for permutation importance.
+    eval_fdr: f32,
+}
+
+impl RecursiveFeatureElimination {
+    /// Creates a new RecursiveFeatureElimination instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `n_features_to_select` - The number of features to keep.
+    /// * `step` - The number of features removed at each round.
+    ///
+    /// # Returns
+    ///
+    /// A new RecursiveFeatureElimination instance, using permutation importance at 1% FDR.
+    pub fn new(n_features_to_select: usize, step: usize) -> Self {
+        RecursiveFeatureElimination {
+            n_features_to_select,
+            step: step.max(1),
+            importance_type: ImportanceType::Permutation,
+            eval_fdr: 0.01,
+        }
+    }
+
+    /// Set the kind of feature importance used to rank features (default: permutation)
+    pub fn with_importance_type(mut self, importance_type: ImportanceType) -> Self {
+        self.importance_type = importance_type;
+        self
+    }
+
+    /// Set the FDR threshold used for permutation importance (default: 0.01)
+    pub fn with_eval_fdr(mut self, eval_fdr: f32) -> Self {
+        self.eval_fdr = eval_fdr;
+        self
+    }
+
+    /// Run recursive feature elimination and return the indices of the selected features.
+    ///
+    /// # Arguments
+    ///
+    /// * `make_learner` - Creates a new, unfitted learner for each round
+    /// * `x` - The features, shape (n_samples, n_features)
+    /// * `y` - The labels, 1 for targets and -1 for decoys
+    /// * `psm_metadata` - The PSM metadata, with one feature name per column of `x`
+    ///
+    /// # Returns
+    ///
+    /// The indices of the selected features, in their original column order
+    pub fn fit<F>(
+        &self,
+        make_learner: F,
+        x: &Array2<f32>,
+        y: &Array1<i32>,
+        psm_metadata: &PsmMetadata,
+    ) -> anyhow::Result<Vec<usize>>
+    where
+        F: Fn() -> SemiSupervisedLearner,
+    {
+        let mut remaining: Vec<usize> = (0..x.ncols()).collect();
+
+        while remaining.len() > self.n_features_to_select {
+            let x_sub = x.select(Axis(1), &remaining);
+            let mut metadata_sub = psm_metadata.clone();
+            metadata_sub.feature_names = remaining
+                .iter()
+                .map(|&i| psm_metadata.feature_names[i].clone())
+                .collect();
+
+            let mut learner = make_learner();
+            learner.fit(x_sub.clone(), y.clone(), metadata_sub.clone())?;
+
+            let importance = match learner.feature_importance(self.importance_type) {
+                Some(importance) => importance,
+                None => learner.permutation_importance(&x_sub, y, self.eval_fdr, 1)?,
+            };
+
+            // Features dropped by the learner's preprocessing have no importance and go first
+            let mut ranked: Vec<(usize, f32)> = remaining
+                .iter()
+                .zip(metadata_sub.feature_names.iter())
+                .filter(|(_, name)| name.as_str() != "rank")
+                .map(|(&i, name)| (i, importance.get(name).unwrap_or(f32::NEG_INFINITY)))
+                .collect();
+            ranked.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+
+            let n_remove = self
+                .step
+                .min(remaining.len() - self.n_features_to_select)
+                .min(ranked.len());
+            if n_remove == 0 {
+                break;
+            }
+            for &(i, score) in ranked.iter().take(n_remove) {
+                log::debug!(
+                    "RFE: removing feature '{}' ({} importance {:.4})",
+                    psm_metadata.feature_names[i],
+                    self.importance_type,
+                    score
+                );
+                remaining.retain(|&r| r != i);
+            }
+        }
+
+        Ok(remaining)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::utils::ModelType;
+
+    #[test]
+    fn test_recursive_feature_elimination() {
+        // One PSM per spectrum, features: [noise, signal, cycle, rank]
+        let n = 200;
+        let x = Array2::from_shape_fn((n, 4), |(i, j)| match j {
+            0 => ((i * 7919) % 13) as f32,
+            1 => if i % 2 == 0 { 2.0 + (i % 5) as f32 } else { -2.0 - (i % 5) as f32 },
+            2 => (i % 3) as f32,
+            _ => 1.0,
+        });
+        let y = Array1::from_iter((0..n).map(|i| if i % 2 == 0 { 1 } else { -1 }));
+        let metadata = PsmMetadata {
+            spec_id: (0..n).map(|i| format!("scan={}", i)).collect(),
+            file_id: vec![0; n],
+            feature_names: vec!["noise".to_string(), "signal".to_string(), "cycle".to_string(), "rank".to_string()],
+            peptide: None,
+            proteins: None,
+        };
+
+        let make_learner = || {
+            SemiSupervisedLearner::new(
+                ModelType::RandomForest {
+                    n_trees: 10,
+                    max_depth: 3,
+                    min_samples_leaf: 1,
+                    max_features: 1.0,
+                    bootstrap: true,
+                    seed: 42,
+                },
+                0.1,
+                0.01,
+                2,
+                None,
+            )
+        };
+
+        let rfe = RecursiveFeatureElimination::new(2, 1).with_importance_type(ImportanceType::Gain);
+        let selected = rfe.fit(make_learner, &x, &y, &metadata).unwrap();
+
+        assert_eq!(selected, vec![1, 3]);
+    }
+}
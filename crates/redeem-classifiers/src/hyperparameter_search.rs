@@ -0,0 +1,418 @@
+//! Hyperparameter search for the semi-supervised models.
+//!
+//! Each candidate configuration is evaluated by fitting a [`SemiSupervisedLearner`] and counting
+//! the targets identified at a given FDR from its out-of-fold scores, so the score of every
+//! configuration comes from models that never saw the PSMs they scored.
+
+use ndarray::{Array1, Array2};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+use crate::data_handling::PsmMetadata;
+use crate::models::utils::{ModelParams, ModelType};
+use crate::psm_scorer::SemiSupervisedLearner;
+use crate::stats::tdc;
+
+/// Built-in search space of a model type
+///
+/// The parameters that matter most for each model are varied around the values of `model_type`,
+/// the other parameters are kept as given. The linear SVM already selects its penalties with an
+/// internal grid search, so only its iteration budget is varied.
+///
+/// # Arguments
+///
+/// * `model_type` - The model type, whose values are used for the parameters that are not searched
+///
+/// # Returns
+///
+/// All combinations of the searched parameters, as model parameters
+pub fn search_space(model_type: &ModelType) -> Vec<ModelParams> {
+    let mut candidates = Vec::new();
+    match model_type {
+        ModelType::GBDT { debug, training_optimization_level, loss_type, .. } => {
+            for learning_rate in [0.05, 0.1, 0.3] {
+                for max_depth in [3, 6, 9] {
+                    for num_boost_round in [20, 50, 100] {
+                        candidates.push(ModelParams::new(
+                            learning_rate,
+                            ModelType::GBDT {
+                                max_depth,
+                                num_boost_round,
+                                debug: *debug,
+                                training_optimization_level: *training_optimization_level,
+                                loss_type: loss_type.clone(),
+                            },
+                        ));
+                    }
+                }
+            }
+        }
+        ModelType::LinearSVM { c_grid, class_ratio_grid, eps, eval_fdr, .. } => {
+            for max_iter in [50, 100, 200] {
+                candidates.push(ModelParams::new(
+                    0.1,
+                    ModelType::LinearSVM {
+                        c_grid: c_grid.clone(),
+                        class_ratio_grid: class_ratio_grid.clone(),
+                        max_iter,
+                        eps: *eps,
+                        eval_fdr: *eval_fdr,
+                    },
+                ));
+            }
+        }
+        ModelType::MLP { epochs, batch_size, early_stopping_patience, device, .. } => {
+            for learning_rate in [0.001, 0.01] {
+                for hidden_layers in [vec![16], vec![32, 16], vec![64, 32]] {
+                    for dropout in [0.0, 0.1, 0.3] {
+                        candidates.push(ModelParams::new(
+                            learning_rate,
+                            ModelType::MLP {
+                                hidden_layers: hidden_layers.clone(),
+                                dropout,
+                                epochs: *epochs,
+                                batch_size: *batch_size,
+                                early_stopping_patience: *early_stopping_patience,
+                                device: device.clone(),
+                            },
+                        ));
+                    }
+                }
+            }
+        }
+        ModelType::RandomForest { bootstrap, seed, .. } => {
+            for n_trees in [50, 100, 200] {
+                for max_depth in [5, 10, 20] {
+                    for min_samples_leaf in [1, 5] {
+                        for max_features in [0.33, 0.5] {
+                            candidates.push(ModelParams::new(
+                                0.1,
+                                ModelType::RandomForest {
+                                    n_trees,
+                                    max_depth,
+                                    min_samples_leaf,
+                                    max_features,
+                                    bootstrap: *bootstrap,
+                                    seed: *seed,
+                                },
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+        #[cfg(feature = "xgboost")]
+        ModelType::XGBoost { early_stopping_rounds, verbose_eval, .. } => {
+            for learning_rate in [0.05, 0.1, 0.3] {
+                for max_depth in [3, 6, 8] {
+                    for num_boost_round in [100, 200, 400] {
+                        candidates.push(ModelParams::new(
+                            learning_rate,
+                            ModelType::XGBoost {
+                                max_depth,
+                                num_boost_round,
+                                early_stopping_rounds: *early_stopping_rounds,
+                                verbose_eval: *verbose_eval,
+                            },
+                        ));
+                    }
+                }
+            }
+        }
+        #[cfg(feature = "linfa")]
+        ModelType::SVM { eps, gaussian_kernel_eps, polynomial_kernel_constant, polynomial_kernel_degree, .. } => {
+            for c in [0.1, 1.0, 10.0] {
+                for kernel in ["linear", "gauss"] {
+                    candidates.push(ModelParams::new(
+                        0.1,
+                        ModelType::SVM {
+                            eps: *eps,
+                            c: (c, c),
+                            kernel: kernel.to_string(),
+                            gaussian_kernel_eps: *gaussian_kernel_eps,
+                            polynomial_kernel_constant: *polynomial_kernel_constant,
+                            polynomial_kernel_degree: *polynomial_kernel_degree,
+                        },
+                    ));
+                }
+            }
+        }
+    }
+    candidates
+}
+
+/// Evaluation of one candidate configuration
+#[derive(Debug, Clone)]
+pub struct SearchTrial {
+    /// The evaluated model parameters
+    pub params: ModelParams,
+    /// Number of targets at the evaluation FDR, or `None` if the learner could not be trained
+    pub identifications: Option<usize>,
+}
+
+/// Result of a hyperparameter search
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    /// The configuration with the most identifications
+    pub best_params: ModelParams,
+    /// The number of identifications of the best configuration
+    pub best_identifications: usize,
+    /// All evaluated configurations, in evaluation order
+    pub trials: Vec<SearchTrial>,
+}
+
+/// Grid or random search over model parameters.
+///
+/// # Example
+///
+/// ```ignore
+/// let search = HyperparameterSearch::random(&ModelType::from_str("gbdt")?, 10, 42).with_eval_fdr(0.01);
+/// let result = search.run(&x, &y, &metadata)?;
+/// let learner = SemiSupervisedLearner::new(result.best_params.model_type, result.best_params.learning_rate, 0.01, 3, None);
+/// ```
+pub struct HyperparameterSearch {
+    /// The configurations to evaluate
+    candidates: Vec<ModelParams>,
+    /// The FDR threshold at which identifications are counted
+    eval_fdr: f32,
+    /// The FDR threshold used to select positive examples during training
+    train_fdr: f32,
+    /// The number of cross-validation folds
+    n_folds: usize,
+    /// The maximum number of training iterations of each learner
+    max_iter: usize,
+    /// The seed used for fold assignment
+    seed: u64,
+}
+
+impl HyperparameterSearch {
+    /// Create a search over the given configurations
+    pub fn from_candidates(candidates: Vec<ModelParams>) -> Self {
+        HyperparameterSearch {
+            candidates,
+            eval_fdr: 0.01,
+            train_fdr: 0.01,
+            n_folds: 3,
+            max_iter: 3,
+            seed: 42,
+        }
+    }
+
+    /// Create a grid search over the built-in [`search_space`] of a model type
+    pub fn grid(model_type: &ModelType) -> Self {
+        Self::from_candidates(search_space(model_type))
+    }
+
+    /// Create a random search over `n_iter` distinct configurations of the built-in [`search_space`]
+    ///
+    /// # Arguments
+    ///
+    /// * `model_type` - The model type to tune
+    /// * `n_iter` - The number of configurations to evaluate
+    /// * `seed` - The seed used to sample the configurations
+    pub fn random(model_type: &ModelType, n_iter: usize, seed: u64) -> Self {
+        let mut candidates = search_space(model_type);
+        candidates.shuffle(&mut StdRng::seed_from_u64(seed));
+        candidates.truncate(n_iter);
+        Self::from_candidates(candidates)
+    }
+
+    /// Set the FDR threshold at which identifications are counted (default: 0.01)
+    pub fn with_eval_fdr(mut self, eval_fdr: f32) -> Self {
+        self.eval_fdr = eval_fdr;
+        self
+    }
+
+    /// Set the FDR threshold used to select positive examples during training (default: 0.01)
+    pub fn with_train_fdr(mut self, train_fdr: f32) -> Self {
+        self.train_fdr = train_fdr;
+        self
+    }
+
+    /// Set the number of cross-validation folds (default: 3)
+    pub fn with_folds(mut self, n_folds: usize) -> Self {
+        self.n_folds = n_folds;
+        self
+    }
+
+    /// Set the maximum number of training iterations of each learner (default: 3)
+    pub fn with_max_iterations(mut self, max_iter: usize) -> Self {
+        self.max_iter = max_iter;
+        self
+    }
+
+    /// Set the seed used for fold assignment (default: 42)
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// The configurations that will be evaluated
+    pub fn candidates(&self) -> &[ModelParams] {
+        &self.candidates
+    }
+
+    /// Number of targets at the evaluation FDR from the out-of-fold scores of one configuration
+    fn evaluate(
+        &self,
+        params: &ModelParams,
+        x: &Array2<f32>,
+        y: &Array1<i32>,
+        psm_metadata: &PsmMetadata,
+    ) -> anyhow::Result<usize> {
+        let mut learner = SemiSupervisedLearner::new(
+            params.model_type.clone(),
+            params.learning_rate,
+            self.train_fdr,
+            self.n_folds,
+            None,
+        )
+        .with_max_iterations(self.max_iter)
+        .with_seed(self.seed);
+        let (scores, _ranks) = learner.fit(x.clone(), y.clone(), psm_metadata.clone())?;
+
+        // The learner falls back to the best single feature if no model could be trained
+        if learner.fold_calibrations().is_empty() {
+            anyhow::bail!("No model could be trained");
+        }
+
+        let targets: Array1<bool> = y.iter().map(|&l| l == 1).collect();
+        let qvals = tdc(&scores, &targets, true)?;
+        Ok(qvals
+            .iter()
+            .zip(targets.iter())
+            .filter(|(&q, &t)| t && q <= self.eval_fdr)
+            .count())
+    }
+
+    /// Evaluate all configurations and return the best one
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - The features, shape (n_samples, n_features)
+    /// * `y` - The labels, 1 for targets and -1 for decoys
+    /// * `psm_metadata` - The PSM metadata
+    ///
+    /// # Returns
+    ///
+    /// The best configuration and the evaluation of all configurations, or an error if there are
+    /// no candidates or no configuration could be trained
+    pub fn run(&self, x: &Array2<f32>, y: &Array1<i32>, psm_metadata: &PsmMetadata) -> anyhow::Result<SearchResult> {
+        if self.candidates.is_empty() {
+            anyhow::bail!("No hyperparameter configurations to evaluate");
+        }
+
+        let mut trials = Vec::with_capacity(self.candidates.len());
+        let mut best: Option<(usize, usize)> = None;
+
+        for (i, params) in self.candidates.iter().enumerate() {
+            let identifications = match self.evaluate(params, x, y, psm_metadata) {
+                Ok(identifications) => {
+                    log::info!(
+                        "Hyperparameter search {}/{}: {} targets at {} FDR with {:?}",
+                        i + 1,
+                        self.candidates.len(),
+                        identifications,
+                        self.eval_fdr,
+                        params
+                    );
+                    if best.map_or(true, |(_, n)| identifications > n) {
+                        best = Some((i, identifications));
+                    }
+                    Some(identifications)
+                }
+                Err(e) => {
+                    log::warn!("Hyperparameter search {}/{}: {:?} failed: {}", i + 1, self.candidates.len(), params, e);
+                    None
+                }
+            };
+            trials.push(SearchTrial {
+                params: params.clone(),
+                identifications,
+            });
+        }
+
+        let (best_index, best_identifications) =
+            best.ok_or_else(|| anyhow::anyhow!("No hyperparameter configuration could be trained"))?;
+        let best_params = self.candidates[best_index].clone();
+        log::info!(
+            "Best hyperparameters: {:?} with {} targets at {} FDR",
+            best_params,
+            best_identifications,
+            self.eval_fdr
+        );
+
+        Ok(SearchResult {
+            best_params,
+            best_identifications,
+            trials,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hyperparameter_search() {
+        assert_eq!(search_space(&ModelType::default()).len(), 27);
+        let random = HyperparameterSearch::random(&ModelType::default(), 5, 42);
+        assert_eq!(random.candidates().len(), 5);
+
+        // One PSM per spectrum. Half of the targets stand out on the first feature. Targets have
+        // the same sign on the second and third features and decoys opposite signs, which a sum
+        // of single splits can't separate
+        let n = 400;
+        let x = Array2::from_shape_fn((n, 4), |(i, j)| {
+            let is_target = i % 2 == 0;
+            let sign = if (i / 4) % 2 == 0 { 1.0 } else { -1.0 };
+            match j {
+                0 if is_target && i % 4 == 0 => 10.0,
+                0 => ((i * 13) % 6) as f32,
+                1 => sign * (0.1 + ((i * 7) % 20) as f32 / 10.0),
+                2 if is_target => sign * (0.1 + ((i * 11) % 20) as f32 / 10.0),
+                2 => -sign * (0.1 + ((i * 11) % 20) as f32 / 10.0),
+                _ => 1.0,
+            }
+        });
+        let y = Array1::from_iter((0..n).map(|i| if i % 2 == 0 { 1 } else { -1 }));
+        let metadata = PsmMetadata {
+            spec_id: (0..n).map(|i| format!("scan={}", i)).collect(),
+            file_id: vec![0; n],
+            feature_names: vec!["score".to_string(), "a".to_string(), "b".to_string(), "rank".to_string()],
+            peptide: None,
+            proteins: None,
+        };
+
+        let candidates: Vec<ModelParams> = [1, 3]
+            .into_iter()
+            .map(|max_depth| {
+                ModelParams::new(
+                    0.1,
+                    ModelType::RandomForest {
+                        n_trees: 20,
+                        max_depth,
+                        min_samples_leaf: 1,
+                        max_features: 0.3,
+                        bootstrap: true,
+                        seed: 42,
+                    },
+                )
+            })
+            .collect();
+        let result = HyperparameterSearch::from_candidates(candidates)
+            .with_max_iterations(1)
+            .run(&x, &y, &metadata)
+            .unwrap();
+
+        assert_eq!(result.trials.len(), 2);
+        let identifications: Vec<usize> = result.trials.iter().map(|trial| trial.identifications.unwrap()).collect();
+        assert!(identifications[0] < identifications[1]);
+
+        // Only the deeper trees can split on both signs, so they are selected
+        assert!(matches!(result.best_params.model_type, ModelType::RandomForest { max_depth: 3, .. }));
+        assert_eq!(result.best_identifications, identifications[1]);
+    }
+}
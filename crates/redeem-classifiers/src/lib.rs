@@ -1,7 +1,11 @@
 pub mod models;
+pub mod preprocessing;
 pub mod feature_selection;
+pub mod feature_importance;
+pub mod hyperparameter_search;
 pub mod psm_scorer;
 pub mod data_handling;
 pub mod stats;
+pub mod diagnostics;
 pub mod report;
 pub mod error;
\ No newline at end of file
@@ -4,12 +4,15 @@ use gbdt::decision_tree::{Data, DataVec, PredVec};
 use gbdt::gradient_boost::GBDT;
 
 
+use crate::error::ModelError;
+use crate::feature_importance::ImportanceType;
 use crate::models::utils::{ModelType, ModelParams};
 use crate::psm_scorer::SemiSupervisedModel;
 
 /// Gradient Boosting Decision Tree (GBDT) classifier
 pub struct GBDTClassifier {
     model: Option<GBDT>,
+    n_features: usize,
     params: ModelParams,
 }
 
@@ -17,19 +20,55 @@ impl GBDTClassifier {
     pub fn new(params: ModelParams) -> Self {
         GBDTClassifier {
             model: None,
+            n_features: 0,
             params,
         }
     }
 }
 
+/// Number of splits on each feature over all trees of a fitted GBDT model
+///
+/// gbdt does not expose its trees, so they are read from the serialized model, the same JSON
+/// layout written by `GBDT::save_model`: `trees[].tree.tree[].value` holds the nodes, with
+/// `is_leaf` and the `feature_index` of the split. Returns `None` if the layout is not recognized.
+fn split_counts(model: &GBDT, n_features: usize) -> Option<Vec<f32>> {
+    let model = serde_json::to_value(model).ok()?;
+    let mut counts = vec![0.0f32; n_features];
+    for tree in model.get("trees")?.as_array()? {
+        for node in tree.get("tree")?.get("tree")?.as_array()? {
+            let node = node.get("value")?;
+            if node.get("is_leaf")?.as_bool()? {
+                continue;
+            }
+            let feature = node.get("feature_index")?.as_u64()? as usize;
+            *counts.get_mut(feature)? += 1.0;
+        }
+    }
+    Some(counts)
+}
+
 impl SemiSupervisedModel for GBDTClassifier {
     fn fit(
         &mut self,
         x: &Array2<f32>,
         y: &[i32],
+        x_eval: Option<&Array2<f32>>,
+        y_eval: Option<&[i32]>,
+    ) -> Result<(), ModelError> {
+        self.fit_weighted(x, y, &vec![1.0; x.nrows()], x_eval, y_eval)
+    }
+
+    fn fit_weighted(
+        &mut self,
+        x: &Array2<f32>,
+        y: &[i32],
+        sample_weight: &[f32],
         _x_eval: Option<&Array2<f32>>,
         _y_eval: Option<&[i32]>,
-    ) {
+    ) -> Result<(), ModelError> {
+        if sample_weight.len() != x.nrows() {
+            return Err(ModelError::InvalidParams(format!("Expected {} sample weights, got {}", x.nrows(), sample_weight.len())));
+        }
         let feature_size = x.ncols();
         
         if let ModelType::GBDT {
@@ -59,19 +98,21 @@ impl SemiSupervisedModel for GBDTClassifier {
                 for (j, &val) in row.iter().enumerate() {
                     train_row.push(val);
                 }
-                train_x.push(Data::new_training_data(train_row, 1.0, y[i] as f32, None));
+                train_x.push(Data::new_training_data(train_row, sample_weight[i], y[i] as f32, None));
             }
 
             gbdt.fit(&mut train_x);
 
             self.model = Some(gbdt);
+            self.n_features = feature_size;
+            Ok(())
         } else {
-            panic!("Error: Expected ModelType::GBDT params, got {:?}", self.params.model_type);
+            Err(ModelError::InvalidParams(format!("Expected ModelType::GBDT params, got {:?}", self.params.model_type)))
         }
-        
     }
 
-    fn predict(&self, x: &Array2<f32>) -> Vec<f32> {
+    fn predict(&self, x: &Array2<f32>) -> Result<Vec<f32>, ModelError> {
+        let model = self.model.as_ref().ok_or(ModelError::NotFitted)?;
         let mut test_x = DataVec::new();
         for row in x.outer_iter() {
             let mut test_row = Vec::new();
@@ -80,13 +121,21 @@ impl SemiSupervisedModel for GBDTClassifier {
             }
             test_x.push(Data::new_training_data(test_row, 1.0, 0.0, None));
         }
-        let predictions = self.model.as_ref().unwrap().decision_function(&test_x);
-        predictions
+        let predictions = model.decision_function(&test_x);
+        Ok(predictions)
     }
 
-    fn predict_proba(&mut self, x: &Array2<f32>) -> Vec<f32> {
+    fn predict_proba(&mut self, x: &Array2<f32>) -> Result<Vec<f32>, ModelError> {
         self.predict(x)
     }
+
+    /// Split counts only, gbdt does not record the gain of its splits
+    fn feature_importance(&self, importance_type: ImportanceType) -> Option<Vec<f32>> {
+        match importance_type {
+            ImportanceType::SplitCount => split_counts(self.model.as_ref()?, self.n_features),
+            _ => None,
+        }
+    }
 }
 
 
@@ -134,10 +183,10 @@ mod tests {
         let mut classifier = GBDTClassifier::new(params);
 
         // Fit the classifier
-        classifier.fit(&x, &y.to_vec(), None, None);
+        classifier.fit(&x, &y.to_vec(), None, None).unwrap();
 
         // Make predictions
-        let predictions = classifier.predict(&x);
+        let predictions = classifier.predict(&x).unwrap();
 
         println!("Predictions: {:?}", predictions);
         println!("y: {:?}", y.to_vec());
@@ -145,5 +194,21 @@ mod tests {
         // Check that predictions are reasonable
         // assert_eq!(predictions.len(), y.len());
 
+        // Sample weights must match the number of samples
+        let weights = vec![2.0; 10];
+        classifier.fit_weighted(&x, &y.to_vec(), &weights, None, None).unwrap();
+        assert!(classifier.fit_weighted(&x, &y.to_vec(), &weights[..5], None, None).is_err());
+
+        // The second feature separates the classes, so it is split on the most
+        let importance = classifier.feature_importance(ImportanceType::SplitCount).unwrap();
+        assert_eq!(importance.len(), 5);
+        let most_split = importance
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map(|(i, _)| i);
+        assert_eq!(most_split, Some(1));
+        assert!(classifier.feature_importance(ImportanceType::Gain).is_none());
+
     }
 }
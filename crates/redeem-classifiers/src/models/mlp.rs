@@ -0,0 +1,396 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use candle_core::{DType, Device, Tensor};
+use candle_nn::{linear, Dropout, Linear, Module, Optimizer, VarBuilder, VarMap};
+use ndarray::{Array1, Array2, Axis};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+use crate::error::ModelError;
+use crate::models::utils::{ModelParams, ModelType};
+use crate::psm_scorer::SemiSupervisedModel;
+
+/// Multi-layer perceptron (MLP) classifier built with candle.
+///
+/// The network is a stack of fully connected layers with ReLU activations and dropout,
+/// followed by a single output logit. It is trained with AdamW on the binary cross-entropy
+/// loss, weighted by the sample weights given to [`SemiSupervisedModel::fit_weighted`], in
+/// mini-batches. When an evaluation set is given, the weights with the lowest
+/// evaluation loss are kept and training stops after `early_stopping_patience` epochs
+/// without improvement.
+///
+/// Features are standardized internally with the statistics of the training data.
+///
+/// The network is trained on the device given in the params, which can be a CUDA device
+/// when the crate is built with the `cuda` feature.
+pub struct MLPClassifier {
+    varmap: VarMap,
+    layers: Vec<Linear>,
+    dropout: Dropout,
+    mean: Array1<f32>,
+    std: Array1<f32>,
+    device: Device,
+    params: ModelParams,
+}
+
+impl MLPClassifier {
+    pub fn new(params: ModelParams) -> Self {
+        let dropout = match &params.model_type {
+            ModelType::MLP { dropout, .. } => *dropout,
+            _ => 0.0,
+        };
+
+        MLPClassifier {
+            varmap: VarMap::new(),
+            layers: Vec::new(),
+            dropout: Dropout::new(dropout),
+            mean: Array1::zeros(0),
+            std: Array1::zeros(0),
+            device: Device::Cpu,
+            params,
+        }
+    }
+
+    /// Build the network layers for the given input size.
+    fn build(&mut self, n_features: usize, hidden_layers: &[usize]) -> Result<()> {
+        self.varmap = VarMap::new();
+        let vb = VarBuilder::from_varmap(&self.varmap, DType::F32, &self.device);
+
+        let mut layers = Vec::with_capacity(hidden_layers.len() + 1);
+        let mut in_dim = n_features;
+        for (i, &out_dim) in hidden_layers.iter().enumerate() {
+            layers.push(linear(in_dim, out_dim, vb.pp(format!("hidden_{}", i)))?);
+            in_dim = out_dim;
+        }
+        layers.push(linear(in_dim, 1, vb.pp("output"))?);
+
+        self.layers = layers;
+        Ok(())
+    }
+
+    fn forward(&self, xs: &Tensor, train: bool) -> Result<Tensor> {
+        let (output, hidden) = self.layers.split_last().expect("MLP model has not been built");
+
+        let mut xs = xs.clone();
+        for layer in hidden {
+            xs = layer.forward(&xs)?.relu()?;
+            xs = self.dropout.forward(&xs, train)?;
+        }
+        Ok(output.forward(&xs)?.squeeze(1)?)
+    }
+
+    /// Standardize a feature matrix with the statistics computed during `fit` and move it to the device.
+    fn to_tensor(&self, x: &Array2<f32>) -> Result<Tensor> {
+        let mut x = x.to_owned();
+        for (j, mut col) in x.axis_iter_mut(Axis(1)).enumerate() {
+            let (mean, std) = (self.mean[j], self.std[j]);
+            col.mapv_inplace(|v| if v.is_finite() { (v - mean) / std } else { 0.0 });
+        }
+        let (n_rows, n_cols) = x.dim();
+        Ok(Tensor::from_vec(x.iter().cloned().collect::<Vec<f32>>(), (n_rows, n_cols), &self.device)?)
+    }
+
+    fn to_targets(&self, y: &[i32]) -> Result<Tensor> {
+        let y: Vec<f32> = y.iter().map(|&l| if l == 1 { 1.0 } else { 0.0 }).collect();
+        let n_samples = y.len();
+        Ok(Tensor::from_vec(y, n_samples, &self.device)?)
+    }
+
+    /// Binary cross-entropy with logits, averaged with one weight per sample
+    fn weighted_bce_with_logit(logits: &Tensor, targets: &Tensor, weights: &Tensor) -> Result<Tensor> {
+        // max(l, 0) - l * y + ln(1 + exp(-|l|)) is the loss, computed without overflow
+        let loss = ((logits.relu()? - (logits * targets)?)? + (logits.abs()?.neg()?.exp()? + 1.0)?.log()?)?;
+        let total_weight = weights.sum_all()?.to_vec0::<f32>()?.max(f32::EPSILON);
+        Ok(((loss * weights)?.sum_all()? / total_weight as f64)?)
+    }
+
+    fn snapshot_weights(&self) -> Result<HashMap<String, Tensor>> {
+        let data = self.varmap.data().lock().unwrap();
+        data.iter()
+            .map(|(name, var)| Ok((name.clone(), var.as_tensor().copy()?)))
+            .collect()
+    }
+
+    fn restore_weights(&self, weights: &HashMap<String, Tensor>) -> Result<()> {
+        let data = self.varmap.data().lock().unwrap();
+        for (name, var) in data.iter() {
+            if let Some(tensor) = weights.get(name) {
+                var.set(tensor)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn train(
+        &mut self,
+        x: &Array2<f32>,
+        y: &[i32],
+        sample_weight: &[f32],
+        x_eval: Option<&Array2<f32>>,
+        y_eval: Option<&[i32]>,
+    ) -> Result<()> {
+        let ModelType::MLP {
+            hidden_layers,
+            dropout: _,
+            epochs,
+            batch_size,
+            early_stopping_patience,
+            device,
+        } = self.params.model_type.clone()
+        else {
+            anyhow::bail!("Expected ModelType::MLP params, got {:?}", self.params.model_type);
+        };
+        self.device = get_device(&device)?;
+
+        // Compute standardization statistics on the training data only
+        let n_features = x.ncols();
+        self.mean = Array1::zeros(n_features);
+        self.std = Array1::ones(n_features);
+        for (j, col) in x.axis_iter(Axis(1)).enumerate() {
+            let finite: Vec<f32> = col.iter().filter(|v| v.is_finite()).cloned().collect();
+            if finite.is_empty() {
+                continue;
+            }
+            let mean = finite.iter().sum::<f32>() / finite.len() as f32;
+            let var = finite.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / finite.len() as f32;
+            self.mean[j] = mean;
+            self.std[j] = if var.sqrt() > 1e-6 { var.sqrt() } else { 1.0 };
+        }
+
+        self.build(n_features, &hidden_layers)?;
+
+        let x_train = self.to_tensor(x)?;
+        let y_train = self.to_targets(y)?;
+        let w_train = Tensor::from_slice(sample_weight, sample_weight.len(), &self.device)?;
+        let eval = match (x_eval, y_eval) {
+            (Some(x_e), Some(y_e)) => Some((self.to_tensor(x_e)?, self.to_targets(y_e)?)),
+            _ => None,
+        };
+
+        let params = candle_nn::ParamsAdamW {
+            lr: self.params.learning_rate as f64,
+            ..Default::default()
+        };
+        let mut opt = candle_nn::AdamW::new(self.varmap.all_vars(), params)?;
+
+        let n_samples = x.nrows();
+        let batch_size = batch_size.max(1);
+        let mut indices: Vec<u32> = (0..n_samples as u32).collect();
+        let mut rng = StdRng::seed_from_u64(42);
+
+        let mut best_eval_loss = f32::INFINITY;
+        let mut best_weights = None;
+        let mut epochs_without_improvement = 0;
+
+        for epoch in 0..epochs {
+            indices.shuffle(&mut rng);
+            let mut batch_losses = vec![];
+
+            for batch in indices.chunks(batch_size) {
+                let batch_idx = Tensor::from_slice(batch, batch.len(), &self.device)?;
+                let xs = x_train.index_select(&batch_idx, 0)?;
+                let ys = y_train.index_select(&batch_idx, 0)?;
+                let ws = w_train.index_select(&batch_idx, 0)?;
+
+                let logits = self.forward(&xs, true)?;
+                let loss = Self::weighted_bce_with_logit(&logits, &ys, &ws)?;
+                opt.backward_step(&loss)?;
+
+                let loss_val = loss.to_vec0::<f32>()?;
+                if loss_val.is_nan() {
+                    anyhow::bail!("Loss is NaN, stopping training.");
+                }
+                batch_losses.push(loss_val);
+            }
+
+            let avg_loss = batch_losses.iter().sum::<f32>() / batch_losses.len().max(1) as f32;
+
+            if let Some((x_e, y_e)) = &eval {
+                let logits = self.forward(x_e, false)?;
+                let eval_loss = candle_nn::loss::binary_cross_entropy_with_logit(&logits, y_e)?
+                    .to_vec0::<f32>()?;
+                log::trace!(
+                    "MLP epoch {}: train loss {:.4}, eval loss {:.4}",
+                    epoch,
+                    avg_loss,
+                    eval_loss
+                );
+
+                if eval_loss < best_eval_loss {
+                    best_eval_loss = eval_loss;
+                    best_weights = Some(self.snapshot_weights()?);
+                    epochs_without_improvement = 0;
+                } else {
+                    epochs_without_improvement += 1;
+                    if epochs_without_improvement >= early_stopping_patience {
+                        log::debug!(
+                            "MLP early stopping triggered after {} epochs without eval loss improvement.",
+                            early_stopping_patience
+                        );
+                        break;
+                    }
+                }
+            } else {
+                log::trace!("MLP epoch {}: train loss {:.4}", epoch, avg_loss);
+            }
+        }
+
+        if let Some(weights) = best_weights {
+            self.restore_weights(&weights)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parse a device string, `cpu`, `cuda` or `cuda:<index>`, into a candle device.
+fn get_device(device: &str) -> Result<Device> {
+    match device {
+        "cpu" => Ok(Device::Cpu),
+        _ if device.starts_with("cuda") => {
+            let index = match device.split_once(':') {
+                Some((_, index)) => index
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("Invalid CUDA device index: {}", device))?,
+                None => 0,
+            };
+            let device = Device::cuda_if_available(index)?;
+            if !device.is_cuda() {
+                anyhow::bail!(
+                    "CUDA device {} is not available, build with the `cuda` feature to use it",
+                    index
+                );
+            }
+            Ok(device)
+        }
+        _ => anyhow::bail!("Unsupported device: {}", device),
+    }
+}
+
+impl SemiSupervisedModel for MLPClassifier {
+    fn fit(
+        &mut self,
+        x: &Array2<f32>,
+        y: &[i32],
+        x_eval: Option<&Array2<f32>>,
+        y_eval: Option<&[i32]>,
+    ) -> Result<(), ModelError> {
+        self.fit_weighted(x, y, &vec![1.0; x.nrows()], x_eval, y_eval)
+    }
+
+    fn fit_weighted(
+        &mut self,
+        x: &Array2<f32>,
+        y: &[i32],
+        sample_weight: &[f32],
+        x_eval: Option<&Array2<f32>>,
+        y_eval: Option<&[i32]>,
+    ) -> Result<(), ModelError> {
+        if sample_weight.len() != x.nrows() {
+            return Err(ModelError::InvalidParams(format!("Expected {} sample weights, got {}", x.nrows(), sample_weight.len())));
+        }
+        self.train(x, y, sample_weight, x_eval, y_eval)
+            .map_err(|e| ModelError::Training(e.to_string()))
+    }
+
+    fn predict(&self, x: &Array2<f32>) -> Result<Vec<f32>, ModelError> {
+        if self.layers.is_empty() {
+            return Err(ModelError::NotFitted);
+        }
+        let predict = || -> Result<Vec<f32>> {
+            let xs = self.to_tensor(x)?;
+            Ok(self.forward(&xs, false)?.to_vec1::<f32>()?)
+        };
+        predict().map_err(|e| ModelError::Prediction(e.to_string()))
+    }
+
+    fn predict_proba(&mut self, x: &Array2<f32>) -> Result<Vec<f32>, ModelError> {
+        self.predict(x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::{Array1, Array2};
+
+    #[test]
+    fn test_mlp_classifier() {
+        // Create a feature matrix with 5 features and 10 samples
+        let x = Array2::from_shape_vec(
+            (10, 5),
+            vec![
+                0.1, 1.0, 5.0, 0.2, -0.3, 0.4, -1.0, 5.0, 0.8, 0.1, 0.6, 1.0, 5.0, 1.2, 0.2, 0.9,
+                -1.0, 5.0, 1.8, -0.1, 1.2, 1.0, 5.0, 2.4, 0.3, 1.5, -1.0, 5.0, 3.0, 0.0, 1.8, 1.0,
+                5.0, 3.6, -0.2, 2.1, -1.0, 5.0, 4.2, 0.4, 2.4, 1.0, 5.0, 4.8, -0.1, 2.7, -1.0, 5.0,
+                5.4, 0.2,
+            ],
+        )
+        .unwrap();
+
+        // Create a target vector perfectly correlated with the second feature
+        let y = Array1::from_vec(vec![
+            1i32, -1i32, 1i32, -1i32, 1i32, -1i32, 1i32, -1i32, 1i32, -1i32,
+        ]);
+
+        let params = ModelParams {
+            learning_rate: 0.01,
+            model_type: ModelType::MLP {
+                hidden_layers: vec![8],
+                dropout: 0.0,
+                epochs: 200,
+                batch_size: 4,
+                early_stopping_patience: 10,
+                device: "cpu".to_string(),
+            },
+        };
+
+        let mut classifier = MLPClassifier::new(params);
+        classifier.fit(&x, &y.to_vec(), Some(&x), Some(&y.to_vec())).unwrap();
+
+        let predictions = classifier.predict(&x).unwrap();
+        println!("Predictions: {:?}", predictions);
+
+        assert_eq!(predictions.len(), y.len());
+        for (pred, label) in predictions.iter().zip(y.iter()) {
+            assert_eq!(*pred > 0.0, *label == 1);
+        }
+    }
+
+    #[test]
+    fn test_weighted_bce_with_logit() {
+        let device = Device::Cpu;
+        let logits = Tensor::new(&[2.0f32, -1.0, 0.5, -30.0], &device).unwrap();
+        let targets = Tensor::new(&[1.0f32, 0.0, 0.0, 1.0], &device).unwrap();
+
+        // Unit weights give the unweighted loss
+        let ones = Tensor::ones(4, DType::F32, &device).unwrap();
+        let weighted = MLPClassifier::weighted_bce_with_logit(&logits, &targets, &ones)
+            .unwrap()
+            .to_vec0::<f32>()
+            .unwrap();
+        let expected = candle_nn::loss::binary_cross_entropy_with_logit(&logits, &targets)
+            .unwrap()
+            .to_vec0::<f32>()
+            .unwrap();
+        assert!((weighted - expected).abs() < 1e-4);
+
+        // A zero weight removes the sample from the loss
+        let weights = Tensor::new(&[1.0f32, 1.0, 1.0, 0.0], &device).unwrap();
+        let weighted = MLPClassifier::weighted_bce_with_logit(&logits, &targets, &weights)
+            .unwrap()
+            .to_vec0::<f32>()
+            .unwrap();
+        let expected = [(1.0f32 + (-2.0f32).exp()).ln(), (1.0f32 + (-1.0f32).exp()).ln(), 0.5 + (1.0f32 + (-0.5f32).exp()).ln()];
+        assert!((weighted - expected.iter().sum::<f32>() / 3.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_mlp_device() {
+        assert!(get_device("cpu").unwrap().is_cpu());
+        assert!(get_device("cuda:gpu").is_err());
+        assert!(get_device("tpu").is_err());
+    }
+}
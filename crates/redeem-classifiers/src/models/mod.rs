@@ -1,5 +1,8 @@
 pub mod utils;
 pub mod gbdt;
+pub mod linear_svm;
+pub mod mlp;
+pub mod random_forest;
 #[cfg(feature = "xgboost")]
 pub mod xgboost;
 #[cfg(feature = "linfa")]
@@ -0,0 +1,493 @@
+use ndarray::{Array2, ArrayView1};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+
+use crate::error::ModelError;
+use crate::feature_importance::ImportanceType;
+use crate::models::utils::{ModelParams, ModelType};
+use crate::psm_scorer::SemiSupervisedModel;
+
+/// Node of a fitted decision tree
+#[derive(Debug, Clone)]
+enum Node {
+    Leaf {
+        /// Weighted fraction of targets in the leaf
+        value: f32,
+    },
+    Split {
+        feature: usize,
+        threshold: f32,
+        /// Gini impurity decrease weighted by the total sample weight in the node
+        gain: f64,
+        left: usize,
+        right: usize,
+    },
+}
+
+/// CART classification tree grown with the weighted Gini impurity criterion
+#[derive(Debug, Clone)]
+struct DecisionTree {
+    nodes: Vec<Node>,
+}
+
+impl DecisionTree {
+    /// Grow a tree on the given sample indices.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Feature matrix, shape (n_samples, n_features)
+    /// * `y` - Binary labels (true for targets)
+    /// * `w` - Sample weights
+    /// * `samples` - Indices of the samples used to grow the tree (may contain duplicates)
+    /// * `max_depth` - Maximum depth of the tree
+    /// * `min_samples_leaf` - Minimum number of samples in each leaf
+    /// * `max_features` - Number of features considered at each split
+    /// * `rng` - Random number generator used for feature subsampling
+    #[allow(clippy::too_many_arguments)]
+    fn fit(
+        x: &Array2<f32>,
+        y: &[bool],
+        w: &[f64],
+        samples: Vec<usize>,
+        max_depth: usize,
+        min_samples_leaf: usize,
+        max_features: usize,
+        rng: &mut StdRng,
+    ) -> Self {
+        let mut tree = DecisionTree { nodes: Vec::new() };
+        tree.grow(x, y, w, samples, 0, max_depth, min_samples_leaf.max(1), max_features, rng);
+        tree
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn grow(
+        &mut self,
+        x: &Array2<f32>,
+        y: &[bool],
+        w: &[f64],
+        samples: Vec<usize>,
+        depth: usize,
+        max_depth: usize,
+        min_samples_leaf: usize,
+        max_features: usize,
+        rng: &mut StdRng,
+    ) -> usize {
+        let n = samples.len();
+        let n_pos = samples.iter().filter(|&&i| y[i]).count();
+        let w_total: f64 = samples.iter().map(|&i| w[i]).sum();
+        let w_pos: f64 = samples.iter().filter(|&&i| y[i]).map(|&i| w[i]).sum();
+        let node_idx = self.nodes.len();
+        self.nodes.push(Node::Leaf {
+            value: if w_total > 0.0 { (w_pos / w_total) as f32 } else { 0.0 },
+        });
+
+        // Stop if the node is pure, too small or too deep
+        if depth >= max_depth || n < 2 * min_samples_leaf || n_pos == 0 || n_pos == n || w_total <= 0.0 {
+            return node_idx;
+        }
+
+        let Some((feature, threshold, decrease)) =
+            Self::best_split(x, y, w, &samples, min_samples_leaf, max_features, rng)
+        else {
+            return node_idx;
+        };
+
+        let (left_samples, right_samples): (Vec<usize>, Vec<usize>) = samples
+            .into_iter()
+            .partition(|&i| x[[i, feature]] <= threshold);
+
+        let left = self.grow(x, y, w, left_samples, depth + 1, max_depth, min_samples_leaf, max_features, rng);
+        let right = self.grow(x, y, w, right_samples, depth + 1, max_depth, min_samples_leaf, max_features, rng);
+        self.nodes[node_idx] = Node::Split {
+            feature,
+            threshold,
+            gain: decrease * w_total,
+            left,
+            right,
+        };
+
+        node_idx
+    }
+
+    /// Find the split with the largest weighted Gini impurity decrease among a random subset of features.
+    fn best_split(
+        x: &Array2<f32>,
+        y: &[bool],
+        w: &[f64],
+        samples: &[usize],
+        min_samples_leaf: usize,
+        max_features: usize,
+        rng: &mut StdRng,
+    ) -> Option<(usize, f32, f64)> {
+        let total: f64 = samples.iter().map(|&i| w[i]).sum();
+        let total_pos: f64 = samples.iter().filter(|&&i| y[i]).map(|&i| w[i]).sum();
+        let gini = |pos: f64, total: f64| {
+            if total == 0.0 {
+                0.0
+            } else {
+                let p = pos / total;
+                2.0 * p * (1.0 - p)
+            }
+        };
+        let parent_impurity = gini(total_pos, total);
+
+        let mut features: Vec<usize> = (0..x.ncols()).collect();
+        features.shuffle(rng);
+        features.truncate(max_features.max(1));
+
+        let mut best: Option<(usize, f32, f64)> = None;
+        let mut sorted: Vec<(f32, bool, f64)> = Vec::with_capacity(samples.len());
+
+        for &feature in &features {
+            sorted.clear();
+            sorted.extend(samples.iter().map(|&i| (x[[i, feature]], y[i], w[i])));
+            sorted.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+            let (mut left_weight, mut left_pos) = (0.0f64, 0.0f64);
+            for k in 0..sorted.len() - 1 {
+                left_weight += sorted[k].2;
+                if sorted[k].1 {
+                    left_pos += sorted[k].2;
+                }
+                let n_left = k + 1;
+                let n_right = sorted.len() - n_left;
+                // No threshold separates equal values, or a value from NaN
+                if n_left < min_samples_leaf
+                    || n_right < min_samples_leaf
+                    || sorted[k].0 == sorted[k + 1].0
+                    || sorted[k].0.is_nan()
+                    || sorted[k + 1].0.is_nan()
+                {
+                    continue;
+                }
+
+                let right_weight = total - left_weight;
+                let impurity = (left_weight * gini(left_pos, left_weight)
+                    + right_weight * gini(total_pos - left_pos, right_weight))
+                    / total;
+                let decrease = parent_impurity - impurity;
+
+                if decrease > 1e-12 && best.map_or(true, |(_, _, d)| decrease > d) {
+                    let threshold = sorted[k].0 + (sorted[k + 1].0 - sorted[k].0) / 2.0;
+                    best = Some((feature, threshold, decrease));
+                }
+            }
+        }
+
+        best
+    }
+
+    fn predict_row(&self, row: ArrayView1<f32>) -> f32 {
+        let mut idx = 0;
+        loop {
+            match &self.nodes[idx] {
+                Node::Leaf { value } => return *value,
+                Node::Split {
+                    feature,
+                    threshold,
+                    left,
+                    right,
+                    ..
+                } => {
+                    idx = if row[*feature] <= *threshold { *left } else { *right };
+                }
+            }
+        }
+    }
+}
+
+/// Random forest classifier
+///
+/// An ensemble of CART trees, each grown on a bootstrap sample of the training data and
+/// considering a random subset of the features at every split. Trees are built in parallel
+/// with Rayon. The predicted score is the average fraction of targets in the leaves reached
+/// by a PSM, i.e. the estimated probability that it is a target.
+///
+/// Sample weights given to [`SemiSupervisedModel::fit_weighted`] weight the Gini impurity and
+/// the fraction of targets in the leaves, on top of the bootstrap sampling.
+///
+/// When bootstrapping is enabled, out-of-bag (OOB) scores are computed for the training
+/// samples, using only the trees that did not see a given sample.
+pub struct RandomForestClassifier {
+    trees: Vec<DecisionTree>,
+    oob_scores: Option<Vec<f32>>,
+    oob_accuracy: Option<f32>,
+    n_features: usize,
+    params: ModelParams,
+}
+
+impl RandomForestClassifier {
+    pub fn new(params: ModelParams) -> Self {
+        RandomForestClassifier {
+            trees: Vec::new(),
+            oob_scores: None,
+            oob_accuracy: None,
+            n_features: 0,
+            params,
+        }
+    }
+
+    /// Out-of-bag scores of the training samples. Samples that were included in the bootstrap
+    /// sample of every tree have a NaN score.
+    pub fn oob_scores(&self) -> Option<&Vec<f32>> {
+        self.oob_scores.as_ref()
+    }
+
+    /// Out-of-bag accuracy, using a 0.5 probability cut-off.
+    pub fn oob_accuracy(&self) -> Option<f32> {
+        self.oob_accuracy
+    }
+}
+
+impl SemiSupervisedModel for RandomForestClassifier {
+    fn fit(
+        &mut self,
+        x: &Array2<f32>,
+        y: &[i32],
+        x_eval: Option<&Array2<f32>>,
+        y_eval: Option<&[i32]>,
+    ) -> Result<(), ModelError> {
+        self.fit_weighted(x, y, &vec![1.0; x.nrows()], x_eval, y_eval)
+    }
+
+    fn fit_weighted(
+        &mut self,
+        x: &Array2<f32>,
+        y: &[i32],
+        sample_weight: &[f32],
+        _x_eval: Option<&Array2<f32>>,
+        _y_eval: Option<&[i32]>,
+    ) -> Result<(), ModelError> {
+        if sample_weight.len() != x.nrows() {
+            return Err(ModelError::InvalidParams(format!("Expected {} sample weights, got {}", x.nrows(), sample_weight.len())));
+        }
+        let ModelType::RandomForest {
+            n_trees,
+            max_depth,
+            min_samples_leaf,
+            max_features,
+            bootstrap,
+            seed,
+        } = &self.params.model_type
+        else {
+            return Err(ModelError::InvalidParams(format!("Expected ModelType::RandomForest params, got {:?}", self.params.model_type)));
+        };
+
+        let n_samples = x.nrows();
+        let labels: Vec<bool> = y.iter().map(|&l| l == 1).collect();
+        let weights: Vec<f64> = sample_weight.iter().map(|&w| w as f64).collect();
+        let n_split_features = ((x.ncols() as f64 * max_features).round() as usize).clamp(1, x.ncols().max(1));
+
+        log::trace!(
+            "Fitting random forest with {} trees on {} samples ({} of {} features per split)",
+            n_trees,
+            n_samples,
+            n_split_features,
+            x.ncols()
+        );
+
+        let fitted: Vec<(DecisionTree, Vec<bool>)> = (0..*n_trees)
+            .into_par_iter()
+            .map(|tree_idx| {
+                let mut rng = StdRng::seed_from_u64(seed.wrapping_add(tree_idx as u64));
+                let mut in_bag = vec![!*bootstrap; n_samples];
+                let samples: Vec<usize> = if *bootstrap {
+                    (0..n_samples)
+                        .map(|_| {
+                            let i = rng.gen_range(0..n_samples);
+                            in_bag[i] = true;
+                            i
+                        })
+                        .collect()
+                } else {
+                    (0..n_samples).collect()
+                };
+                let tree = DecisionTree::fit(
+                    x,
+                    &labels,
+                    &weights,
+                    samples,
+                    *max_depth,
+                    *min_samples_leaf,
+                    n_split_features,
+                    &mut rng,
+                );
+                (tree, in_bag)
+            })
+            .collect();
+
+        if *bootstrap {
+            let mut sums = vec![0.0f32; n_samples];
+            let mut counts = vec![0usize; n_samples];
+            for (tree, in_bag) in &fitted {
+                for (i, row) in x.outer_iter().enumerate() {
+                    if !in_bag[i] {
+                        sums[i] += tree.predict_row(row);
+                        counts[i] += 1;
+                    }
+                }
+            }
+            let oob_scores: Vec<f32> = sums
+                .iter()
+                .zip(counts.iter())
+                .map(|(&s, &c)| if c > 0 { s / c as f32 } else { f32::NAN })
+                .collect();
+
+            let (correct, scored) = oob_scores
+                .iter()
+                .zip(labels.iter())
+                .filter(|(s, _)| !s.is_nan())
+                .fold((0usize, 0usize), |(correct, scored), (&s, &l)| {
+                    (correct + ((s > 0.5) == l) as usize, scored + 1)
+                });
+            self.oob_accuracy = if scored > 0 { Some(correct as f32 / scored as f32) } else { None };
+            self.oob_scores = Some(oob_scores);
+
+            log::debug!("Random forest OOB accuracy: {:?}", self.oob_accuracy);
+        }
+
+        self.trees = fitted.into_iter().map(|(tree, _)| tree).collect();
+        self.n_features = x.ncols();
+        Ok(())
+    }
+
+    fn predict(&self, x: &Array2<f32>) -> Result<Vec<f32>, ModelError> {
+        if self.trees.is_empty() {
+            return Err(ModelError::NotFitted);
+        }
+        let n_trees = self.trees.len() as f32;
+        Ok((0..x.nrows())
+            .into_par_iter()
+            .map(|i| self.trees.iter().map(|tree| tree.predict_row(x.row(i))).sum::<f32>() / n_trees)
+            .collect())
+    }
+
+    fn predict_proba(&mut self, x: &Array2<f32>) -> Result<Vec<f32>, ModelError> {
+        self.predict(x)
+    }
+
+    /// Total Gini impurity decrease per feature, normalized to sum to one (`Gain`), or the
+    /// number of splits on each feature (`SplitCount`), over all trees. `None` if no tree has
+    /// a split, since the features were never compared.
+    fn feature_importance(&self, importance_type: ImportanceType) -> Option<Vec<f32>> {
+        let mut importances = vec![0.0f64; self.n_features];
+        let mut n_splits = 0;
+        for node in self.trees.iter().flat_map(|tree| tree.nodes.iter()) {
+            if let Node::Split { feature, gain, .. } = node {
+                importances[*feature] += match importance_type {
+                    ImportanceType::Gain => *gain,
+                    ImportanceType::SplitCount => 1.0,
+                    _ => return None,
+                };
+                n_splits += 1;
+            }
+        }
+        if n_splits == 0 {
+            return None;
+        }
+        if importance_type == ImportanceType::Gain {
+            let total: f64 = importances.iter().sum();
+            if total > 0.0 {
+                importances.iter_mut().for_each(|v| *v /= total);
+            }
+        }
+        Some(importances.into_iter().map(|v| v as f32).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::{Array1, Array2};
+
+    #[test]
+    fn test_random_forest_classifier() {
+        // Create a feature matrix with 5 features and 10 samples
+        let x = Array2::from_shape_vec(
+            (10, 5),
+            vec![
+                0.1, 1.0, 5.0, 0.2, -0.3, 0.4, -1.0, 5.0, 0.8, 0.1, 0.6, 1.0, 5.0, 1.2, 0.2, 0.9,
+                -1.0, 5.0, 1.8, -0.1, 1.2, 1.0, 5.0, 2.4, 0.3, 1.5, -1.0, 5.0, 3.0, 0.0, 1.8, 1.0,
+                5.0, 3.6, -0.2, 2.1, -1.0, 5.0, 4.2, 0.4, 2.4, 1.0, 5.0, 4.8, -0.1, 2.7, -1.0, 5.0,
+                5.4, 0.2,
+            ],
+        )
+        .unwrap();
+
+        // Create a target vector perfectly correlated with the second feature
+        let y = Array1::from_vec(vec![
+            1i32, -1i32, 1i32, -1i32, 1i32, -1i32, 1i32, -1i32, 1i32, -1i32,
+        ]);
+
+        let params = ModelParams {
+            learning_rate: 0.1,
+            model_type: ModelType::RandomForest {
+                n_trees: 50,
+                max_depth: 4,
+                min_samples_leaf: 1,
+                max_features: 1.0,
+                bootstrap: true,
+                seed: 42,
+            },
+        };
+
+        let mut classifier = RandomForestClassifier::new(params);
+        classifier.fit(&x, &y.to_vec(), None, None).unwrap();
+
+        let predictions = classifier.predict(&x).unwrap();
+        println!("Predictions: {:?}", predictions);
+        println!("OOB accuracy: {:?}", classifier.oob_accuracy());
+
+        assert_eq!(predictions.len(), y.len());
+        for (pred, label) in predictions.iter().zip(y.iter()) {
+            assert_eq!(*pred > 0.5, *label == 1);
+        }
+        assert_eq!(classifier.oob_scores().unwrap().len(), y.len());
+    }
+
+    #[test]
+    fn test_feature_importance_without_splits() {
+        let x = Array2::from_shape_fn((10, 3), |(i, j)| (i * (j + 1)) as f32);
+        let y: Vec<i32> = (0..10).map(|i| if i % 2 == 0 { 1 } else { -1 }).collect();
+
+        let params = ModelParams {
+            learning_rate: 0.1,
+            model_type: ModelType::RandomForest {
+                n_trees: 5,
+                max_depth: 0,
+                min_samples_leaf: 1,
+                max_features: 1.0,
+                bootstrap: false,
+                seed: 42,
+            },
+        };
+        let mut classifier = RandomForestClassifier::new(params);
+        classifier.fit(&x, &y, None, None).unwrap();
+
+        // Depth-0 trees are single leaves, which says nothing about the features
+        assert_eq!(classifier.feature_importance(ImportanceType::Gain), None);
+        assert_eq!(classifier.feature_importance(ImportanceType::SplitCount), None);
+    }
+
+    #[test]
+    fn test_best_split_with_nan() {
+        let x = Array2::from_shape_vec(
+            (6, 1),
+            vec![f32::NAN, 0.1, 3.0, f32::NAN, 0.2, 2.5],
+        )
+        .unwrap();
+        let y = vec![false, false, true, true, false, true];
+        let w = vec![1.0; 6];
+        let samples: Vec<usize> = (0..6).collect();
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let (feature, threshold, decrease) =
+            DecisionTree::best_split(&x, &y, &w, &samples, 1, 1, &mut rng).unwrap();
+        assert_eq!(feature, 0);
+        assert!(threshold.is_finite());
+        assert!(threshold > 0.2 && threshold < 2.5);
+        assert!(decrease > 0.0);
+    }
+}
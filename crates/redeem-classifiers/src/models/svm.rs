@@ -5,11 +5,14 @@ use linfa_svm::SvmParams;
 use linfa_svm::Svm;
 use ndarray::{Array1, Array2};
 
+use crate::error::ModelError;
+use crate::feature_importance::ImportanceType;
 use crate::models::utils::{ModelParams, ModelType};
 use crate::psm_scorer::SemiSupervisedModel;
 
 pub struct SVMClassifier {
     model: Option<Svm<f64, Pr>>,
+    n_features: usize,
     params: ModelParams,
     predictions: Option<linfa::DatasetBase<ndarray::ArrayBase<ndarray::OwnedRepr<f64>, ndarray::Dim<[usize; 2]>>, ndarray::ArrayBase<ndarray::OwnedRepr<Pr>, ndarray::Dim<[usize; 1]>>>>,
 }
@@ -18,6 +21,7 @@ impl SVMClassifier {
     pub fn new(params: ModelParams) -> Self {
         SVMClassifier {
             model: None,
+            n_features: 0,
             params,
             predictions: None,
         }
@@ -31,7 +35,34 @@ impl SemiSupervisedModel for SVMClassifier {
         y: &[i32],
         x_eval: Option<&Array2<f32>>,
         y_eval: Option<&[i32]>,
-    ) {
+    ) -> Result<(), ModelError> {
+        self.fit_weighted(x, y, &vec![1.0; x.nrows()], x_eval, y_eval)
+    }
+
+    /// linfa's SVM only supports per-class penalties, so the `c` pair is scaled by the mean
+    /// weight of the targets and of the decoys.
+    fn fit_weighted(
+        &mut self,
+        x: &Array2<f32>,
+        y: &[i32],
+        sample_weight: &[f32],
+        _x_eval: Option<&Array2<f32>>,
+        _y_eval: Option<&[i32]>,
+    ) -> Result<(), ModelError> {
+        if sample_weight.len() != x.nrows() {
+            return Err(ModelError::InvalidParams(format!("Expected {} sample weights, got {}", x.nrows(), sample_weight.len())));
+        }
+        let mean_weight = |target: bool| -> f64 {
+            let weights: Vec<f64> = y
+                .iter()
+                .zip(sample_weight.iter())
+                .filter(|(&l, _)| (l == 1) == target)
+                .map(|(_, &w)| w as f64)
+                .collect();
+            if weights.is_empty() { 1.0 } else { weights.iter().sum::<f64>() / weights.len() as f64 }
+        };
+        let (target_weight, decoy_weight) = (mean_weight(true), mean_weight(false));
+
         // Convert y to [0, 1] for regular binary labels
         // Note: we set targets (original 1) as 1 and decoys (original -1) as 0, so that the scores are positive for targets and negative for decoys
         // TODO: this maybe should be done outside of the model
@@ -65,7 +96,7 @@ impl SemiSupervisedModel for SVMClassifier {
             polynomial_kernel_degree,
         } = &self.params.model_type
         {
-            let (c1, c2) = *c;
+            let (c1, c2) = (c.0 * target_weight, c.1 * decoy_weight);
             let eps = *eps;
             let kernel = kernel.clone();
 
@@ -78,33 +109,59 @@ impl SemiSupervisedModel for SVMClassifier {
                 "gauss" => model.gaussian_kernel(*gaussian_kernel_eps),
                 "poly" => model.polynomial_kernel(*polynomial_kernel_constant, *polynomial_kernel_degree),
                 _ => {
-                    eprintln!("Error: Unsupported kernel type: {}. Valid options are: linear, gauss, poly", kernel);
-                    return; // Exit early if the kernel type is unsupported
+                    return Err(ModelError::InvalidParams(format!("Unsupported kernel type: {}. Valid options are: linear, gauss, poly", kernel)));
                 }
             };
 
             // Fit the model
             log::trace!("Fitting model...");
-            self.model = Some(<SvmParams<f64, Pr> as linfa::traits::Fit<_, _, _>>::fit(&model, &dataset).unwrap());
+            let fitted = <SvmParams<f64, Pr> as linfa::traits::Fit<_, _, _>>::fit(&model, &dataset)
+                .map_err(|e| ModelError::Training(e.to_string()))?;
+            self.model = Some(fitted);
+            self.n_features = x.ncols();
             log::trace!("Model fitted successfully.");
+            Ok(())
         } else {
-            eprintln!("Error: Expected ModelType::SVM but got another type.");
+            Err(ModelError::InvalidParams(format!("Expected ModelType::SVM params, got {:?}", self.params.model_type)))
         }
     }
 
-    fn predict(&self, x: &Array2<f32>) -> Vec<f32> {
-        todo!()
+    fn predict(&self, x: &Array2<f32>) -> Result<Vec<f32>, ModelError> {
+        // Convert feature matrix from f32 to f64
+        let x_f64 = x.mapv(|v| v as f64);
+        let predictions = self.model.as_ref().ok_or(ModelError::NotFitted)?.predict(x_f64);
+        // Convert predictions from Pr to Vec<f32>
+        Ok(predictions.targets().iter().map(|&v| *v).collect::<Vec<f32>>())
     }
 
-    fn predict_proba(&mut self, x: &Array2<f32>) -> Vec<f32> {
+    fn predict_proba(&mut self, x: &Array2<f32>) -> Result<Vec<f32>, ModelError> {
         // Convert feature matrix from f32 to f64
         let x_f64 = x.mapv(|v| v as f64);
-        let predictions = self.model.as_ref().unwrap().predict(x_f64);
+        let predictions = self.model.as_ref().ok_or(ModelError::NotFitted)?.predict(x_f64);
         self.predictions = Some(predictions.clone());
         // let tmp = predictions.records();
         let tmp: Vec<Pr> = predictions.targets().to_vec();
         // Convert predictions from ArrayBase<OwnedRepr<f64>, Dim<[usize; 1]>> to Vec<f32>
-        tmp.iter().map(|&v| *v).collect::<Vec<f32>>()
+        Ok(tmp.iter().map(|&v| *v).collect::<Vec<f32>>())
+    }
+
+    /// Absolute weights of the separating hyperplane, only defined for the linear kernel
+    fn feature_importance(&self, importance_type: ImportanceType) -> Option<Vec<f32>> {
+        let is_linear = matches!(&self.params.model_type, ModelType::SVM { kernel, .. } if kernel == "linear");
+        if importance_type != ImportanceType::Weight || !is_linear {
+            return None;
+        }
+        // The weighted sum of the linear kernel is w . x, so w_j is the weighted sum of the j-th unit vector
+        let model = self.model.as_ref()?;
+        Some(
+            (0..self.n_features)
+                .map(|j| {
+                    let mut unit = Array1::<f64>::zeros(self.n_features);
+                    unit[j] = 1.0;
+                    model.weighted_sum(&unit).abs() as f32
+                })
+                .collect(),
+        )
     }
 }
 
@@ -156,10 +213,10 @@ mod tests {
         let mut classifier = SVMClassifier::new(params);
 
         // Fit the classifier
-        classifier.fit(&x, &y.to_vec(), None, None);
+        classifier.fit(&x, &y.to_vec(), None, None).unwrap();
 
         // Make predictions
-        let predictions = classifier.predict_proba(&x);
+        let predictions = classifier.predict_proba(&x).unwrap();
 
         println!("Predictions: {:?}", predictions);
 
@@ -179,5 +236,15 @@ mod tests {
         println!("Confusion Matrix: {:?}", cm);
 
         println!("accuracy {}, MCC {}", cm.accuracy(), cm.mcc());
+
+        // The second feature separates the classes, so it has the largest absolute weight
+        let importance = classifier.feature_importance(ImportanceType::Weight).unwrap();
+        assert_eq!(importance.len(), 5);
+        let largest = importance
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map(|(i, _)| i);
+        assert_eq!(largest, Some(1));
     }
 }
@@ -36,6 +36,35 @@ pub enum ModelType {
         training_optimization_level: u8,
         loss_type: String,
     },
+    LinearSVM {
+        c_grid: Vec<f64>,
+        class_ratio_grid: Vec<f64>,
+        max_iter: usize,
+        eps: f64,
+        eval_fdr: f32,
+    },
+    MLP {
+        hidden_layers: Vec<usize>,
+        dropout: f32,
+        epochs: usize,
+        batch_size: usize,
+        early_stopping_patience: usize,
+        /// Device to train on, `cpu`, `cuda` or `cuda:<index>`. CUDA requires the `cuda` feature.
+        #[serde(default = "default_mlp_device")]
+        device: String,
+    },
+    RandomForest {
+        n_trees: usize,
+        max_depth: usize,
+        min_samples_leaf: usize,
+        max_features: f64,
+        bootstrap: bool,
+        seed: u64,
+    },
+}
+
+fn default_mlp_device() -> String {
+    "cpu".to_string()
 }
 
 impl Default for ModelType {
@@ -60,6 +89,29 @@ impl ModelType {
                 training_optimization_level: 2,
                 loss_type:"LogLikelyhood".to_string(),
             }),
+            "linear_svm" | "percolator" => Ok(ModelType::LinearSVM {
+                c_grid: vec![0.1, 1.0, 10.0],
+                class_ratio_grid: vec![1.0, 3.0, 10.0],
+                max_iter: 100,
+                eps: 0.1,
+                eval_fdr: 0.01,
+            }),
+            "mlp" => Ok(ModelType::MLP {
+                hidden_layers: vec![32, 16],
+                dropout: 0.1,
+                epochs: 50,
+                batch_size: 256,
+                early_stopping_patience: 5,
+                device: default_mlp_device(),
+            }),
+            "random_forest" | "rf" => Ok(ModelType::RandomForest {
+                n_trees: 100,
+                max_depth: 10,
+                min_samples_leaf: 5,
+                max_features: 0.33,
+                bootstrap: true,
+                seed: 42,
+            }),
             #[cfg(feature = "xgboost")]
             "xgboost" => Ok(ModelType::XGBoost {
                 max_depth: 6,
@@ -104,3 +156,41 @@ impl Default for ModelParams {
         }
     }
 }
+
+/// Weights that balance targets and decoys, so that both classes contribute equally to training
+///
+/// Each sample of a class is weighted by `n_samples / (2 * n_class)`, as in scikit-learn's
+/// `class_weight="balanced"`. Labels other than 1 are counted as decoys.
+///
+/// # Arguments
+///
+/// * `y` - The labels, 1 for targets and -1 for decoys
+///
+/// # Returns
+///
+/// One weight per sample
+pub fn balanced_class_weights(y: &[i32]) -> Vec<f32> {
+    let n_targets = y.iter().filter(|&&l| l == 1).count();
+    let n_decoys = y.len() - n_targets;
+    let weight = |n_class: usize| if n_class == 0 { 1.0 } else { y.len() as f32 / (2.0 * n_class as f32) };
+    let (target_weight, decoy_weight) = (weight(n_targets), weight(n_decoys));
+    y.iter()
+        .map(|&l| if l == 1 { target_weight } else { decoy_weight })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_balanced_class_weights() {
+        let y = vec![1, 1, 1, -1];
+        let weights = balanced_class_weights(&y);
+        assert_eq!(weights, vec![2.0 / 3.0, 2.0 / 3.0, 2.0 / 3.0, 2.0]);
+
+        // Both classes have the same total weight
+        let target_total: f32 = weights.iter().zip(&y).filter(|(_, &l)| l == 1).map(|(w, _)| w).sum();
+        assert!((target_total - 2.0).abs() < 1e-6);
+    }
+}
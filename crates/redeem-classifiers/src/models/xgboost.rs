@@ -14,6 +14,8 @@ use xgboost::{
 
 use sage_core::scoring::Feature;
 
+use crate::error::ModelError;
+use crate::feature_importance::ImportanceType;
 use crate::models::utils::{ModelType, ModelParams};
 use crate::psm_scorer::SemiSupervisedModel;
 
@@ -69,8 +71,47 @@ fn eval_auc(preds: &[f32], dtrain: &DMatrix) -> f32 {
     auc / (total_pos * total_neg)
 }
 
+/// Split counts or total gain per feature, parsed from a text dump of the booster with statistics
+///
+/// Split nodes are dumped as `0:[f1<0.5] yes=1,no=2,missing=1,gain=4.2,cover=10`. Total gains
+/// are normalized to sum to 1, as for the random forest.
+fn dump_importance(dump: &str, n_features: usize, importance_type: ImportanceType) -> Option<Vec<f32>> {
+    let mut importances = vec![0.0f64; n_features];
+    for line in dump.lines() {
+        let Some(start) = line.find("[f") else {
+            continue;
+        };
+        let feature: usize = line[start + 2..]
+            .split(|c: char| !c.is_ascii_digit())
+            .next()?
+            .parse()
+            .ok()?;
+        let value = match importance_type {
+            ImportanceType::SplitCount => 1.0,
+            ImportanceType::Gain => line
+                .split("gain=")
+                .nth(1)?
+                .split(',')
+                .next()?
+                .trim()
+                .parse()
+                .ok()?,
+            _ => return None,
+        };
+        *importances.get_mut(feature)? += value;
+    }
+    if importance_type == ImportanceType::Gain {
+        let total: f64 = importances.iter().sum();
+        if total > 0.0 {
+            importances.iter_mut().for_each(|v| *v /= total);
+        }
+    }
+    Some(importances.into_iter().map(|v| v as f32).collect())
+}
+
 pub struct XGBoostClassifier {
     booster: Option<Booster>,
+    n_features: usize,
     params: ModelParams,
 }
 
@@ -78,6 +119,7 @@ impl XGBoostClassifier {
     pub fn new(params: ModelParams) -> Self {
         XGBoostClassifier {
             booster: None,
+            n_features: 0,
             params,
         }
     }
@@ -90,31 +132,51 @@ impl SemiSupervisedModel for XGBoostClassifier {
         y: &[i32],
         x_eval: Option<&Array2<f32>>,
         y_eval: Option<&[i32]>,
-    ) {
+    ) -> Result<(), ModelError> {
+        self.fit_weighted(x, y, &vec![1.0; x.nrows()], x_eval, y_eval)
+    }
+
+    fn fit_weighted(
+        &mut self,
+        x: &Array2<f32>,
+        y: &[i32],
+        sample_weight: &[f32],
+        x_eval: Option<&Array2<f32>>,
+        y_eval: Option<&[i32]>,
+    ) -> Result<(), ModelError> {
+        if sample_weight.len() != x.nrows() {
+            return Err(ModelError::InvalidParams(format!("Expected {} sample weights, got {}", x.nrows(), sample_weight.len())));
+        }
+        let training_err = |e: &dyn std::fmt::Display| ModelError::Training(e.to_string());
+
         // Convert y to [0, 1] for XGBoost binary regression
         // Note: we set targets (original 1) as 1 and decoys (original -1) as 0, so that the scores are positive for targets and negative for decoys
         // TODO: this maybe should be done outside of the model
         let y = y.iter().map(|&l| if l == 1 { 1 } else { 0 }).collect::<Vec<i32>>();
-        let y_eval = Some(y_eval.unwrap().iter().map(|&l| if l == 1 { 1 } else { 0 }).collect::<Vec<i32>>());
+        let y_eval = y_eval.map(|y_e| y_e.iter().map(|&l| if l == 1 { 1 } else { 0 }).collect::<Vec<i32>>());
     
         // Convert feature matrix into DMatrix
-        let mut dmat = DMatrix::from_dense(x.as_slice().unwrap(), x.nrows()).unwrap();
-        dmat.set_labels(&y.iter().map(|&l| l as f32).collect::<Vec<f32>>()).unwrap();
+        let x = x.as_standard_layout();
+        let x_slice = x.as_slice().ok_or_else(|| ModelError::Training("Feature matrix is not contiguous".to_string()))?;
+        let mut dmat = DMatrix::from_dense(x_slice, x.nrows()).map_err(|e| training_err(&e))?;
+        dmat.set_labels(&y.iter().map(|&l| l as f32).collect::<Vec<f32>>()).map_err(|e| training_err(&e))?;
+        dmat.set_weights(sample_weight).map_err(|e| training_err(&e))?;
 
         // println!("TRAIN dmat: {:?}", dmat);
     
-        let mut eval_matrix = None;
-        let dmat_eval = if let (Some(x_e), Some(y_e)) = (x_eval, y_eval) {
-            let mut matrix = DMatrix::from_dense(x_e.as_slice().unwrap(), x_e.nrows()).unwrap();
-            matrix.set_labels(&y_e.iter().map(|&l| l as f32).collect::<Vec<f32>>()).unwrap();
-            eval_matrix = Some(matrix);
-            Some(vec![
-                (&dmat, "train"),
-                (eval_matrix.as_ref().unwrap(), "eval"),
-            ])
+        let eval_matrix = if let (Some(x_e), Some(y_e)) = (x_eval, y_eval) {
+            let x_e = x_e.as_standard_layout();
+            let x_e_slice = x_e.as_slice().ok_or_else(|| ModelError::Training("Evaluation feature matrix is not contiguous".to_string()))?;
+            let mut matrix = DMatrix::from_dense(x_e_slice, x_e.nrows()).map_err(|e| training_err(&e))?;
+            matrix.set_labels(&y_e.iter().map(|&l| l as f32).collect::<Vec<f32>>()).map_err(|e| training_err(&e))?;
+            Some(matrix)
         } else {
             None
         };
+        let dmat_eval = eval_matrix.as_ref().map(|matrix| vec![
+            (&dmat, "train"),
+            (matrix, "eval"),
+        ]);
     
         if let ModelType::XGBoost {
             max_depth,
@@ -129,7 +191,7 @@ impl SemiSupervisedModel for XGBoostClassifier {
                 // .eval_metrics(Metrics::Custom(vec![EvaluationMetric::LogLoss, EvaluationMetric::MAE]))
                 // .num_feature(x.ncols())
                 .build()
-                .unwrap();
+                .map_err(|e| training_err(&e))?;
     
             // Configure the tree-based learning model's parameters
             let tree_params = TreeBoosterParametersBuilder::default()
@@ -137,7 +199,7 @@ impl SemiSupervisedModel for XGBoostClassifier {
                 .max_depth(*max_depth)
                 .eta(self.params.learning_rate)
                 .build()
-                .unwrap();
+                .map_err(|e| training_err(&e))?;
     
             // Overall configuration for Booster
             let booster_params = BoosterParametersBuilder::default()
@@ -145,7 +207,7 @@ impl SemiSupervisedModel for XGBoostClassifier {
                 .learning_params(learning_params)
                 .verbose(*verbose_eval)
                 .build()
-                .unwrap();
+                .map_err(|e| training_err(&e))?;
     
             // Create Training Parameters with evaluation sets if needed
             let training_params = TrainingParametersBuilder::default()
@@ -157,25 +219,36 @@ impl SemiSupervisedModel for XGBoostClassifier {
                 .evaluation_score_direction(Some("high"))
                 .custom_evaluation_fn(Some(eval_auc))
                 .build()
-                .unwrap();
+                .map_err(|e| training_err(&e))?;
     
             // Train the model and store the booster
-            self.booster = Some(Booster::train(&training_params).unwrap());
+            self.booster = Some(Booster::train(&training_params).map_err(|e| training_err(&e))?);
+            self.n_features = x.ncols();
+            Ok(())
         } else {
-            eprintln!("Error: Expected ModelType::XGBoost but got another type.");
+            Err(ModelError::InvalidParams(format!("Expected ModelType::XGBoost params, got {:?}", self.params.model_type)))
         }
     }
     
 
-    fn predict(&self, x: &Array2<f32>) -> Vec<f32> {
-        let dmat = DMatrix::from_dense(x.as_slice().unwrap(), x.nrows()).unwrap();
+    fn predict(&self, x: &Array2<f32>) -> Result<Vec<f32>, ModelError> {
+        let prediction_err = |e: &dyn std::fmt::Display| ModelError::Prediction(e.to_string());
+        let booster = self.booster.as_ref().ok_or(ModelError::NotFitted)?;
+        let x = x.as_standard_layout();
+        let x_slice = x.as_slice().ok_or_else(|| ModelError::Prediction("Feature matrix is not contiguous".to_string()))?;
+        let dmat = DMatrix::from_dense(x_slice, x.nrows()).map_err(|e| prediction_err(&e))?;
         // println!("PREDICT: dmat: {:?}", dmat);
-        self.booster.as_ref().unwrap().predict(&dmat).unwrap()
+        booster.predict(&dmat).map_err(|e| prediction_err(&e))
     }
 
-    fn predict_proba(&mut self, x: &Array2<f32>) -> Vec<f32> {
+    fn predict_proba(&mut self, x: &Array2<f32>) -> Result<Vec<f32>, ModelError> {
         self.predict(x)
     }
+
+    fn feature_importance(&self, importance_type: ImportanceType) -> Option<Vec<f32>> {
+        let dump = self.booster.as_ref()?.dump_model(true, None).ok()?;
+        dump_importance(&dump, self.n_features, importance_type)
+    }
 }
 
 #[cfg(test)]
@@ -183,6 +256,26 @@ mod tests {
     use super::*;
     use ndarray::{Array1, Array2};
 
+    #[test]
+    fn test_dump_importance() {
+        let dump = "booster[0]:\n\
+            0:[f1<0.5] yes=1,no=2,missing=1,gain=6,cover=10\n\
+            \t1:[f2<3] yes=3,no=4,missing=3,gain=2,cover=5\n\
+            \t\t3:leaf=0.1,cover=2\n\
+            \t\t4:leaf=0.2,cover=3\n\
+            \t2:leaf=-0.3,cover=5\n\
+            booster[1]:\n\
+            0:[f1<0.5] yes=1,no=2,missing=1,gain=2,cover=10\n\
+            \t1:leaf=0.1,cover=5\n\
+            \t2:leaf=-0.1,cover=5\n";
+
+        let counts = dump_importance(dump, 3, ImportanceType::SplitCount).unwrap();
+        assert_eq!(counts, vec![0.0, 2.0, 1.0]);
+        let gains = dump_importance(dump, 3, ImportanceType::Gain).unwrap();
+        assert_eq!(gains, vec![0.0, 0.8, 0.2]);
+        assert!(dump_importance(dump, 3, ImportanceType::Weight).is_none());
+    }
+
     #[test]
     fn test_xgboost_classifier() {
         // Create a feature matrix with 5 features and 10 samples
@@ -215,10 +308,10 @@ mod tests {
         let mut classifier = XGBoostClassifier::new(params);
 
         // Fit the classifier
-        classifier.fit(&x, &y.to_vec(), Some(&x), Some(&y.to_vec()));
+        classifier.fit(&x, &y.to_vec(), Some(&x), Some(&y.to_vec())).unwrap();
 
         // Make predictions
-        let predictions = classifier.predict(&x);
+        let predictions = classifier.predict(&x).unwrap();
 
         println!("Predictions: {:?}", predictions);
         // println!("y: {:?}", y.to_vec());
@@ -0,0 +1,336 @@
+//! Feature preprocessing applied before training the classifier models.
+//!
+//! The [`Preprocessor`] is fitted once on the feature matrix and stores all the statistics it
+//! needs (imputation values, log-transformed columns, centering and scaling factors, removed
+//! columns), so that exactly the same transform can be applied to new data at prediction time.
+
+use ndarray::{Array2, Axis};
+use serde::{Deserialize, Serialize};
+
+use crate::error::ModelError;
+
+/// Method used to scale the features
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ScalingMethod {
+    /// Keep the features on their original scale
+    None,
+    /// Subtract the mean and divide by the standard deviation
+    #[default]
+    ZScore,
+    /// Subtract the median and divide by the interquartile range
+    Robust,
+}
+
+/// Configuration of the preprocessing stage
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreprocessingConfig {
+    /// How features are scaled
+    pub scaling: ScalingMethod,
+    /// Apply a signed `ln(1 + |x|)` transform to features whose absolute skewness exceeds this
+    /// threshold. `None` disables the log transform.
+    pub log_skewness_threshold: Option<f32>,
+    /// Replace NaN and infinite values. NaN is replaced by the column median, `+inf` and `-inf`
+    /// by the largest and smallest finite values of the column.
+    pub impute: bool,
+    /// Remove features that are constant after imputation
+    pub remove_constant: bool,
+}
+
+impl Default for PreprocessingConfig {
+    fn default() -> Self {
+        PreprocessingConfig {
+            scaling: ScalingMethod::ZScore,
+            log_skewness_threshold: Some(5.0),
+            impute: true,
+            remove_constant: true,
+        }
+    }
+}
+
+/// Statistics of a single feature column
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ColumnStats {
+    log_transform: bool,
+    nan_fill: f32,
+    pos_inf_fill: f32,
+    neg_inf_fill: f32,
+    center: f32,
+    scale: f32,
+}
+
+/// A fitted preprocessing transform
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Preprocessor {
+    config: PreprocessingConfig,
+    columns: Vec<ColumnStats>,
+    /// Indices of the input columns kept after constant-column removal
+    kept_columns: Vec<usize>,
+}
+
+fn signed_log1p(v: f32) -> f32 {
+    v.signum() * v.abs().ln_1p()
+}
+
+/// Linear interpolation quantile of sorted values
+fn quantile(sorted: &[f32], q: f32) -> f32 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let pos = q * (sorted.len() - 1) as f32;
+    let lo = pos.floor() as usize;
+    let hi = pos.ceil() as usize;
+    sorted[lo] + (sorted[hi] - sorted[lo]) * (pos - lo as f32)
+}
+
+fn skewness(values: &[f32]) -> f32 {
+    let n = values.len() as f64;
+    if n < 3.0 {
+        return 0.0;
+    }
+    let mean = values.iter().map(|&v| v as f64).sum::<f64>() / n;
+    let m2 = values.iter().map(|&v| (v as f64 - mean).powi(2)).sum::<f64>() / n;
+    let m3 = values.iter().map(|&v| (v as f64 - mean).powi(3)).sum::<f64>() / n;
+    if m2 <= f64::EPSILON {
+        0.0
+    } else {
+        (m3 / m2.powf(1.5)) as f32
+    }
+}
+
+impl Preprocessor {
+    /// Fit the preprocessing statistics on a feature matrix.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - The features, shape (n_samples, n_features)
+    /// * `config` - The preprocessing configuration
+    ///
+    /// # Returns
+    ///
+    /// A fitted `Preprocessor`
+    pub fn fit(x: &Array2<f32>, config: PreprocessingConfig) -> Self {
+        let mut columns = Vec::with_capacity(x.ncols());
+        let mut kept_columns = Vec::with_capacity(x.ncols());
+
+        for (j, col) in x.axis_iter(Axis(1)).enumerate() {
+            let mut finite: Vec<f32> = col.iter().filter(|v| v.is_finite()).cloned().collect();
+            finite.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+            let log_transform = match config.log_skewness_threshold {
+                Some(threshold) => skewness(&finite).abs() > threshold,
+                None => false,
+            };
+            if log_transform {
+                // signed_log1p is monotonic, so the values stay sorted
+                finite.iter_mut().for_each(|v| *v = signed_log1p(*v));
+            }
+
+            let nan_fill = quantile(&finite, 0.5);
+            let pos_inf_fill = finite.last().cloned().unwrap_or(0.0);
+            let neg_inf_fill = finite.first().cloned().unwrap_or(0.0);
+
+            // Scaling statistics are computed on the imputed values
+            let mut imputed: Vec<f32> = if config.impute {
+                col.iter()
+                    .map(|&v| {
+                        let v = if log_transform { signed_log1p(v) } else { v };
+                        if v.is_nan() {
+                            nan_fill
+                        } else if v == f32::INFINITY {
+                            pos_inf_fill
+                        } else if v == f32::NEG_INFINITY {
+                            neg_inf_fill
+                        } else {
+                            v
+                        }
+                    })
+                    .collect()
+            } else {
+                finite.clone()
+            };
+            imputed.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+            let (center, scale) = match config.scaling {
+                ScalingMethod::None => (0.0, 1.0),
+                ScalingMethod::ZScore => {
+                    let n = imputed.len().max(1) as f64;
+                    let mean = imputed.iter().map(|&v| v as f64).sum::<f64>() / n;
+                    let var = imputed.iter().map(|&v| (v as f64 - mean).powi(2)).sum::<f64>() / n;
+                    (mean as f32, var.sqrt() as f32)
+                }
+                ScalingMethod::Robust => {
+                    let iqr = quantile(&imputed, 0.75) - quantile(&imputed, 0.25);
+                    (quantile(&imputed, 0.5), iqr)
+                }
+            };
+            let scale = if scale.is_finite() && scale > 1e-8 { scale } else { 1.0 };
+
+            let is_constant = pos_inf_fill - neg_inf_fill <= 0.0;
+            if !(config.remove_constant && is_constant) {
+                kept_columns.push(j);
+            } else {
+                log::debug!("Removing constant feature column {}", j);
+            }
+
+            columns.push(ColumnStats {
+                log_transform,
+                nan_fill,
+                pos_inf_fill,
+                neg_inf_fill,
+                center,
+                scale,
+            });
+        }
+
+        log::debug!(
+            "Fitted preprocessing on {} features: {} log-transformed, {} removed",
+            x.ncols(),
+            columns.iter().filter(|c| c.log_transform).count(),
+            x.ncols() - kept_columns.len()
+        );
+
+        Preprocessor {
+            config,
+            columns,
+            kept_columns,
+        }
+    }
+
+    /// Apply the fitted transform to a feature matrix.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - The features, shape (n_samples, n_features), with the same columns as the data used in `fit`
+    ///
+    /// # Returns
+    ///
+    /// The transformed features, shape (n_samples, n_kept_features), or
+    /// [`ModelError::InvalidParams`] if `x` does not have the number of columns seen in `fit`
+    pub fn transform(&self, x: &Array2<f32>) -> Result<Array2<f32>, ModelError> {
+        if x.ncols() != self.columns.len() {
+            return Err(ModelError::InvalidParams(format!(
+                "Preprocessor was fitted on {} features, but got {}",
+                self.columns.len(),
+                x.ncols()
+            )));
+        }
+        Ok(self.apply(x))
+    }
+
+    /// Apply the fitted transform to a feature matrix with the columns seen in `fit`
+    fn apply(&self, x: &Array2<f32>) -> Array2<f32> {
+        let mut out = x.select(Axis(1), &self.kept_columns);
+        for (mut col, &j) in out.axis_iter_mut(Axis(1)).zip(self.kept_columns.iter()) {
+            let stats = &self.columns[j];
+            let impute = self.config.impute;
+            col.mapv_inplace(|v| {
+                let v = if stats.log_transform { signed_log1p(v) } else { v };
+                let v = if v.is_nan() && impute {
+                    stats.nan_fill
+                } else if v == f32::INFINITY && impute {
+                    stats.pos_inf_fill
+                } else if v == f32::NEG_INFINITY && impute {
+                    stats.neg_inf_fill
+                } else {
+                    v
+                };
+                (v - stats.center) / stats.scale
+            });
+        }
+        out
+    }
+
+    /// Fit the preprocessing statistics and transform the same feature matrix
+    pub fn fit_transform(x: &Array2<f32>, config: PreprocessingConfig) -> (Self, Array2<f32>) {
+        let preprocessor = Self::fit(x, config);
+        let transformed = preprocessor.apply(x);
+        (preprocessor, transformed)
+    }
+
+    /// Indices of the input columns kept by the transform
+    pub fn kept_columns(&self) -> &[usize] {
+        &self.kept_columns
+    }
+
+    /// Names of the features kept by the transform
+    pub fn kept_feature_names(&self, feature_names: &[String]) -> Vec<String> {
+        self.kept_columns
+            .iter()
+            .filter_map(|&j| feature_names.get(j).cloned())
+            .collect()
+    }
+
+    /// The configuration used to fit the preprocessor
+    pub fn config(&self) -> &PreprocessingConfig {
+        &self.config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::array;
+
+    #[test]
+    fn test_preprocessor_zscore_impute_and_constant_removal() {
+        let x = array![
+            [1.0, 5.0, f32::NAN],
+            [2.0, 5.0, 1.0],
+            [3.0, 5.0, f32::INFINITY],
+            [4.0, 5.0, 3.0],
+        ];
+
+        let (preprocessor, transformed) = Preprocessor::fit_transform(
+            &x,
+            PreprocessingConfig {
+                log_skewness_threshold: None,
+                ..Default::default()
+            },
+        );
+
+        // The constant column is removed
+        assert_eq!(preprocessor.kept_columns(), &[0, 2]);
+        assert_eq!(transformed.ncols(), 2);
+        assert!(transformed.iter().all(|v| v.is_finite()));
+
+        // Z-scored columns have zero mean and unit variance
+        for col in transformed.axis_iter(Axis(1)) {
+            let mean = col.sum() / col.len() as f32;
+            let var = col.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / col.len() as f32;
+            assert!(mean.abs() < 1e-5);
+            assert!((var - 1.0).abs() < 1e-4);
+        }
+
+        // The same transform is applied to new data
+        let new = preprocessor.transform(&array![[2.5, 5.0, f32::NAN]]).unwrap();
+        assert_eq!(new.ncols(), 2);
+        assert!((new[[0, 0]] - 0.0).abs() < 1e-5);
+
+        // New data must have the columns seen in `fit`
+        assert!(matches!(
+            preprocessor.transform(&array![[2.5, 5.0]]),
+            Err(ModelError::InvalidParams(_))
+        ));
+    }
+
+    #[test]
+    fn test_preprocessor_log_transform_heavy_tailed() {
+        let mut values = vec![1.0f32; 99];
+        values.push(1.0e6);
+        let x = Array2::from_shape_vec((100, 1), values).unwrap();
+
+        let preprocessor = Preprocessor::fit(
+            &x,
+            PreprocessingConfig {
+                scaling: ScalingMethod::None,
+                log_skewness_threshold: Some(2.0),
+                impute: true,
+                remove_constant: true,
+            },
+        );
+        let transformed = preprocessor.transform(&x).unwrap();
+
+        assert!((transformed[[99, 0]] - 1.0e6f32.ln_1p()).abs() < 1e-4);
+        assert!((transformed[[0, 0]] - 2.0f32.ln()).abs() < 1e-6);
+    }
+}
@@ -1,18 +1,27 @@
 use std::f64;
 
 use ndarray::{Array1, Array2};
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
-use rand::thread_rng;
+use rand::SeedableRng;
 use serde::{Deserialize, Serialize};
 
-use crate::data_handling::{Experiment, PsmMetadata};
+use crate::data_handling::{Experiment, FoldGrouping, PsmMetadata};
+use crate::error::{ExperimentError, ModelError};
+use crate::feature_selection::classification::SelectKBestClassif;
+use crate::feature_importance::{permutation_importance_with, FeatureImportance, ImportanceType};
+use crate::preprocessing::{PreprocessingConfig, Preprocessor};
+use crate::stats::ScoreCalibration;
 
-use crate::models::utils::{ModelParams, ModelType};
+use crate::models::utils::{balanced_class_weights, ModelParams, ModelType};
 #[cfg(feature = "xgboost")]
 use crate::models::xgboost::XGBoostClassifier;
 #[cfg(feature = "linfa")]
 use crate::models::svm::SVMClassifier;
 use crate::models::gbdt::GBDTClassifier;
+use crate::models::linear_svm::LinearSVMClassifier;
+use crate::models::mlp::MLPClassifier;
+use crate::models::random_forest::RandomForestClassifier;
 
 
 pub trait SemiSupervisedModel {
@@ -22,16 +31,153 @@ pub trait SemiSupervisedModel {
         y: &[i32],
         x_eval: Option<&Array2<f32>>,
         y_eval: Option<&[i32]>,
-    );
-    fn predict(&self, x: &Array2<f32>) -> Vec<f32>;
-    fn predict_proba(&mut self, x: &Array2<f32>) -> Vec<f32>;
+    ) -> Result<(), ModelError>;
+    fn predict(&self, x: &Array2<f32>) -> Result<Vec<f32>, ModelError>;
+    fn predict_proba(&mut self, x: &Array2<f32>) -> Result<Vec<f32>, ModelError>;
+    /// Fit with one non-negative weight per training sample. Models that do not support sample
+    /// weights ignore them and fall back to [`SemiSupervisedModel::fit`].
+    fn fit_weighted(
+        &mut self,
+        x: &Array2<f32>,
+        y: &[i32],
+        sample_weight: &[f32],
+        x_eval: Option<&Array2<f32>>,
+        y_eval: Option<&[i32]>,
+    ) -> Result<(), ModelError> {
+        log::debug!("Sample weights are not supported by this model and are ignored ({} weights)", sample_weight.len());
+        self.fit(x, y, x_eval, y_eval)
+    }
+    /// Model-specific feature importance, one value per feature, or `None` if the model does not
+    /// support the requested kind. Use [`crate::feature_importance::permutation_importance`] for a
+    /// model-agnostic estimate.
+    fn feature_importance(&self, _importance_type: ImportanceType) -> Option<Vec<f32>> {
+        None
+    }
 }
 
-pub struct SemiSupervisedLearner {
+/// Scores recorded while fitting a [`SemiSupervisedLearner`], used to build rescoring reports
+#[derive(Debug, Clone, Default)]
+pub struct FitHistory {
+    /// Name of the best single feature used to initialize the labels
+    pub initial_feature: Option<String>,
+    /// Scores of the best single feature, oriented so that higher is better
+    pub initial_scores: Option<Array1<f32>>,
+    /// Calibrated out-of-fold scores after each training iteration
+    pub iteration_scores: Vec<Array1<f32>>,
+    /// Number of targets below the training FDR after each training iteration
+    pub iteration_identifications: Vec<usize>,
+    /// Scores of the last iteration from the models whose training set contained each PSM,
+    /// averaged over folds (NaN for PSMs never used for training). Used to detect overfitting.
+    pub in_sample_scores: Option<Array1<f32>>,
+}
+
+/// Create an unfitted model of the given type
+fn create_model(model_type: ModelType, learning_rate: f32) -> Box<dyn SemiSupervisedModel> {
+    match model_type {
+        ModelType::GBDT { max_depth, num_boost_round, debug, training_optimization_level, loss_type } => {
+            let params = ModelParams {
+                learning_rate,
+                model_type: ModelType::GBDT {
+                    max_depth,
+                    num_boost_round,
+                    debug,
+                    training_optimization_level,
+                    loss_type
+                },
+            };
+            Box::new(GBDTClassifier::new(params))
+        }
+        ModelType::LinearSVM { c_grid, class_ratio_grid, max_iter, eps, eval_fdr } => {
+            let params = ModelParams {
+                learning_rate,
+                model_type: ModelType::LinearSVM { c_grid, class_ratio_grid, max_iter, eps, eval_fdr },
+            };
+            Box::new(LinearSVMClassifier::new(params))
+        }
+        ModelType::MLP { hidden_layers, dropout, epochs, batch_size, early_stopping_patience, device } => {
+            let params = ModelParams {
+                learning_rate,
+                model_type: ModelType::MLP { hidden_layers, dropout, epochs, batch_size, early_stopping_patience, device },
+            };
+            Box::new(MLPClassifier::new(params))
+        }
+        ModelType::RandomForest { n_trees, max_depth, min_samples_leaf, max_features, bootstrap, seed } => {
+            let params = ModelParams {
+                learning_rate,
+                model_type: ModelType::RandomForest { n_trees, max_depth, min_samples_leaf, max_features, bootstrap, seed },
+            };
+            Box::new(RandomForestClassifier::new(params))
+        }
+        #[cfg(feature = "xgboost")]
+        ModelType::XGBoost { max_depth, num_boost_round, early_stopping_rounds, verbose_eval } => {
+            let params = ModelParams {
+                learning_rate,
+                model_type: ModelType::XGBoost { max_depth, num_boost_round, early_stopping_rounds, verbose_eval },
+            };
+            Box::new(XGBoostClassifier::new(params))
+        }
+        #[cfg(feature = "linfa")]
+        ModelType::SVM { eps, c, kernel, gaussian_kernel_eps, polynomial_kernel_constant, polynomial_kernel_degree } => {
+            let params = ModelParams {
+                learning_rate,
+                model_type: ModelType::SVM {
+                    eps,
+                    c,
+                    kernel,
+                    gaussian_kernel_eps,
+                    polynomial_kernel_constant,
+                    polynomial_kernel_degree,
+                },
+            };
+            Box::new(SVMClassifier::new(params))
+        }
+        #[cfg(not(any(feature = "xgboost", feature = "linfa")))]
+        _ => panic!("No model selected. Please enable the 'xgboost' or 'linfa' feature."),
+    }
+}
+
+/// A model trained on one cross-validation fold, with the calibration of its scores
+struct FoldModel {
     model: Box<dyn SemiSupervisedModel>,
+    calibration: ScoreCalibration,
+    /// Columns of the preprocessed features selected on the fold's training PSMs, if feature
+    /// selection is enabled
+    selected_features: Option<Vec<usize>>,
+}
+
+impl FoldModel {
+    /// Keep only the columns selected for this fold, or return a copy if feature selection is disabled
+    fn select_features(&self, x: &Array2<f32>) -> Array2<f32> {
+        select_columns(x, self.selected_features.as_deref())
+    }
+}
+
+/// Keep only the given columns, or return a copy if `columns` is `None`
+fn select_columns(x: &Array2<f32>, columns: Option<&[usize]>) -> Array2<f32> {
+    match columns {
+        Some(columns) => x.select(ndarray::Axis(1), columns),
+        None => x.clone(),
+    }
+}
+
+pub struct SemiSupervisedLearner {
+    model_type: ModelType,
+    learning_rate: f32,
+    fold_models: Vec<FoldModel>,
     train_fdr: f32,
     xeval_num_iter: usize,
     class_pct: Option<(f64, f64)>,
+    max_iter: usize,
+    convergence_tol: f32,
+    class_balancing: bool,
+    fold_grouping: FoldGrouping,
+    seed: u64,
+    preprocessing: Option<PreprocessingConfig>,
+    preprocessor: Option<Preprocessor>,
+    feature_selection: Option<SelectKBestClassif>,
+    n_features: usize,
+    feature_names: Vec<String>,
+    history: FitHistory,
 }
 
 impl SemiSupervisedLearner {
@@ -54,52 +200,143 @@ impl SemiSupervisedLearner {
         xeval_num_iter: usize,
         class_pct: Option<(f64, f64)>,
     ) -> Self {
-        let model: Box<dyn SemiSupervisedModel> = match model_type {
-            ModelType::GBDT { max_depth, num_boost_round, debug, training_optimization_level, loss_type } => {
-                let params = ModelParams {
-                    learning_rate,
-                    model_type: ModelType::GBDT {
-                        max_depth,
-                        num_boost_round,
-                        debug,
-                        training_optimization_level,
-                        loss_type
-                    },
-                };
-                Box::new(GBDTClassifier::new(params))
-            }
-            #[cfg(feature = "xgboost")]
-            ModelType::XGBoost { max_depth, num_boost_round, early_stopping_rounds, verbose_eval } => {
-                let params = ModelParams {
-                    learning_rate,
-                    model_type: ModelType::XGBoost { max_depth, num_boost_round, early_stopping_rounds, verbose_eval },
-                };
-                Box::new(XGBoostClassifier::new(params))
-            }
-            #[cfg(feature = "linfa")]
-            ModelType::SVM { eps, c, kernel, gaussian_kernel_eps, polynomial_kernel_constant, polynomial_kernel_degree } => {
-                let params = ModelParams {
-                    learning_rate,
-                    model_type: ModelType::SVM {
-                        eps,
-                        c,
-                        kernel,
-                        gaussian_kernel_eps,
-                        polynomial_kernel_constant,
-                        polynomial_kernel_degree,
-                    },
-                };
-                Box::new(SVMClassifier::new(params))
-            }
-            #[cfg(not(any(feature = "xgboost", feature = "linfa")))]
-            _ => panic!("No model selected. Please enable the 'xgboost' or 'linfa' feature."),
-        };
-
         SemiSupervisedLearner {
-            model,
+            model_type,
+            learning_rate,
+            fold_models: Vec::new(),
             train_fdr,
             xeval_num_iter,
             class_pct,
+            max_iter: 10,
+            convergence_tol: 0.0,
+            class_balancing: false,
+            fold_grouping: FoldGrouping::default(),
+            seed: 42,
+            preprocessing: None,
+            preprocessor: None,
+            feature_selection: None,
+            n_features: 0,
+            feature_names: Vec::new(),
+            history: FitHistory::default(),
+        }
+    }
+
+    /// Set the maximum number of training iterations (default: 10)
+    ///
+    /// Each iteration retrains all cross-validation fold models on labels derived from the scores
+    /// of the previous iteration, as in Percolator.
+    pub fn with_max_iterations(mut self, max_iter: usize) -> Self {
+        self.max_iter = max_iter.max(1);
+        self
+    }
+
+    /// Set the convergence tolerance of the training iterations (default: 0.0)
+    ///
+    /// Training stops once an iteration increases the number of targets below the training FDR
+    /// by no more than this fraction of the previous count.
+    pub fn with_convergence_tolerance(mut self, convergence_tol: f32) -> Self {
+        self.convergence_tol = convergence_tol;
+        self
+    }
+
+    /// Weight targets and decoys so that both classes contribute equally to training (default: false)
+    ///
+    /// The class weights are computed on the training PSMs of each fold and multiplied with the
+    /// sample weights given to [`SemiSupervisedLearner::fit_weighted`]. All the built-in models
+    /// support sample weights; the linfa SVM only uses the mean weight of each class.
+    pub fn with_class_balancing(mut self, class_balancing: bool) -> Self {
+        self.class_balancing = class_balancing;
+        self
+    }
+
+    /// Set how PSMs are grouped when creating cross-validation folds (default: by spectrum)
+    pub fn with_fold_grouping(mut self, fold_grouping: FoldGrouping) -> Self {
+        self.fold_grouping = fold_grouping;
+        self
+    }
+
+    /// Set the seed used for fold assignment, subsampling and the evaluation split of each fold (default: 42)
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Preprocess the features before they are passed to the model (default: disabled)
+    ///
+    /// The preprocessor is fitted on all PSMs at the start of [`SemiSupervisedLearner::fit`] and
+    /// stored with the learner, so the same transform is applied in [`SemiSupervisedLearner::predict`].
+    /// It only uses the feature values, not the target/decoy labels.
+    pub fn with_preprocessing(mut self, config: PreprocessingConfig) -> Self {
+        self.preprocessing = Some(config);
+        self
+    }
+
+    /// The fitted preprocessor, if preprocessing is enabled and the learner has been fitted
+    pub fn preprocessor(&self) -> Option<&Preprocessor> {
+        self.preprocessor.as_ref()
+    }
+
+    /// Automatically select features before fitting (default: disabled)
+    ///
+    /// The selector is run inside each cross-validation fold, on the preprocessed features and
+    /// target/decoy labels of the fold's training PSMs only, so the held-out PSMs never influence
+    /// which features their model sees. Only the selected features are passed to the fold model.
+    /// All features are kept in the experiment, so the `rank` feature is still updated if it is
+    /// not selected.
+    pub fn with_feature_selection(mut self, selector: SelectKBestClassif) -> Self {
+        self.feature_selection = Some(selector);
+        self
+    }
+
+    /// Indices of the input features selected for each fold model of the last training iteration,
+    /// if feature selection is enabled
+    pub fn selected_features(&self) -> Option<Vec<Vec<usize>>> {
+        self.fold_models
+            .iter()
+            .map(|fold_model| {
+                fold_model.selected_features.as_ref().map(|selected| match &self.preprocessor {
+                    Some(preprocessor) => selected.iter().map(|&j| preprocessor.kept_columns()[j]).collect(),
+                    None => selected.clone(),
+                })
+            })
+            .collect()
+    }
+
+    /// Scores recorded during the last call to [`SemiSupervisedLearner::fit`]
+    pub fn history(&self) -> &FitHistory {
+        &self.history
+    }
+
+    /// Names of the preprocessed features, before the feature selection of each fold
+    fn model_feature_names(&self) -> Vec<String> {
+        match &self.preprocessor {
+            Some(preprocessor) => preprocessor.kept_feature_names(&self.feature_names),
+            None => self.feature_names.clone(),
+        }
+    }
+
+    /// Number of preprocessed features, before the feature selection of each fold
+    fn n_model_features(&self) -> usize {
+        match &self.preprocessor {
+            Some(preprocessor) => preprocessor.kept_columns().len(),
+            None => self.n_features,
+        }
+    }
+
+    /// Apply the fitted preprocessing to a feature matrix
+    ///
+    /// Returns [`ModelError::InvalidParams`] if `x` does not have the number of columns seen in `fit`.
+    fn transform(&self, x: &Array2<f32>) -> Result<Array2<f32>, ModelError> {
+        if x.ncols() != self.n_features {
+            return Err(ModelError::InvalidParams(format!(
+                "Expected {} feature columns, got {}",
+                self.n_features,
+                x.ncols()
+            )));
+        }
+        match &self.preprocessor {
+            Some(preprocessor) => preprocessor.transform(x),
+            None => Ok(x.clone()),
         }
     }
 
@@ -115,14 +352,23 @@ impl SemiSupervisedLearner {
         &mut self,
         experiment: &Experiment,
         eval_fdr: f32,
-    ) -> (usize, usize, Array1<i32>, bool, Array1<f32>) {
-        /// Helper function to count targets by feature
+    ) -> Result<(usize, usize, Array1<i32>, bool, Array1<f32>), ExperimentError> {
+        if experiment.x.ncols() == 0 {
+            return Err(ExperimentError::NoFeatures);
+        }
+
+        // Helper function to count targets by feature, features with NaN scores are skipped
         let targets_count_by_feature = |desc: bool| -> Vec<usize> {
             (0..experiment.x.ncols())
                 .map(|col| {
                     let scores = experiment.x.column(col).to_owned();
-                    let labels = experiment.update_labels(&scores, eval_fdr, desc);
-                    labels.iter().filter(|&&x| x == 1).count()
+                    match experiment.update_labels(&scores, eval_fdr, desc) {
+                        Ok(labels) => labels.iter().filter(|&&x| x == 1).count(),
+                        Err(e) => {
+                            log::debug!("Skipping feature {} for initial labeling: {}", col, e);
+                            0
+                        }
+                    }
                 })
                 .collect()
         };
@@ -135,19 +381,21 @@ impl SemiSupervisedLearner {
 
         for desc in &[true, false] {
             let num_passing = targets_count_by_feature(*desc);
-            let feat_idx = num_passing
+            let Some(feat_idx) = num_passing
                 .iter()
                 .enumerate()
                 .max_by_key(|&(_, count)| count)
                 .map(|(idx, _)| idx)
-                .unwrap();
+            else {
+                continue;
+            };
             let num_passing = num_passing[feat_idx];
 
             if num_passing > best_positives {
                 best_positives = num_passing;
                 best_feat = feat_idx;
                 let scores = experiment.x.column(feat_idx).to_owned();
-                new_labels = experiment.update_labels(&scores, eval_fdr, *desc);
+                new_labels = experiment.update_labels(&scores, eval_fdr, *desc)?;
                 best_desc = *desc;
             }
         }
@@ -159,18 +407,18 @@ impl SemiSupervisedLearner {
         );
 
         if best_positives == 0 {
-            panic!("No PSMs found below the 'eval_fdr' {}", eval_fdr);
+            return Err(ExperimentError::NoPsmsBelowFdr(eval_fdr));
         }
 
         let best_feature_scores = experiment.x.column(best_feat).to_owned();
 
-        (
+        Ok((
             best_feat,
             best_positives,
             new_labels,
             best_desc,
             best_feature_scores,
-        )
+        ))
     }
 
     /// Remove unlabeled PSMs
@@ -197,6 +445,14 @@ impl SemiSupervisedLearner {
 
     /// Create folds for cross-validation
     ///
+    /// PSMs are split into `n_folds` disjoint folds with [`Experiment::assign_folds`], grouped
+    /// according to the learner's [`FoldGrouping`]. Each fold is used once as the test set, and
+    /// the labeled PSMs of the remaining folds form the training set, so every PSM is scored by
+    /// exactly one model that was not trained on it. If `target_pct` or `decoy_pct` is set, the
+    /// training set is subsampled to that fraction of targets and decoys; the test sets are never
+    /// subsampled. The calibration set holds all PSMs of the remaining folds, including the
+    /// unlabeled targets.
+    ///
     /// # Arguments
     ///
     /// * `experiment` - The experiment to use
@@ -206,93 +462,61 @@ impl SemiSupervisedLearner {
     ///
     /// # Returns
     ///
-    /// A vector of tuples containing the training and testing experiments for each fold
+    /// A vector of tuples containing the training, calibration and testing experiments for each fold
     fn create_folds(
         &self,
         experiment: &Experiment,
         n_folds: usize,
         target_pct: Option<f64>,
         decoy_pct: Option<f64>,
-    ) -> Vec<(Experiment, Experiment)> {
-        let mut rng = thread_rng();
+    ) -> Vec<(Experiment, Experiment, Experiment)> {
         let n_samples = experiment.x.nrows();
-    
-        // Separate targets and decoys
-        let targets: Vec<_> = (0..n_samples).filter(|&i| experiment.y[i] == 1).collect();
-        let decoys: Vec<_> = (0..n_samples).filter(|&i| experiment.y[i] == -1).collect();
-    
+
+        let n_folds = if n_folds < 2 {
+            log::warn!("At least 2 cross-validation folds are required, using 2 folds instead of {}", n_folds);
+            2
+        } else {
+            n_folds
+        };
+
         // If neither target_pct nor decoy_pct is set, use the full data
         let use_full_data = target_pct.is_none() && decoy_pct.is_none();
 
         if !use_full_data {
             log::info!("Using {} % of targets and {} % of decoys for training", target_pct.unwrap_or(1.0) * 100.0, decoy_pct.unwrap_or(1.0) * 100.0);
         }
-    
-        // Calculate the number of targets and decoys to include in each fold
-        let targets_per_fold = if use_full_data {
-            (targets.len() as f64 / n_folds as f64).ceil() as usize
-        } else {
-            (targets.len() as f64 * target_pct.unwrap_or(1.0) / n_folds as f64).ceil() as usize
-        };
-        let decoys_per_fold = if use_full_data {
-            (decoys.len() as f64 / n_folds as f64).ceil() as usize
-        } else {
-            (decoys.len() as f64 * decoy_pct.unwrap_or(1.0) / n_folds as f64).ceil() as usize
-        };
-    
+
+        let fold_ids = experiment.assign_folds(n_folds, self.fold_grouping, self.seed);
+        let mut rng = StdRng::seed_from_u64(self.seed);
+
         (0..n_folds)
             .map(|i| {
-                // Randomly select targets and decoys for the test set
-                let test_targets: Vec<_> = targets.choose_multiple(&mut rng, targets_per_fold).cloned().collect();
-                let test_decoys: Vec<_> = decoys.choose_multiple(&mut rng, decoys_per_fold).cloned().collect();
-    
-                // Remaining targets and decoys after selecting test samples
-                let remaining_targets: Vec<_> = targets.iter().filter(|t| !test_targets.contains(t)).cloned().collect();
-                let remaining_decoys: Vec<_> = decoys.iter().filter(|d| !test_decoys.contains(d)).cloned().collect();
-    
-                // Randomly select targets and decoys for the training set
-                let train_targets: Vec<_> = if use_full_data {
-                    remaining_targets.clone()
-                } else {
-                    remaining_targets
-                        .choose_multiple(
-                            &mut rng,
-                            (remaining_targets.len() as f64 * target_pct.unwrap_or(1.0)).round() as usize,
-                        )
-                        .cloned()
-                        .collect()
-                };
-                let train_decoys: Vec<_> = if use_full_data {
-                    remaining_decoys.clone()
-                } else {
-                    remaining_decoys
-                        .choose_multiple(
-                            &mut rng,
-                            (remaining_decoys.len() as f64 * decoy_pct.unwrap_or(1.0)).round() as usize,
-                        )
-                        .cloned()
-                        .collect()
-                };
-    
-                // Combine training and testing indices
-                let train_indices: Vec<_> = train_targets.into_iter().chain(train_decoys.into_iter()).collect();
-                let test_indices: Vec<_> = test_targets.into_iter().chain(test_decoys.into_iter()).collect();
-    
-                // Create masks for training and testing sets
+                let test_mask: Array1<bool> = fold_ids.iter().map(|&f| f == i).collect();
+                let calibration_mask: Array1<bool> = test_mask.mapv(|is_test| !is_test);
+
+                // Training PSMs are all PSMs from the other folds, optionally subsampled per class
+                let mut train_targets: Vec<usize> = (0..n_samples).filter(|&j| fold_ids[j] != i && experiment.y[j] == 1).collect();
+                let mut train_decoys: Vec<usize> = (0..n_samples).filter(|&j| fold_ids[j] != i && experiment.y[j] == -1).collect();
+
+                if !use_full_data {
+                    let n_targets = (train_targets.len() as f64 * target_pct.unwrap_or(1.0)).round() as usize;
+                    let n_decoys = (train_decoys.len() as f64 * decoy_pct.unwrap_or(1.0)).round() as usize;
+                    train_targets.shuffle(&mut rng);
+                    train_targets.truncate(n_targets);
+                    train_decoys.shuffle(&mut rng);
+                    train_decoys.truncate(n_decoys);
+                }
+
                 let mut train_mask = Array1::from_elem(n_samples, false);
-                for &idx in &train_indices {
+                for &idx in train_targets.iter().chain(train_decoys.iter()) {
                     train_mask[idx] = true;
                 }
-    
-                let mut test_mask = Array1::from_elem(n_samples, false);
-                for &idx in &test_indices {
-                    test_mask[idx] = true;
-                }
-    
+
                 // Filter the experiment to create training and testing sets
                 let train_exp = experiment.filter(&train_mask);
+                let calibration_exp = experiment.filter(&calibration_mask);
                 let test_exp = experiment.filter(&test_mask);
-    
+
                 log::trace!(
                     "Preparing fold {} with {} training samples ({} targets and {} decoys) and {} testing samples ({} targets and {} decoys)",
                     i,
@@ -303,48 +527,60 @@ impl SemiSupervisedLearner {
                     test_exp.y.iter().filter(|&&x| x == 1).count(),
                     test_exp.y.iter().filter(|&&x| x == -1).count()
                 );
-    
-                (train_exp, test_exp)
+
+                (train_exp, calibration_exp, test_exp)
             })
             .collect()
     }
-    
-    
 
-    /// Fit the SemiSupervisedLearner
+    /// Train one model per cross-validation fold and score the held-out PSMs
+    ///
+    /// The models are trained on the current labels of the experiment, which are left unchanged.
+    /// If feature selection is enabled, features are selected on the target/decoy labels of each
+    /// fold's training PSMs. The scores of each fold model are calibrated with a
+    /// [`ScoreCalibration`] fitted on the targets and decoys of the other folds, including the
+    /// unlabeled targets, so that scores from different folds are comparable once merged.
     ///
     /// # Arguments
     ///
-    /// * `x` - The features to use, shape (n_samples, n_features)
-    /// * `y` - The labels to use, shape (n_samples,)
+    /// * `experiment` - The experiment to use, with labels from the previous iteration
+    /// * `sample_weight` - Optional weight of each PSM of the experiment, indexed by row
     ///
     /// # Returns
     ///
-    /// The predictions for the input features
-    pub fn fit(&mut self, x: Array2<f32>, y: Array1<i32>, psm_metadata: PsmMetadata) -> anyhow::Result<(Array1<f32>, Array1<u32>)> {
-
-        let mut experiment = Experiment::new(x.clone(), y.clone(), psm_metadata.clone())?;
-
-        experiment.log_input_data_summary();
-
-        // Get initial best feature
-        let (_best_feat, _best_positives, mut new_labels, best_desc, _best_feature_scores) =
-            self.init_best_feature(&experiment, self.train_fdr);
+    /// The calibrated out-of-fold predictions for all PSMs, the calibrated in-sample predictions
+    /// averaged over the models whose training set contained each PSM (NaN for PSMs never used
+    /// for training), and the fold models
+    fn cross_validate(&self, experiment: &Experiment, sample_weight: Option<&Array1<f32>>) -> anyhow::Result<(Array1<f32>, Array1<f32>, Vec<FoldModel>)> {
+        let folds = self.create_folds(experiment, self.xeval_num_iter, self.class_pct.map(|(t, _d)| t), self.class_pct.map(|(_t, d)| d));
 
-        experiment.y = new_labels.clone();
-
-        let folds = self.create_folds(&experiment, self.xeval_num_iter, self.class_pct.map(|(t, _d)| t), self.class_pct.map(|(_t, d)| d));
+        // Out-of-fold predictions, each PSM is scored by the model of the fold it was held out in
+        let n_samples = experiment.x.nrows();
+        let mut all_predictions = Array1::zeros(n_samples);
+        let mut in_sample_sum = Array1::<f32>::zeros(n_samples);
+        let mut in_sample_count = Array1::<f32>::zeros(n_samples);
+        let mut fold_models = Vec::with_capacity(folds.len());
 
-        for (fold, (mut train_exp, test_exp)) in folds.into_iter().enumerate() {
-            
-            let n_samples = experiment.x.nrows();
+        for (fold, (mut train_exp, calibration_exp, test_exp)) in folds.into_iter().enumerate() {
             log::info!("Learning on Cross-Validation Fold: {} with {} training samples", fold, train_exp.x.nrows());
 
-            let mut all_predictions = Array1::zeros(n_samples);
-
             self.remove_unlabeled_psms(&mut train_exp);
+            let train_x = self.transform(&train_exp.x)?;
+
+            // Features are selected on the training PSMs of the fold only
+            let selected_features = self.feature_selection.as_ref().map(|selector| {
+                let labels = train_exp.is_target.mapv(|is_target| if is_target { 1 } else { -1 });
+                let selected = selector.fit(&train_x, &labels);
+                log::debug!("Fold {}: selected {} of {} features: {:?}", fold, selected.len(), train_x.ncols(), selected);
+                selected
+            });
+
+            let train_x = select_columns(&train_x, selected_features.as_deref());
+            let calibration_x = select_columns(&self.transform(&calibration_exp.x)?, selected_features.as_deref());
+            let test_x = select_columns(&self.transform(&test_exp.x)?, selected_features.as_deref());
 
-            train_exp.split_for_xval(0.80, false);
+            // The evaluation split of each fold is seeded, so repeated fits give identical models
+            train_exp.split_for_xval(0.80, false, self.seed.wrapping_add(fold as u64));
 
             let train_indices: Vec<usize> = train_exp.is_train
             .iter()
@@ -359,35 +595,327 @@ impl SemiSupervisedLearner {
             .collect();
             
 
-            self.model
-                .fit(&train_exp.x.select(ndarray::Axis(0), &train_indices), &train_exp.y.select(ndarray::Axis(0), &train_indices).to_vec(), Some(&train_exp.x.select(ndarray::Axis(0), &test_indices)), Some(&train_exp.y.select(ndarray::Axis(0), &test_indices).to_vec()));
+            let fit_x = train_x.select(ndarray::Axis(0), &train_indices);
+            let fit_y = train_exp.y.select(ndarray::Axis(0), &train_indices).to_vec();
+            let eval_x = train_x.select(ndarray::Axis(0), &test_indices);
+            let eval_y = train_exp.y.select(ndarray::Axis(0), &test_indices).to_vec();
+
+            let mut model = create_model(self.model_type.clone(), self.learning_rate);
+            if sample_weight.is_some() || self.class_balancing {
+                let mut weights: Vec<f32> = match sample_weight {
+                    Some(sample_weight) => train_indices.iter().map(|&i| sample_weight[train_exp.tg_num_id[i] as usize]).collect(),
+                    None => vec![1.0; train_indices.len()],
+                };
+                if self.class_balancing {
+                    weights.iter_mut().zip(balanced_class_weights(&fit_y)).for_each(|(w, c)| *w *= c);
+                }
+                model.fit_weighted(&fit_x, &fit_y, &weights, Some(&eval_x), Some(&eval_y))?;
+            } else {
+                model.fit(&fit_x, &fit_y, Some(&eval_x), Some(&eval_y))?;
+            }
             
-            let fold_predictions = Array1::from(self.model.predict_proba(&test_exp.x));
+            let calibration_predictions = Array1::from(model.predict_proba(&calibration_x)?);
+            let calibration = ScoreCalibration::fit(&calibration_predictions, &calibration_exp.is_target, self.train_fdr)
+                .unwrap_or_else(|| {
+                    log::warn!("Could not calibrate the scores of fold {}, no target passes {} FDR", fold, self.train_fdr);
+                    ScoreCalibration::default()
+                });
+            log::debug!("Fold {} calibration: {:?}", fold, calibration);
+
+            let fold_predictions = calibration.transform(&Array1::from(model.predict_proba(&test_x)?));
+
+            let train_predictions = Array1::from(model.predict_proba(&train_x)?);
+            for (i, pred) in calibration.transform(&train_predictions).iter().enumerate() {
+                let row = train_exp.tg_num_id[i] as usize;
+                in_sample_sum[row] += *pred;
+                in_sample_count[row] += 1.0;
+            }
 
             // Update predictions
             for (i, pred) in fold_predictions.iter().enumerate() {
                 all_predictions[test_exp.tg_num_id[i] as usize] = *pred;
             }
 
-            new_labels = experiment.update_labels(&all_predictions, self.train_fdr, best_desc);
-            experiment.y = new_labels;
+            fold_models.push(FoldModel { model, calibration, selected_features });
+        }
+
+        let in_sample_predictions = ndarray::Zip::from(&in_sample_sum)
+            .and(&in_sample_count)
+            .map_collect(|&sum, &count| if count > 0.0 { sum / count } else { f32::NAN });
+
+        Ok((all_predictions, in_sample_predictions, fold_models))
+    }
+
+    /// Iteratively retrain the fold models, as in Percolator
+    ///
+    /// At each iteration all fold models are retrained on the current labels, and the merged
+    /// out-of-fold scores are used to relabel the PSMs and update their ranks for the next
+    /// iteration. Training stops after `max_iter` iterations, or once the number of targets
+    /// below the training FDR stops increasing by more than the convergence tolerance.
+    ///
+    /// # Arguments
+    ///
+    /// * `experiment` - The experiment to use, with labels initialized from the best feature
+    /// * `initial_identifications` - The number of targets below the training FDR for the best feature
+    /// * `sample_weight` - Optional weight of each PSM of the experiment
+    ///
+    /// # Returns
+    ///
+    /// The out-of-fold predictions of the last successful iteration, whose fold models are kept
+    /// for [`SemiSupervisedLearner::predict`], or an error if the first iteration fails
+    fn train(&mut self, experiment: &mut Experiment, initial_identifications: usize, sample_weight: Option<&Array1<f32>>) -> anyhow::Result<Array1<f32>> {
+        let mut previous_identifications = initial_identifications;
+        let mut predictions: Option<Array1<f32>> = None;
+
+        for iteration in 0..self.max_iter {
+            log::info!("Training iteration {} of {}", iteration + 1, self.max_iter);
+
+            let result = self.cross_validate(experiment, sample_weight).and_then(|(iteration_predictions, in_sample_predictions, fold_models)| {
+                let labels = experiment.update_labels(&iteration_predictions, self.train_fdr, true)?;
+                Ok((iteration_predictions, in_sample_predictions, fold_models, labels))
+            });
+            let (iteration_predictions, in_sample_predictions, fold_models, labels) = match result {
+                Ok(result) => result,
+                Err(e) if predictions.is_some() => {
+                    log::warn!("Training iteration {} failed: {}. Keeping the scores of the previous iteration.", iteration + 1, e);
+                    break;
+                }
+                Err(e) => return Err(e),
+            };
+
+            let identifications = labels.iter().filter(|&&l| l == 1).count();
+            log::info!(
+                "Iteration {}: {} targets below {} FDR (previously {})",
+                iteration + 1,
+                identifications,
+                self.train_fdr,
+                previous_identifications
+            );
+
+            experiment.y = labels;
+            experiment.update_rank_feature(&iteration_predictions, &experiment.psm_metadata.clone());
+
+            self.history.iteration_scores.push(iteration_predictions.clone());
+            self.history.iteration_identifications.push(identifications);
+            self.history.in_sample_scores = Some(in_sample_predictions);
+            self.fold_models = fold_models;
+            predictions = Some(iteration_predictions);
+
+            if (identifications as f32) <= previous_identifications as f32 * (1.0 + self.convergence_tol) {
+                log::info!("Training converged after {} iterations", iteration + 1);
+                break;
+            }
+            previous_identifications = identifications;
+        }
+
+        predictions.ok_or_else(|| anyhow::anyhow!("No training iteration was run"))
+    }
 
-            experiment.update_rank_feature(&all_predictions, &experiment.psm_metadata.clone());
+    /// Fit the SemiSupervisedLearner
+    ///
+    /// The fold models are trained iteratively with [`SemiSupervisedLearner::with_max_iterations`]
+    /// and [`SemiSupervisedLearner::with_convergence_tolerance`] controlling when training stops.
+    /// If learning fails (e.g. a model cannot be trained or produces NaN scores), the learner
+    /// falls back to the best single feature found by [`SemiSupervisedLearner::init_best_feature`],
+    /// oriented so that higher scores are better. An error is only returned if the input data
+    /// is invalid or no feature yields any PSM below the training FDR.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - The features to use, shape (n_samples, n_features)
+    /// * `y` - The labels to use, shape (n_samples,)
+    /// * `psm_metadata` - The PSM metadata (spectrum and file identifiers, feature names)
+    ///
+    /// # Returns
+    ///
+    /// The predictions for the input features and the updated PSM ranks
+    pub fn fit(&mut self, x: Array2<f32>, y: Array1<i32>, psm_metadata: PsmMetadata) -> anyhow::Result<(Array1<f32>, Array1<u32>)> {
+        self.fit_weighted(x, y, psm_metadata, None)
+    }
 
+    /// Fit the SemiSupervisedLearner with per-PSM sample weights
+    ///
+    /// Same as [`SemiSupervisedLearner::fit`], with an optional non-negative weight for each PSM.
+    /// GBDT, XGBoost, the linear SVM, the random forest and the MLP use the weight of each PSM;
+    /// the linfa SVM only uses the mean weight of the targets and of the decoys. Models that don't
+    /// implement [`SemiSupervisedModel::fit_weighted`] ignore the weights.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - The features to use, shape (n_samples, n_features)
+    /// * `y` - The labels to use, shape (n_samples,)
+    /// * `psm_metadata` - The PSM metadata (spectrum and file identifiers, feature names)
+    /// * `sample_weight` - Optional weight of each PSM, shape (n_samples,)
+    ///
+    /// # Returns
+    ///
+    /// The predictions for the input features and the updated PSM ranks
+    pub fn fit_weighted(
+        &mut self,
+        x: Array2<f32>,
+        y: Array1<i32>,
+        psm_metadata: PsmMetadata,
+        sample_weight: Option<Array1<f32>>,
+    ) -> anyhow::Result<(Array1<f32>, Array1<u32>)> {
+        if let Some(sample_weight) = &sample_weight {
+            if sample_weight.len() != x.nrows() {
+                anyhow::bail!("Expected {} sample weights, got {}", x.nrows(), sample_weight.len());
+            }
+            if sample_weight.iter().any(|&w| !w.is_finite() || w < 0.0) {
+                anyhow::bail!("Sample weights must be finite and non-negative");
+            }
         }
 
-        // Final prediction on the entire dataset
+        let mut experiment = Experiment::new(x.clone(), y.clone(), psm_metadata.clone())?;
+
+        experiment.log_input_data_summary();
+
+        self.n_features = experiment.x.ncols();
+        self.feature_names = experiment.psm_metadata.feature_names.clone();
+        self.preprocessor = self
+            .preprocessing
+            .clone()
+            .map(|config| Preprocessor::fit(&experiment.x, config));
+
+        // Get initial best feature
+        let (best_feat, best_positives, new_labels, best_desc, best_feature_scores) =
+            self.init_best_feature(&experiment, self.train_fdr)?;
+
+        experiment.y = new_labels;
+
+        let best_feature_scores = if best_desc {
+            best_feature_scores
+        } else {
+            best_feature_scores.mapv(|v| -v)
+        };
+        self.fold_models = Vec::new();
+        self.history = FitHistory {
+            initial_feature: psm_metadata.feature_names.get(best_feat).cloned(),
+            initial_scores: Some(best_feature_scores.clone()),
+            ..Default::default()
+        };
+
+        let final_predictions = match self.train(&mut experiment, best_positives, sample_weight.as_ref()) {
+            Ok(predictions) => predictions,
+            Err(e) => {
+                log::warn!(
+                    "Learning failed: {}. Falling back to the best single feature '{}'.",
+                    e,
+                    psm_metadata.feature_names.get(best_feat).map(String::as_str).unwrap_or("unknown")
+                );
+                best_feature_scores
+            }
+        };
+
+        // Final predictions are the out-of-fold predictions
         log::info!("Final prediction on the entire dataset");
         let mut experiment = Experiment::new(x, y, psm_metadata)?;
 
-        // self.model
-        //     .fit(&experiment.x, &experiment.y.to_vec(), None, None);
-        let final_predictions = Array1::from(self.model.predict_proba(&experiment.x));
         experiment.update_rank_feature(&final_predictions, &experiment.psm_metadata.clone());
         let updated_ranks = experiment.get_rank_column()?; 
 
         Ok((final_predictions, updated_ranks))
     }
+
+    /// The score calibration of each fold model kept from the last training iteration
+    pub fn fold_calibrations(&self) -> Vec<ScoreCalibration> {
+        self.fold_models.iter().map(|fold_model| fold_model.calibration).collect()
+    }
+
+    /// Average the calibrated scores of all fold models on already transformed features
+    fn predict_ensemble(&self, x: &Array2<f32>) -> Result<Vec<f32>, ModelError> {
+        if self.fold_models.is_empty() {
+            return Err(ModelError::NotFitted);
+        }
+        let mut scores = Array1::<f32>::zeros(x.nrows());
+        for fold_model in &self.fold_models {
+            let x = fold_model.select_features(x);
+            scores += &fold_model.calibration.transform(&Array1::from(fold_model.model.predict(&x)?));
+        }
+        Ok((scores / self.fold_models.len() as f32).to_vec())
+    }
+
+    /// Score new PSMs with the ensemble of fold models
+    ///
+    /// The preprocessing fitted in [`SemiSupervisedLearner::fit`] is applied, and the calibrated
+    /// scores of the fold models from the last training iteration are averaged. Use this to score
+    /// PSMs that were not used for training; the scores returned by `fit` are out-of-fold scores,
+    /// since ensemble scores of the training PSMs would be biased by the models trained on them.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - The features to score, shape (n_samples, n_features), with the same columns as in `fit`
+    ///
+    /// # Returns
+    ///
+    /// The predicted scores, [`ModelError::NotFitted`] if no fold model was trained (e.g. if
+    /// `fit` fell back to the best single feature), or [`ModelError::InvalidParams`] if `x` does
+    /// not have the columns seen in `fit`
+    pub fn predict(&self, x: &Array2<f32>) -> Result<Array1<f32>, ModelError> {
+        if self.fold_models.is_empty() {
+            return Err(ModelError::NotFitted);
+        }
+        let x = self.transform(x)?;
+        Ok(Array1::from(self.predict_ensemble(&x)?))
+    }
+
+    /// Model-specific feature importance of the fitted model, keyed by feature name
+    ///
+    /// The importances are averaged over the fold models. With feature selection, features not
+    /// selected for a fold count as zero importance for that fold.
+    ///
+    /// # Arguments
+    ///
+    /// * `importance_type` - The kind of importance, e.g. `Gain` for tree models or `Weight` for linear models
+    ///
+    /// # Returns
+    ///
+    /// The feature importance, or `None` if the model does not support the requested kind
+    pub fn feature_importance(&self, importance_type: ImportanceType) -> Option<FeatureImportance> {
+        if self.fold_models.is_empty() {
+            return None;
+        }
+        let mut values = vec![0.0f32; self.n_model_features()];
+        for fold_model in &self.fold_models {
+            let fold_values = fold_model.model.feature_importance(importance_type)?;
+            match &fold_model.selected_features {
+                Some(selected) => selected.iter().zip(fold_values).for_each(|(&j, f)| values[j] += f),
+                None => values.iter_mut().zip(fold_values).for_each(|(v, f)| *v += f),
+            }
+        }
+        let values = values
+            .into_iter()
+            .map(|v| v / self.fold_models.len() as f32)
+            .collect();
+        Some(FeatureImportance::new(importance_type, &self.model_feature_names(), values))
+    }
+
+    /// Permutation importance of the ensemble of fold models, keyed by feature name
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - The features, shape (n_samples, n_features), with the same columns as in `fit`
+    /// * `y` - The labels, 1 for targets and -1 for decoys
+    /// * `eval_fdr` - The FDR threshold at which targets are counted
+    /// * `n_repeats` - The number of times each feature is shuffled
+    ///
+    /// # Returns
+    ///
+    /// The mean decrease in the number of targets at `eval_fdr` when each feature is shuffled
+    pub fn permutation_importance(
+        &self,
+        x: &Array2<f32>,
+        y: &Array1<i32>,
+        eval_fdr: f32,
+        n_repeats: usize,
+    ) -> Result<FeatureImportance, ModelError> {
+        if self.fold_models.is_empty() {
+            return Err(ModelError::NotFitted);
+        }
+        let x = self.transform(x)?;
+        let values = permutation_importance_with(|x| self.predict_ensemble(x), &x, &y.to_vec(), eval_fdr, n_repeats, self.seed)?;
+        Ok(FeatureImportance::new(ImportanceType::Permutation, &self.model_feature_names(), values))
+    }
 }
 
 #[cfg(test)]
@@ -457,6 +985,253 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_iterative_training_converges() {
+        // One PSM per spectrum, the first feature is noisy and the second separates targets and decoys
+        let n = 400;
+        let x = Array2::from_shape_fn((n, 3), |(i, j)| match j {
+            0 => if i % 2 == 0 { (i % 11) as f32 } else { (i % 7) as f32 },
+            1 => if i % 2 == 0 { 1.0 + (i % 13) as f32 / 4.0 } else { -1.0 - (i % 5) as f32 / 4.0 },
+            _ => 1.0,
+        });
+        let y = Array1::from_iter((0..n).map(|i| if i % 2 == 0 { 1 } else { -1 }));
+        let metadata = PsmMetadata {
+            spec_id: (0..n).map(|i| format!("scan={}", i)).collect(),
+            file_id: vec![0; n],
+            feature_names: vec!["noise".to_string(), "signal".to_string(), "rank".to_string()],
+            peptide: None,
+            proteins: None,
+        };
+
+        let mut learner = SemiSupervisedLearner::new(
+            ModelType::RandomForest {
+                n_trees: 10,
+                max_depth: 3,
+                min_samples_leaf: 1,
+                max_features: 1.0,
+                bootstrap: true,
+                seed: 42,
+            },
+            0.1,
+            0.01,
+            3,
+            None,
+        )
+        .with_max_iterations(4);
+        let (scores, ranks) = learner.fit(x, y, metadata).unwrap();
+
+        let history = learner.history();
+        assert!(!history.iteration_scores.is_empty() && history.iteration_scores.len() <= 4);
+        assert_eq!(history.iteration_scores.len(), history.iteration_identifications.len());
+        assert_eq!(history.iteration_scores.last(), Some(&scores));
+        assert_eq!(*history.iteration_identifications.last().unwrap(), n / 2);
+        assert!(ranks.iter().all(|&r| r == 1));
+
+        // The fold models of the last iteration are kept and scored as an ensemble
+        assert_eq!(learner.fold_calibrations().len(), 3);
+        let new_x = Array2::from_shape_vec((2, 3), vec![5.0, 3.0, 1.0, 5.0, -1.5, 1.0]).unwrap();
+        let new_scores = learner.predict(&new_x).unwrap();
+        assert!(new_scores[0] > new_scores[1]);
+
+        // A feature matrix with the wrong number of columns is rejected
+        assert!(matches!(
+            learner.predict(&Array2::zeros((2, 2))),
+            Err(ModelError::InvalidParams(_))
+        ));
+    }
+
+    #[test]
+    fn test_class_balancing_changes_model() {
+        // Overlapping targets and decoys, with three targets for each decoy
+        let n = 400;
+        let x = Array2::from_shape_fn((n, 2), |(i, j)| match j {
+            0 => (i % 10) as f32 + if i % 4 == 0 { 0.0 } else { 3.0 },
+            _ => 1.0,
+        });
+        let y = Array1::from_iter((0..n).map(|i| if i % 4 == 0 { -1 } else { 1 }));
+        let metadata = PsmMetadata {
+            spec_id: (0..n).map(|i| format!("scan={}", i)).collect(),
+            file_id: vec![0; n],
+            feature_names: vec!["score".to_string(), "rank".to_string()],
+            peptide: None,
+            proteins: None,
+        };
+        let new_x = Array2::from_shape_fn((13, 2), |(i, j)| if j == 0 { i as f32 } else { 1.0 });
+
+        let mut scores = Vec::new();
+        for class_balancing in [false, true] {
+            let mut learner = SemiSupervisedLearner::new(
+                ModelType::RandomForest {
+                    n_trees: 5,
+                    max_depth: 2,
+                    min_samples_leaf: 20,
+                    max_features: 1.0,
+                    bootstrap: false,
+                    seed: 42,
+                },
+                0.1,
+                1.0,
+                3,
+                None,
+            )
+            .with_max_iterations(1)
+            .with_class_balancing(class_balancing);
+            learner.fit(x.clone(), y.clone(), metadata.clone()).unwrap();
+            scores.push(learner.predict(&new_x).unwrap());
+        }
+
+        // The leaves mix targets and decoys, so balancing the classes changes their values
+        assert_ne!(scores[0], scores[1]);
+    }
+
+    #[test]
+    fn test_feature_selection_per_fold() {
+        use crate::feature_selection::classification::ClassificationScorer;
+
+        let n = 300;
+        let x = Array2::from_shape_fn((n, 3), |(i, j)| match j {
+            0 => (i % 7) as f32,
+            1 => if i % 2 == 0 { 1.0 + (i % 13) as f32 / 4.0 } else { -1.0 - (i % 5) as f32 / 4.0 },
+            _ => 1.0,
+        });
+        let y = Array1::from_iter((0..n).map(|i| if i % 2 == 0 { 1 } else { -1 }));
+        let metadata = PsmMetadata {
+            spec_id: (0..n).map(|i| format!("scan={}", i)).collect(),
+            file_id: vec![0; n],
+            feature_names: vec!["noise".to_string(), "signal".to_string(), "rank".to_string()],
+            peptide: None,
+            proteins: None,
+        };
+
+        let mut learner = SemiSupervisedLearner::new(
+            ModelType::RandomForest {
+                n_trees: 5,
+                max_depth: 3,
+                min_samples_leaf: 1,
+                max_features: 1.0,
+                bootstrap: true,
+                seed: 42,
+            },
+            0.1,
+            0.01,
+            3,
+            None,
+        )
+        .with_max_iterations(2)
+        .with_feature_selection(SelectKBestClassif::new(1, ClassificationScorer::FClassif));
+        learner.fit(x, y, metadata).unwrap();
+
+        // Each fold model selects the separating feature on its own training PSMs
+        assert_eq!(learner.selected_features(), Some(vec![vec![1]; 3]));
+
+        // Importances are reported for all features, unselected ones count as zero
+        let importance = learner.feature_importance(ImportanceType::SplitCount).unwrap();
+        assert_eq!(importance.values.len(), 3);
+        assert_eq!(importance.values[0], 0.0);
+        assert!(importance.values[1] > 0.0);
+
+        let new_x = Array2::from_shape_vec((2, 3), vec![5.0, 3.0, 1.0, 5.0, -1.5, 1.0]).unwrap();
+        let new_scores = learner.predict(&new_x).unwrap();
+        assert!(new_scores[0] > new_scores[1]);
+    }
+
+    #[test]
+    fn test_fit_is_deterministic_with_seed() {
+        let n = 300;
+        // Overlapping targets and decoys, so the fold models depend on their training PSMs
+        let x = Array2::from_shape_fn((n, 3), |(i, j)| match j {
+            0 => ((i * 7919) % 1000) as f32 / 100.0 + if i % 2 == 0 { 3.0 } else { 0.0 },
+            1 => ((i * 13) % 11) as f32,
+            _ => 1.0,
+        });
+        let y = Array1::from_iter((0..n).map(|i| if i % 2 == 0 { 1 } else { -1 }));
+        let metadata = PsmMetadata {
+            spec_id: (0..n).map(|i| format!("scan={}", i)).collect(),
+            file_id: vec![0; n],
+            feature_names: vec!["score".to_string(), "noise".to_string(), "rank".to_string()],
+            peptide: None,
+            proteins: None,
+        };
+
+        let scores: Vec<Array1<f32>> = (0..2)
+            .map(|_| {
+                let mut learner = SemiSupervisedLearner::new(
+                    ModelType::RandomForest {
+                        n_trees: 5,
+                        max_depth: 4,
+                        min_samples_leaf: 5,
+                        max_features: 1.0,
+                        bootstrap: false,
+                        seed: 42,
+                    },
+                    0.1,
+                    0.1,
+                    3,
+                    None,
+                )
+                .with_max_iterations(1)
+                .with_seed(7);
+                learner.fit(x.clone(), y.clone(), metadata.clone()).unwrap().0
+            })
+            .collect();
+
+        assert_eq!(scores[0], scores[1]);
+    }
+
+    #[test]
+    fn test_calibration_includes_unlabeled_targets() {
+        // Decoys score below 10, labeled targets above 20 and unlabeled targets just above the decoys
+        let n = 300;
+        let x = Array2::from_shape_fn((n, 1), |(i, _)| match i % 3 {
+            0 => (i % 10) as f32,
+            1 => 20.0 + (i % 10) as f32,
+            _ => 9.5 + (i % 10) as f32 / 10.0,
+        });
+        let y = Array1::from_iter((0..n).map(|i| if i % 3 == 0 { -1 } else { 1 }));
+        let metadata = PsmMetadata {
+            spec_id: (0..n).map(|i| format!("scan={}", i)).collect(),
+            file_id: vec![0; n],
+            feature_names: vec!["score".to_string()],
+            peptide: None,
+            proteins: None,
+        };
+        let mut experiment = Experiment::new(x, y, metadata).unwrap();
+        for i in (2..n).step_by(3) {
+            experiment.y[i] = 0;
+        }
+
+        let mut learner = SemiSupervisedLearner::new(
+            ModelType::LinearSVM {
+                c_grid: vec![1.0],
+                class_ratio_grid: vec![1.0],
+                max_iter: 100,
+                eps: 1e-4,
+                eval_fdr: 0.02,
+            },
+            0.1,
+            0.02,
+            3,
+            None,
+        );
+        learner.n_features = 1;
+        let (_, _, mut fold_models) = learner.cross_validate(&experiment, None).unwrap();
+
+        let fold_ids = experiment.assign_folds(3, learner.fold_grouping, learner.seed);
+        let other_folds: Vec<usize> = (0..n).filter(|&i| fold_ids[i] != 0).collect();
+        let labeled: Vec<usize> = other_folds.iter().copied().filter(|&i| experiment.y[i] != 0).collect();
+        let fold = &mut fold_models[0];
+        let mut calibrate = |rows: &[usize]| {
+            let scores = Array1::from(fold.model.predict_proba(&experiment.x.select(ndarray::Axis(0), rows)).unwrap());
+            ScoreCalibration::fit(&scores, &experiment.is_target.select(ndarray::Axis(0), rows), learner.train_fdr).unwrap()
+        };
+        let with_unlabeled = calibrate(&other_folds);
+        let labeled_only = calibrate(&labeled);
+
+        // The unlabeled targets pass the FDR threshold and lower its score
+        assert_eq!(fold.calibration, with_unlabeled);
+        assert!(with_unlabeled.threshold_score < labeled_only.threshold_score);
+    }
+
     #[test]
     #[cfg(feature = "xgboost")]
     fn test_xgb_semi_supervised_learner() {
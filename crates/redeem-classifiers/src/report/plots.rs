@@ -1,10 +1,13 @@
 use ndarray::{Array1, Array2};
 use plotly::box_plot::BoxMean;
 use plotly::common::{DashType, HoverInfo, Label, Line, Marker, Mode, Orientation};
-use plotly::{Plot, Histogram, Scatter, BoxPlot};
+use plotly::{Plot, Histogram, Scatter, BoxPlot, Bar};
 use plotly::layout::{Axis, Layout, Legend};
 use itertools_num::linspace;
 
+use crate::feature_importance::FeatureImportance;
+use crate::stats::tdc;
+
 /// Plot a histogram of the scores for the targets and decoys
 pub fn plot_score_histogram(scores: &Vec<f64>, labels: &Vec<i32>, title: &str, x_title: &str) -> Result<Plot, String> {
     assert_eq!(scores.len(), labels.len(), "Scores and labels must have the same length");
@@ -194,6 +197,95 @@ pub fn plot_scatter(x: &Vec<Vec<f64>>, y: &Vec<Vec<f64>>, labels: Vec<String>, t
     Ok(plot)
 }
 
+/// Generate a plot of the number of target identifications as a function of the q-value threshold
+///
+/// # Arguments
+///
+/// * `scores` - One (name, scores) pair per curve, e.g. before and after rescoring. Higher scores are better.
+/// * `labels` - The labels, 1 for targets and -1 for decoys
+/// * `max_qvalue` - The largest q-value shown on the x-axis
+/// * `title` - The title of the plot
+///
+/// # Returns
+///
+/// A Plot object with one line per set of scores
+pub fn plot_identifications_vs_qvalue(scores: &[(String, Vec<f32>)], labels: &Vec<i32>, max_qvalue: f32, title: &str) -> Result<Plot, String> {
+    let targets: Array1<bool> = labels.iter().map(|&l| l == 1).collect();
+
+    let mut plot = Plot::new();
+    for (name, s) in scores {
+        if s.len() != labels.len() {
+            return Err(format!("Scores '{}' and labels must have the same length", name));
+        }
+        let qvals = tdc(&Array1::from(s.clone()), &targets, true).map_err(|e| e.to_string())?;
+
+        let mut target_qvals: Vec<f32> = qvals
+            .iter()
+            .zip(targets.iter())
+            .filter(|(&q, &t)| t && q <= max_qvalue)
+            .map(|(&q, _)| q)
+            .collect();
+        target_qvals.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+        let x: Vec<f64> = target_qvals.iter().map(|&q| q as f64).collect();
+        let y: Vec<usize> = (1..=target_qvals.len()).collect();
+        let trace = Scatter::new(x, y).mode(Mode::Lines).name(name.clone());
+        plot.add_trace(trace);
+    }
+
+    plot.set_layout(
+        Layout::new()
+            .title(title)
+            .x_axis(Axis::new().title("q-value"))
+            .y_axis(Axis::new().title("Target identifications")),
+    );
+
+    Ok(plot)
+}
+
+/// Generate a horizontal bar plot of feature importances
+///
+/// # Arguments
+///
+/// * `importance` - The feature importances to plot
+/// * `top_n` - Only plot the `top_n` most important features, or all features if `None`
+/// * `title` - The title of the plot
+///
+/// # Returns
+///
+/// A Plot object containing the bar plot, with the most important feature at the top
+pub fn plot_feature_importance(importance: &FeatureImportance, top_n: Option<usize>, title: &str) -> Result<Plot, String> {
+    if importance.values.is_empty() {
+        return Err("No feature importances to plot".to_string());
+    }
+
+    let mut sorted = importance.sorted();
+    if let Some(n) = top_n {
+        sorted.truncate(n);
+    }
+    // Plotly draws the first bar at the bottom, so reverse to put the most important feature on top
+    sorted.reverse();
+    let (names, values): (Vec<String>, Vec<f32>) = sorted.into_iter().unzip();
+
+    let n_features = names.len();
+    let trace = Bar::new(values, names)
+        .orientation(Orientation::Horizontal)
+        .name(importance.importance_type.to_string());
+
+    let layout = Layout::new()
+        .title(title)
+        .x_axis(Axis::new().title(&format!("{} importance", importance.importance_type)))
+        .y_axis(Axis::new().title("Feature").auto_margin(true))
+        .height((n_features * 25).max(400))
+        .show_legend(false);
+
+    let mut plot = Plot::new();
+    plot.add_trace(trace);
+    plot.set_layout(layout);
+
+    Ok(plot)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -77,6 +77,7 @@ pub struct Report {
     software_logo: Option<String>,
     title: String,
     sections: Vec<ReportSection>,
+    embed_plotly_js: bool,
 }
 
 impl Report {
@@ -88,9 +89,18 @@ impl Report {
             software_logo: software_logo.map(|s| s.to_string()),
             title: title.to_string(),
             sections: Vec::new(),
+            embed_plotly_js: false,
         }
     }
 
+    /// Embed the plotly.js sources in the HTML file instead of loading them from a CDN.
+    ///
+    /// Only plotly.js is embedded: jQuery, DataTables, its column resizing plugin and FileSaver.js
+    /// are still loaded from their CDNs, so the tables and the CSV export need network access.
+    pub fn set_embed_plotly_js(&mut self, embed: bool) {
+        self.embed_plotly_js = embed;
+    }
+
     /// Add a section to the report
     pub fn add_section(&mut self, section: ReportSection) {
         self.sections.push(section);
@@ -105,7 +115,11 @@ impl Report {
             html {
                 head {
                     title { (self.title) }
-                    script src="https://cdn.plot.ly/plotly-latest.min.js" {}
+                    @if self.embed_plotly_js {
+                        (PreEscaped(Plot::offline_js_sources()))
+                    } @else {
+                        script src="https://cdn.plot.ly/plotly-latest.min.js" {}
+                    }
                     script src="https://cdnjs.cloudflare.com/ajax/libs/jquery/3.6.4/jquery.min.js" {}
                     script src="https://cdn.datatables.net/1.13.4/js/jquery.dataTables.min.js" {}
                     link rel="stylesheet" href="https://cdn.datatables.net/1.13.4/css/jquery.dataTables.min.css" {}
@@ -0,0 +1,335 @@
+//! HTML report summarizing a semi-supervised rescoring run.
+
+use std::collections::BTreeMap;
+
+use maud::html;
+use ndarray::Array1;
+
+use crate::data_handling::PsmMetadata;
+use crate::feature_importance::FeatureImportance;
+use crate::psm_scorer::SemiSupervisedLearner;
+use crate::report::plots::{
+    plot_boxplot, plot_feature_importance, plot_identifications_vs_qvalue, plot_pp, plot_score_histogram,
+};
+use crate::report::report::{Report, ReportSection};
+use crate::stats::tdc;
+
+/// Builder for a rescoring report.
+///
+/// The report has one tab per topic: a summary of the identifications before and after
+/// rescoring, target/decoy score distributions for each iteration, identifications vs q-value
+/// curves, P-P plots, per-file score boxplots and feature importances.
+///
+/// # Example
+///
+/// ```ignore
+/// let (scores, _ranks) = learner.fit(x.clone(), y.clone(), metadata.clone())?;
+/// RescoringReportBuilder::from_learner(&learner, &y, &scores, &metadata)
+///     .with_feature_importance(learner.permutation_importance(&x, &y, 0.01, 1)?)
+///     .save("rescoring_report.html")?;
+/// ```
+pub struct RescoringReportBuilder {
+    software_name: String,
+    version: String,
+    software_logo: Option<String>,
+    title: String,
+    labels: Vec<i32>,
+    final_scores: Vec<f32>,
+    initial_scores: Option<(String, Vec<f32>)>,
+    iteration_scores: Vec<Vec<f32>>,
+    file_ids: Option<Vec<usize>>,
+    file_names: Option<Vec<String>>,
+    feature_importances: Vec<FeatureImportance>,
+    eval_fdr: f32,
+    max_qvalue: f32,
+    embed_plotly_js: bool,
+}
+
+impl RescoringReportBuilder {
+    /// Create a new RescoringReportBuilder
+    ///
+    /// # Arguments
+    ///
+    /// * `labels` - The labels, 1 for targets and -1 for decoys
+    /// * `final_scores` - The final scores from rescoring, higher is better
+    pub fn new(labels: &Array1<i32>, final_scores: &Array1<f32>) -> Self {
+        RescoringReportBuilder {
+            software_name: "ReDeeM".to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            software_logo: None,
+            title: "Rescoring Report".to_string(),
+            labels: labels.to_vec(),
+            final_scores: final_scores.to_vec(),
+            initial_scores: None,
+            iteration_scores: Vec::new(),
+            file_ids: None,
+            file_names: None,
+            feature_importances: Vec::new(),
+            eval_fdr: 0.01,
+            max_qvalue: 0.1,
+            embed_plotly_js: true,
+        }
+    }
+
+    /// Create a new RescoringReportBuilder from a fitted learner
+    ///
+    /// The best initial feature, the scores of each iteration and the file identifiers are taken
+    /// from the learner's [`FitHistory`](crate::psm_scorer::FitHistory) and the PSM metadata.
+    ///
+    /// # Arguments
+    ///
+    /// * `learner` - The fitted learner
+    /// * `labels` - The labels passed to `fit`, 1 for targets and -1 for decoys
+    /// * `final_scores` - The scores returned by `fit`
+    /// * `psm_metadata` - The PSM metadata passed to `fit`
+    pub fn from_learner(
+        learner: &SemiSupervisedLearner,
+        labels: &Array1<i32>,
+        final_scores: &Array1<f32>,
+        psm_metadata: &PsmMetadata,
+    ) -> Self {
+        let history = learner.history();
+        let mut builder = Self::new(labels, final_scores).with_file_ids(psm_metadata.file_id.clone());
+        if let Some(initial_scores) = &history.initial_scores {
+            let name = history.initial_feature.clone().unwrap_or_else(|| "Best feature".to_string());
+            builder = builder.with_initial_scores(&name, initial_scores);
+        }
+        for scores in &history.iteration_scores {
+            builder = builder.with_iteration_scores(scores);
+        }
+        builder
+    }
+
+    /// Set the software name, version and logo shown in the report banner
+    pub fn with_software(mut self, name: &str, version: &str, logo: Option<&str>) -> Self {
+        self.software_name = name.to_string();
+        self.version = version.to_string();
+        self.software_logo = logo.map(|s| s.to_string());
+        self
+    }
+
+    /// Set the title of the report
+    pub fn with_title(mut self, title: &str) -> Self {
+        self.title = title.to_string();
+        self
+    }
+
+    /// Set the scores before rescoring, e.g. the best search engine feature (higher is better)
+    pub fn with_initial_scores(mut self, name: &str, scores: &Array1<f32>) -> Self {
+        self.initial_scores = Some((name.to_string(), scores.to_vec()));
+        self
+    }
+
+    /// Add the scores of a training iteration
+    pub fn with_iteration_scores(mut self, scores: &Array1<f32>) -> Self {
+        self.iteration_scores.push(scores.to_vec());
+        self
+    }
+
+    /// Set the file identifier of each PSM, used for the per-file boxplots
+    pub fn with_file_ids(mut self, file_ids: Vec<usize>) -> Self {
+        self.file_ids = Some(file_ids);
+        self
+    }
+
+    /// Set the file names, indexed by file identifier
+    pub fn with_file_names(mut self, file_names: Vec<String>) -> Self {
+        self.file_names = Some(file_names);
+        self
+    }
+
+    /// Add a feature importance to plot
+    pub fn with_feature_importance(mut self, importance: FeatureImportance) -> Self {
+        self.feature_importances.push(importance);
+        self
+    }
+
+    /// Set the FDR threshold used to count identifications in the summary (default: 0.01)
+    pub fn with_eval_fdr(mut self, eval_fdr: f32) -> Self {
+        self.eval_fdr = eval_fdr;
+        self
+    }
+
+    /// Set the largest q-value shown in the identification curves (default: 0.1)
+    pub fn with_max_qvalue(mut self, max_qvalue: f32) -> Self {
+        self.max_qvalue = max_qvalue;
+        self
+    }
+
+    /// Embed plotly.js in the HTML file instead of loading it from a CDN (default: true)
+    ///
+    /// The table scripts and styles are always loaded from their CDNs, see [`Report::set_embed_plotly_js`].
+    pub fn with_embed_plotly_js(mut self, embed: bool) -> Self {
+        self.embed_plotly_js = embed;
+        self
+    }
+
+    /// Number of targets at or below `eval_fdr`
+    fn num_identifications(&self, scores: &[f32]) -> Result<usize, String> {
+        let targets: Array1<bool> = self.labels.iter().map(|&l| l == 1).collect();
+        let qvals = tdc(&Array1::from(scores.to_vec()), &targets, true).map_err(|e| e.to_string())?;
+        Ok(qvals
+            .iter()
+            .zip(targets.iter())
+            .filter(|(&q, &t)| t && q <= self.eval_fdr)
+            .count())
+    }
+
+    /// Build the report
+    ///
+    /// # Returns
+    ///
+    /// The report, or an error if the scores and labels do not match or a plot cannot be created
+    pub fn build(&self) -> Result<Report, String> {
+        let n = self.labels.len();
+        if self.final_scores.len() != n {
+            return Err("Final scores and labels must have the same length".to_string());
+        }
+        if let Some(file_ids) = &self.file_ids {
+            if file_ids.len() != n {
+                return Err("File identifiers and labels must have the same length".to_string());
+            }
+        }
+
+        let mut report = Report::new(&self.software_name, &self.version, self.software_logo.as_deref(), &self.title);
+        report.set_embed_plotly_js(self.embed_plotly_js);
+
+        // Scores to compare, before and after rescoring
+        let mut compared: Vec<(String, Vec<f32>)> = Vec::new();
+        if let Some((name, scores)) = &self.initial_scores {
+            compared.push((format!("Before rescoring ({})", name), scores.clone()));
+        }
+        compared.push(("After rescoring".to_string(), self.final_scores.clone()));
+
+        // Summary
+        let mut summary = ReportSection::new("Summary");
+        let n_targets = self.labels.iter().filter(|&&l| l == 1).count();
+        let rows = compared
+            .iter()
+            .map(|(name, scores)| Ok((name.clone(), self.num_identifications(scores)?)))
+            .collect::<Result<Vec<_>, String>>()?;
+        summary.add_content(html! {
+            p { (format!("{} PSMs ({} targets, {} decoys)", n, n_targets, n - n_targets)) }
+            table class="display" {
+                thead {
+                    tr {
+                        th { "Scores" }
+                        th { (format!("Target PSMs at {}% FDR", self.eval_fdr * 100.0)) }
+                    }
+                }
+                tbody {
+                    @for (name, count) in &rows {
+                        tr {
+                            td { (name) }
+                            td { (count) }
+                        }
+                    }
+                }
+            }
+        });
+        summary.add_plot(plot_identifications_vs_qvalue(
+            &compared,
+            &self.labels,
+            self.max_qvalue,
+            "Target identifications vs q-value",
+        )?);
+        report.add_section(summary);
+
+        // Score distributions, one per iteration
+        let mut distributions = ReportSection::new("Score Distributions");
+        let mut iterations: Vec<(String, &Vec<f32>)> = Vec::new();
+        if let Some((name, scores)) = &self.initial_scores {
+            iterations.push((format!("Initial scores ({})", name), scores));
+        }
+        for (i, scores) in self.iteration_scores.iter().enumerate() {
+            iterations.push((format!("Iteration {}", i + 1), scores));
+        }
+        if self.iteration_scores.is_empty() {
+            iterations.push(("Final scores".to_string(), &self.final_scores));
+        }
+        for (name, scores) in &iterations {
+            let scores: Vec<f64> = scores.iter().map(|&s| s as f64).collect();
+            distributions.add_plot(plot_score_histogram(&scores, &self.labels, name, "Score")?);
+        }
+        report.add_section(distributions);
+
+        // P-P plots
+        let mut pp = ReportSection::new("P-P Plots");
+        for (name, scores) in &compared {
+            let scores: Vec<f64> = scores.iter().map(|&s| s as f64).collect();
+            pp.add_plot(plot_pp(&scores, &self.labels, &format!("P-P plot: {}", name))?);
+        }
+        report.add_section(pp);
+
+        // Per-file scores
+        if let Some(file_ids) = &self.file_ids {
+            let mut per_file: BTreeMap<usize, Vec<f64>> = BTreeMap::new();
+            for (&file_id, &score) in file_ids.iter().zip(self.final_scores.iter()) {
+                per_file.entry(file_id).or_default().push(score as f64);
+            }
+            let names: Vec<String> = per_file
+                .keys()
+                .map(|&id| {
+                    self.file_names
+                        .as_ref()
+                        .and_then(|names| names.get(id).cloned())
+                        .unwrap_or_else(|| format!("File {}", id))
+                })
+                .collect();
+            let scores: Vec<Vec<f64>> = per_file.into_values().collect();
+
+            let mut files = ReportSection::new("Per-File Scores");
+            files.add_plot(plot_boxplot(&scores, names, "Final scores per file", "File", "Score")?);
+            report.add_section(files);
+        }
+
+        // Feature importance
+        if !self.feature_importances.is_empty() {
+            let mut importance = ReportSection::new("Feature Importance");
+            for fi in &self.feature_importances {
+                importance.add_plot(plot_feature_importance(fi, Some(30), &format!("{} feature importance", fi.importance_type))?);
+            }
+            report.add_section(importance);
+        }
+
+        Ok(report)
+    }
+
+    /// Build the report and write it to a single HTML file
+    pub fn save(&self, filename: &str) -> anyhow::Result<()> {
+        let report = self.build().map_err(|e| anyhow::anyhow!(e))?;
+        report.save_to_file(filename)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::feature_importance::ImportanceType;
+
+    #[test]
+    fn test_rescoring_report() {
+        let n = 100;
+        let labels = Array1::from_iter((0..n).map(|i| if i % 2 == 0 { 1 } else { -1 }));
+        let final_scores = Array1::from_iter((0..n).map(|i| if i % 2 == 0 { 1.0 + i as f32 / 10.0 } else { i as f32 / 50.0 }));
+        let initial_scores = Array1::from_iter((0..n).map(|i| (i % 7) as f32));
+
+        let builder = RescoringReportBuilder::new(&labels, &final_scores)
+            .with_initial_scores("hyperscore", &initial_scores)
+            .with_iteration_scores(&final_scores)
+            .with_file_ids((0..n).map(|i| i % 3).collect())
+            .with_file_names(vec!["a.mzML".to_string(), "b.mzML".to_string(), "c.mzML".to_string()])
+            .with_feature_importance(FeatureImportance::new(
+                ImportanceType::Gain,
+                &["hyperscore".to_string(), "delta_rt".to_string()],
+                vec![0.7, 0.3],
+            ))
+            .with_eval_fdr(0.05)
+            .with_embed_plotly_js(false);
+
+        assert!(builder.num_identifications(&final_scores.to_vec()).unwrap() > builder.num_identifications(&initial_scores.to_vec()).unwrap());
+
+        builder.save("test_rescoring_report.html").unwrap();
+    }
+}
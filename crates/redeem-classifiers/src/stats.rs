@@ -1,4 +1,5 @@
 use ndarray::{s, Array1, Axis};
+use serde::{Deserialize, Serialize};
 // use ndarray_stats::QuantileExt;
 
 use crate::error::TdcError;
@@ -88,14 +89,15 @@ pub fn tdc(scores: &Array1<f32>, target: &Array1<bool>, desc: bool) -> Result<Ar
     unique_scores.push(current_score);
     indices.push(count);
 
-    // Flip arrays if necessary
-    let fdr = if desc { fdr.slice(s![..;-1]).to_owned() } else { fdr };
-    let num_total = if desc { num_total.slice(s![..;-1]).to_owned() } else { num_total };
-    let unique_scores = if !desc { unique_scores.into_iter().rev().collect() } else { unique_scores };
-    let indices = if !desc { indices.into_iter().rev().collect() } else { indices };
+    // The arrays are ordered from best to worst score, fdr2qvalue expects them from worst to best
+    let fdr = fdr.slice(s![..;-1]).to_owned();
+    let num_total = num_total.slice(s![..;-1]).to_owned();
+    let unique_scores: Vec<f32> = unique_scores.into_iter().rev().collect();
+    let indices: Vec<usize> = indices.into_iter().rev().collect();
 
-    // Calculate q-values
+    // Calculate q-values, and flip them back to the best to worst order
     let qvals = fdr2qvalue(&fdr, &num_total, &unique_scores, &indices);
+    let qvals = qvals.slice(s![..;-1]).to_owned();
 
     // Reorder q-values to match original order
     let mut final_qvals = Array1::<f32>::zeros(scores.len());
@@ -196,6 +198,88 @@ fn fdr2qvalue(fdr: &Array1<f32>, num_total: &Array1<usize>, met: &Vec<f32>, indi
     qvals
 }
 
+/// Linear calibration of classifier scores, adapted from Percolator.
+///
+/// Scores are mapped so that the median decoy score becomes 0 and the score at the FDR threshold
+/// becomes 1. Scores of models trained on different cross-validation folds are calibrated
+/// separately, which puts them on a common scale before they are merged for TDC.
+///
+/// The default calibration is the identity.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ScoreCalibration {
+    /// Median score of the decoys, mapped to 0
+    pub decoy_median: f32,
+    /// Lowest score of the targets passing the FDR threshold, mapped to 1
+    pub threshold_score: f32,
+}
+
+impl Default for ScoreCalibration {
+    fn default() -> Self {
+        ScoreCalibration {
+            decoy_median: 0.0,
+            threshold_score: 1.0,
+        }
+    }
+}
+
+impl ScoreCalibration {
+    /// Fit the calibration on scores where higher is better
+    ///
+    /// # Arguments
+    ///
+    /// * `scores` - The scores to calibrate, typically predictions on a model's training set
+    /// * `target` - A boolean array indicating if the entry is from a target (true) or decoy (false) hit
+    /// * `eval_fdr` - The FDR threshold whose score is mapped to 1
+    ///
+    /// # Returns
+    ///
+    /// The calibration, or `None` if the scores contain NaN values, no target passes `eval_fdr`,
+    /// or the score at the threshold is not above the decoy median
+    pub fn fit(scores: &Array1<f32>, target: &Array1<bool>, eval_fdr: f32) -> Option<Self> {
+        let qvals = tdc(scores, target, true).ok()?;
+
+        let threshold_score = scores
+            .iter()
+            .zip(target.iter())
+            .zip(qvals.iter())
+            .filter(|((_, &t), &q)| t && q <= eval_fdr)
+            .map(|((&s, _), _)| s)
+            .fold(None, |min: Option<f32>, s| Some(min.map_or(s, |m| m.min(s))))?;
+
+        let mut decoys: Vec<f32> = scores
+            .iter()
+            .zip(target.iter())
+            .filter(|(_, &t)| !t)
+            .map(|(&s, _)| s)
+            .collect();
+        if decoys.is_empty() {
+            return None;
+        }
+        decoys.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let mid = decoys.len() / 2;
+        let decoy_median = if decoys.len() % 2 == 1 {
+            decoys[mid]
+        } else {
+            (decoys[mid - 1] + decoys[mid]) / 2.0
+        };
+
+        if threshold_score <= decoy_median {
+            return None;
+        }
+
+        Some(ScoreCalibration {
+            decoy_median,
+            threshold_score,
+        })
+    }
+
+    /// Apply the calibration to scores
+    pub fn transform(&self, scores: &Array1<f32>) -> Array1<f32> {
+        let scale = self.threshold_score - self.decoy_median;
+        scores.mapv(|s| (s - self.decoy_median) / scale)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -212,29 +296,47 @@ mod tests {
         
         // Check basic properties
         assert_eq!(result.len(), 5);
-        // Q-values should be monotonically increasing
-        for i in 0..result.len()-1 {
-            assert!(result[i] <= result[i+1], "Q-values should be monotonically increasing");
+        // Q-values should be monotonically increasing from the best to the worst score
+        let order = [4, 2, 0, 3, 1];
+        for w in order.windows(2) {
+            assert!(result[w[0]] <= result[w[1]], "Q-values should be monotonically increasing");
         }
-        // First entry (highest score) should have lowest q-value
-        assert!(result[4] < result[0]); // 5.5 is highest score at index 4
+        // Highest score should have a lower q-value than the lowest score
+        assert!(result[4] < result[1]); // 5.5 is highest score at index 4, 1.5 lowest at index 1
     }
 
     #[test]
     fn test_tdc_basic_working_case_ascending() {
-        // Test case where lower scores are better (desc = false)
-        let scores = array![3.2, 1.5, 4.0, 2.1, 5.5];
+        // Test case where lower scores are better (desc = false), mirroring the descending case
+        let scores = array![-3.2, -1.5, -4.0, -2.1, -5.5];
         let target = array![true, false, true, false, true];
         
         let result = tdc(&scores, &target, false).unwrap();
         
         assert_eq!(result.len(), 5);
-        // Q-values should be monotonically increasing
-        for i in 0..result.len()-1 {
-            assert!(result[i] <= result[i+1], "Q-values should be monotonically increasing");
+        // Q-values should be monotonically increasing from the best to the worst score
+        let order = [4, 2, 0, 3, 1];
+        for w in order.windows(2) {
+            assert!(result[w[0]] <= result[w[1]], "Q-values should be monotonically increasing");
+        }
+        // Lowest score should have a lower q-value than the highest score
+        assert!(result[4] < result[1]); // -5.5 is lowest score at index 4, -1.5 highest at index 1
+    }
+
+    #[test]
+    fn test_tdc_qvalues_follow_scores() {
+        // Three targets above two decoys: the targets share the FDR of the third target,
+        // (0 + 1) / 3, and each decoy gets the FDR at its own score
+        let target = array![true, true, true, false, false];
+        let expected = array![1.0 / 3.0, 1.0 / 3.0, 1.0 / 3.0, 2.0 / 3.0, 1.0];
+
+        let desc = tdc(&array![5.0, 4.0, 3.0, 2.0, 1.0], &target, true).unwrap();
+        let asc = tdc(&array![1.0, 2.0, 3.0, 4.0, 5.0], &target, false).unwrap();
+        for result in [desc, asc] {
+            for (q, e) in result.iter().zip(expected.iter()) {
+                assert_abs_diff_eq!(*q, *e, epsilon = 1e-6);
+            }
         }
-        // First entry (lowest score) should have lowest q-value
-        assert!(result[1] < result[4]); // 1.5 is lowest score at index 1
     }
 
     #[test]
@@ -256,6 +358,25 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_score_calibration() {
+        // 200 targets scored 1..=200 and 100 decoys scored -50..50
+        let scores = Array1::from_iter((1..=200).map(|i| i as f32).chain((0..100).map(|i| i as f32 - 50.0)));
+        let target = Array1::from_iter((0..300).map(|i| i < 200));
+
+        let calibration = ScoreCalibration::fit(&scores, &target, 0.01).unwrap();
+        assert_eq!(calibration.decoy_median, -0.5);
+        // Every target above the highest decoy (49) passes at 1% FDR
+        assert_eq!(calibration.threshold_score, 50.0);
+
+        let calibrated = calibration.transform(&array![-0.5, 50.0]);
+        assert_eq!(calibrated, array![0.0, 1.0]);
+
+        // No target passes the threshold when targets and decoys are indistinguishable
+        let target = Array1::from_iter((0..300).map(|i| i % 2 == 0));
+        assert!(ScoreCalibration::fit(&scores.mapv(|_| 1.0), &target, 0.01).is_none());
+    }
+
     #[test]
     fn test_tdc_length_mismatch() {
         // Test case with mismatched array lengths
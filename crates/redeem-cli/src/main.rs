@@ -1,4 +1,4 @@
-use clap::{Arg, Command, ArgMatches, ValueHint};
+use clap::{Arg, ArgAction, Command, ArgMatches, ValueHint};
 use log::LevelFilter;
 use std::path::PathBuf;
 use anyhow::Result;
@@ -7,6 +7,7 @@ use redeem_cli::properties::train::input::PropertyTrainConfig;
 use redeem_cli::properties::train::trainer;
 use redeem_cli::properties::inference::input::PropertyInferenceConfig;
 use redeem_cli::properties::inference::inference;
+use redeem_cli::properties::convert;
 
 fn main() -> Result<()> {
     env_logger::Builder::default()
@@ -79,6 +80,10 @@ fn main() -> Result<()> {
                                     "rt_cnn_tf",
                                     "ms2_bert",
                                     "ccs_cnn_lstm",
+                                    "detectability_cnn_lstm",
+                                    "detectability_cnn_tf",
+                                    "charge_cnn_lstm",
+                                    "charge_cnn_tf",
                                 ])
                                 .required(false)
                         )
@@ -127,6 +132,65 @@ fn main() -> Result<()> {
                             .value_parser(clap::value_parser!(PathBuf))
                             .value_hint(ValueHint::FilePath),
                     )
+                )
+                .subcommand(Command::new("convert")
+                    .about("Convert a pretrained PyTorch or safetensors checkpoint into a ReDeeM safetensors model")
+                    .arg(
+                        Arg::new("checkpoint_file")
+                            .help("Path to the checkpoint to convert (*.pth, *.pt or *.safetensors)")
+                            .required(true)
+                            .value_parser(clap::value_parser!(PathBuf))
+                            .value_hint(ValueHint::FilePath),
+                    )
+                    .arg(
+                        Arg::new("model_arch")
+                            .short('m')
+                            .long("model_arch")
+                            .help("Model architecture the checkpoint is converted for")
+                            .value_parser([
+                                "rt_cnn_lstm",
+                                "rt_cnn_tf",
+                                "ms2_bert",
+                                "ccs_cnn_lstm",
+                                "ccs_cnn_tf",
+                                "detectability_cnn_lstm",
+                                "detectability_cnn_tf",
+                                "charge_cnn_lstm",
+                                "charge_cnn_tf",
+                            ])
+                            .required(true),
+                    )
+                    .arg(
+                        Arg::new("output_file")
+                            .short('o')
+                            .long("output_file")
+                            .help("File path that the converted safetensors model will be written to")
+                            .required(true)
+                            .value_parser(clap::value_parser!(PathBuf))
+                            .value_hint(ValueHint::FilePath),
+                    )
+                    .arg(
+                        Arg::new("map")
+                            .long("map")
+                            .help(
+                                "Additional tensor name prefix mapping FROM=TO, applied after the \
+                                 built-in mapping of the architecture. Can be given multiple times.",
+                            )
+                            .value_parser(clap::builder::NonEmptyStringValueParser::new())
+                            .action(ArgAction::Append),
+                    )
+                    .arg(
+                        Arg::new("key")
+                            .long("key")
+                            .help("Key of the weights in a PyTorch checkpoint dictionary, e.g. state_dict")
+                            .value_parser(clap::builder::NonEmptyStringValueParser::new()),
+                    )
+                    .arg(
+                        Arg::new("allow_missing")
+                            .long("allow_missing")
+                            .help("Write the converted model even if tensors of the architecture are missing")
+                            .action(ArgAction::SetTrue),
+                    )
                 ),
         )
         .subcommand(
@@ -191,6 +255,33 @@ fn handle_properties(matches: &ArgMatches) -> Result<()> {
                 }
             }
         }
+        Some(("convert", convert_matches)) => {
+            let checkpoint_path: &PathBuf = convert_matches.get_one("checkpoint_file").unwrap();
+            let output_path: &PathBuf = convert_matches.get_one("output_file").unwrap();
+            let model_arch: &String = convert_matches.get_one("model_arch").unwrap();
+            log::info!("[ReDeeM::Properties] Converting checkpoint: {:?}", checkpoint_path);
+
+            let name_rules = convert_matches
+                .get_many::<String>("map")
+                .unwrap_or_default()
+                .map(|rule| convert::parse_name_rule(rule))
+                .collect::<Result<Vec<_>>>()?;
+
+            match convert::run_convert(
+                checkpoint_path,
+                output_path,
+                model_arch,
+                &name_rules,
+                convert_matches.get_one::<String>("key").map(|k| k.as_str()),
+                convert_matches.get_flag("allow_missing"),
+            ) {
+                Ok(_) => Ok(()),
+                Err(e) => {
+                    log::error!("Conversion failed: {:#}", e);
+                    std::process::exit(1)
+                }
+            }
+        }
         _ => unreachable!(),
     }
 }
@@ -0,0 +1,43 @@
+use anyhow::{Context, Result};
+use redeem_properties::models::convert::{convert_checkpoint, TensorNameMapping};
+use std::path::Path;
+
+/// Parse a `FROM=TO` tensor name prefix rule.
+pub fn parse_name_rule(rule: &str) -> Result<(String, String)> {
+    let (from, to) = rule
+        .split_once('=')
+        .with_context(|| format!("Invalid name mapping '{}', expected FROM=TO", rule))?;
+    Ok((from.to_string(), to.to_string()))
+}
+
+/// Convert an external checkpoint into a safetensors model for `model_arch`.
+///
+/// `name_rules` are applied after the architecture's built-in name mapping.
+pub fn run_convert<P: AsRef<Path>, Q: AsRef<Path>>(
+    input_path: P,
+    output_path: Q,
+    model_arch: &str,
+    name_rules: &[(String, String)],
+    key: Option<&str>,
+    allow_missing: bool,
+) -> Result<()> {
+    let mapping = name_rules
+        .iter()
+        .fold(TensorNameMapping::for_arch(model_arch), |mapping, (from, to)| {
+            mapping.with_rule(from.as_str(), to.as_str())
+        });
+
+    let report = convert_checkpoint(
+        &input_path,
+        &output_path,
+        model_arch,
+        &mapping,
+        key,
+        allow_missing,
+    )?;
+
+    log::info!("{}", report);
+    log::info!("Converted model saved to: {:?}", output_path.as_ref());
+
+    Ok(())
+}
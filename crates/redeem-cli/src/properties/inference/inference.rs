@@ -2,6 +2,11 @@ use anyhow::{Context, Result};
 use maud::{PreEscaped, html};
 use redeem_properties::models::ccs_cnn_lstm_model::CCSCNNLSTMModel;
 use redeem_properties::models::ccs_cnn_tf_model::CCSCNNTFModel;
+use redeem_properties::models::charge_cnn_lstm_model::ChargeCNNLSTMModel;
+use redeem_properties::models::charge_cnn_tf_model::ChargeCNNTFModel;
+use redeem_properties::models::detectability_cnn_lstm_model::DetectabilityCNNLSTMModel;
+use redeem_properties::models::detectability_cnn_tf_model::DetectabilityCNNTFModel;
+use redeem_properties::models::ensemble::{EnsembleModel, ENSEMBLE_ARCH};
 use redeem_properties::models::model_interface::ModelInterface;
 use redeem_properties::models::rt_cnn_lstm_model::RTCNNLSTMModel;
 use redeem_properties::models::rt_cnn_transformer_model::RTCNNTFModel;
@@ -16,20 +21,23 @@ use report_builder::{
 use crate::properties::inference::input::PropertyInferenceConfig;
 use crate::properties::inference::output::write_peptide_data;
 use crate::properties::train::sample_peptides;
+use crate::properties::train::trainer::expected_charge;
 use crate::properties::load_data::load_peptide_data;
-use crate::properties::util::write_bytes_to_file;
+use crate::properties::util::{data_model_arch, write_bytes_to_file};
 
 pub fn run_inference(config: &PropertyInferenceConfig) -> Result<()> {
     let modifications = load_modifications().context("Failed to load modifications")?;
+    let data_arch = data_model_arch(&config.model_arch, &config.model_path)?;
 
     // Load inference data
     let (inference_data, norm_factor) = load_peptide_data(
         &config.inference_data,
-        &config.model_arch,
+        &data_arch,
         Some(config.nce),
         Some(config.instrument.clone()),
         Some(config.normalization.clone().unwrap()),
         &modifications,
+        &config.ion_mobility_params(),
     )?;
     log::info!("Loaded {} peptides", inference_data.len());
 
@@ -74,6 +82,51 @@ pub fn run_inference(config: &PropertyInferenceConfig) -> Result<()> {
             true,
             device.clone(),
         )?),
+        "detectability_cnn_lstm" => Box::new(DetectabilityCNNLSTMModel::new(
+            &config.model_path,
+            None,
+            0,
+            8,
+            4,
+            true,
+            device.clone(),
+        )?),
+        "detectability_cnn_tf" => Box::new(DetectabilityCNNTFModel::new(
+            &config.model_path,
+            None,
+            0,
+            8,
+            4,
+            true,
+            device.clone(),
+        )?),
+        "charge_cnn_lstm" => Box::new(ChargeCNNLSTMModel::new(
+            &config.model_path,
+            None,
+            0,
+            8,
+            4,
+            true,
+            device.clone(),
+        )?),
+        "charge_cnn_tf" => Box::new(ChargeCNNTFModel::new(
+            &config.model_path,
+            None,
+            0,
+            8,
+            4,
+            true,
+            device.clone(),
+        )?),
+        ENSEMBLE_ARCH => Box::new(EnsembleModel::new(
+            &config.model_path,
+            None,
+            0,
+            8,
+            4,
+            true,
+            device.clone(),
+        )?),
         _ => {
             return Err(anyhow::anyhow!(
                 "Unsupported RT model architecture: {}",
@@ -84,12 +137,20 @@ pub fn run_inference(config: &PropertyInferenceConfig) -> Result<()> {
 
     let start_time = std::time::Instant::now();
     model.set_evaluation_mode();
-    let inference_results: Vec<PeptideData> = model.inference(
+    let mut inference_results: Vec<PeptideData> = model.inference(
         &inference_data,
         config.batch_size,
         modifications,
         norm_factor,
     )?;
+
+    // CCS models also report ion mobility (1/K0) for peptides with a precursor m/z and charge
+    if data_arch.contains("ccs") {
+        let ion_mobility_params = config.ion_mobility_params();
+        for peptide in &mut inference_results {
+            peptide.ion_mobility = peptide.ccs_to_ion_mobility(&ion_mobility_params);
+        }
+    }
     log::info!("Inference completed in {:?}", start_time.elapsed());
 
     log::info!("Predictions saved to: {}", config.output_file);
@@ -113,8 +174,12 @@ pub fn run_inference(config: &PropertyInferenceConfig) -> Result<()> {
 
         let modifications = MODIFICATION_MAP.clone();
 
-        let normalize_field = if config.model_arch.contains("ccs") {
+        let normalize_field = if data_arch.contains("ccs") {
             "ccs"
+        } else if data_arch.contains("detectability") {
+            "detectability"
+        } else if data_arch.contains("charge") {
+            "charge"
         } else {
             "retention time"
         };
@@ -140,6 +205,18 @@ pub fn run_inference(config: &PropertyInferenceConfig) -> Result<()> {
                             _ => None,
                         }
                     },
+                    "detectability" => {
+                        match (true_pep.detectability, pred_pep.detectability) {
+                            (Some(t), Some(p)) => Some((t as f64, p as f64)),
+                            _ => None,
+                        }
+                    },
+                    "charge" => {
+                        match (&true_pep.charge_distribution, &pred_pep.charge_distribution) {
+                            (Some(t), Some(p)) => Some((expected_charge(t), expected_charge(p))),
+                            _ => None,
+                        }
+                    },
                     _ => {
                         match (true_pep.retention_time, pred_pep.retention_time) {
                         (Some(t), Some(p)) => {
@@ -3,6 +3,7 @@ use std::fs;
 use std::path::PathBuf;
 use clap::ArgMatches;
 use anyhow::{Context, Result};
+use redeem_properties::utils::peptdeep_utils::{IonMobilityParams, DEFAULT_CCS_IM_COEF, DEFAULT_IM_GAS_MASS};
 
 use crate::properties::util::validate_tsv_or_csv_file;
 
@@ -18,6 +19,8 @@ pub struct PropertyInferenceConfig {
     pub batch_size: usize,
     pub instrument: String,
     pub nce: i32,
+    pub im_gas_mass: f64,
+    pub ccs_im_coef: f64,
 }
 
 impl Default for PropertyInferenceConfig {
@@ -33,6 +36,8 @@ impl Default for PropertyInferenceConfig {
             batch_size: 64,
             instrument: String::from("QE"),
             nce: 20,
+            im_gas_mass: DEFAULT_IM_GAS_MASS,
+            ccs_im_coef: DEFAULT_CCS_IM_COEF,
         }
     }
 }
@@ -74,6 +79,8 @@ impl PropertyInferenceConfig {
         load_or_default!(batch_size);
         load_or_default!(instrument);
         load_or_default!(nce);
+        load_or_default!(im_gas_mass);
+        load_or_default!(ccs_im_coef);
 
         // Apply CLI overrides
         if let Some(model_path) = matches.get_one::<String>("model_path") {
@@ -94,5 +101,9 @@ impl PropertyInferenceConfig {
 
         Ok(config)
     }
-}
 
+    /// Parameters used to convert between CCS and ion mobility (1/K0).
+    pub fn ion_mobility_params(&self) -> IonMobilityParams {
+        IonMobilityParams::new(self.im_gas_mass, self.ccs_im_coef)
+    }
+}
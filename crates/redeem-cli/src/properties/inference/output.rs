@@ -34,6 +34,8 @@ pub fn write_peptide_data<P: AsRef<Path>>(data: &[PeptideData], output_path: P)
         "ion_mobility",
         "ccs",
         "ms2_intensities",
+        "detectability",
+        "charge_distribution",
     ])?;
 
     for entry in data {
@@ -45,6 +47,12 @@ pub fn write_peptide_data<P: AsRef<Path>>(data: &[PeptideData], output_path: P)
             })
             .unwrap_or_default();
 
+        let charge_distribution_str = entry.charge_distribution.as_ref()
+            .map(|distribution| {
+                distribution.iter().map(|p| format!("{:.4}", p)).collect::<Vec<_>>().join(",")
+            })
+            .unwrap_or_default();
+
         writer.write_record(&[
             entry.modified_sequence_str(),
             entry.naked_sequence_str(),
@@ -58,6 +66,8 @@ pub fn write_peptide_data<P: AsRef<Path>>(data: &[PeptideData], output_path: P)
             &entry.ion_mobility.map_or(String::new(), |im| format!("{:.4}", im)),
             &entry.ccs.map_or(String::new(), |c| format!("{:.4}", c)),
             &ms2_str,
+            &entry.detectability.map_or(String::new(), |d| format!("{:.4}", d)),
+            &charge_distribution_str,
         ])?;
     }
 
@@ -4,7 +4,8 @@ use std::path::Path;
 use std::io::BufReader;
 use anyhow::{Result, Context};
 use csv::ReaderBuilder;
-use redeem_properties::utils::peptdeep_utils::{get_modification_indices, get_modification_string, ModificationMap};
+use redeem_properties::models::model_interface::NUM_CHARGE_STATES;
+use redeem_properties::utils::peptdeep_utils::{get_modification_indices, get_modification_string, IonMobilityParams, ModificationMap};
 use redeem_properties::utils::{data_handling::{PeptideData, TargetNormalization}, peptdeep_utils::remove_mass_shift};
 
 
@@ -12,6 +13,9 @@ use redeem_properties::utils::{data_handling::{PeptideData, TargetNormalization}
 /// Load peptide training data from a CSV or TSV file and optionally normalize RT.
 ///
 /// Returns both the peptide vector and optionally (mean, std) of retention times.
+///
+/// For CCS models, rows with an ion mobility (1/K0) but no CCS get their CCS target converted
+/// from the ion mobility, precursor m/z and charge using `ion_mobility_params`.
 pub fn load_peptide_data<P: AsRef<Path>>(
     path: P,
     model_arch: &str,
@@ -19,6 +23,7 @@ pub fn load_peptide_data<P: AsRef<Path>>(
     instrument: Option<String>,
     normalize_target: Option<String>,
     modifications: &HashMap<(String, Option<char>), ModificationMap>,
+    ion_mobility_params: &IonMobilityParams,
 ) -> Result<(Vec<PeptideData>, TargetNormalization)> {
     let file = File::open(&path)
         .with_context(|| format!("Failed to open file: {:?}", path.as_ref()))?;
@@ -65,7 +70,7 @@ pub fn load_peptide_data<P: AsRef<Path>>(
             .and_then(|s| s.parse::<f32>().ok());
 
         let charge = match model_arch {
-            "rt_cnn_lstm" | "rt_cnn_tf" => None,
+            "rt_cnn_lstm" | "rt_cnn_tf" | "detectability_cnn_lstm" | "detectability_cnn_tf" => None,
             _ => record
                 .get(headers.iter().position(|h| h.to_lowercase() == "charge").unwrap_or(usize::MAX))
                 .and_then(|s| s.parse::<i32>().ok()),
@@ -83,6 +88,11 @@ pub fn load_peptide_data<P: AsRef<Path>>(
             .get(headers.iter().position(|h| h.to_lowercase() == "ccs").unwrap_or(usize::MAX))
             .and_then(|s| s.parse::<f32>().ok());
 
+        // Observed (1) / unobserved (0) label for detectability models
+        let detectability = record
+            .get(headers.iter().position(|h| h.to_lowercase() == "detectability").unwrap_or(usize::MAX))
+            .and_then(|s| s.parse::<f32>().ok());
+
         let in_nce = match model_arch {
             "ms2_bert" => nce.or_else(|| {
                 record
@@ -104,14 +114,7 @@ pub fn load_peptide_data<P: AsRef<Path>>(
             _ => None,
         };
 
-        if let Some(val) = match normalize_field {
-            "ccs" => ccs,
-            _ => retention_time,
-        } {
-            target_values.push(val);
-        }
-
-        peptides.push(PeptideData {
+        let mut peptide = PeptideData {
             modified_sequence: sequence_bytes,
             naked_sequence,
             mods,
@@ -124,7 +127,32 @@ pub fn load_peptide_data<P: AsRef<Path>>(
             ion_mobility,
             ccs,
             ms2_intensities: None,
-        });
+            detectability,
+            charge_distribution: None,
+        };
+
+        if normalize_field == "ccs" && peptide.ccs.is_none() {
+            peptide.ccs = peptide.ion_mobility_to_ccs(ion_mobility_params);
+        }
+
+        if let Some(val) = match normalize_field {
+            "ccs" => peptide.ccs,
+            _ => peptide.retention_time,
+        } {
+            target_values.push(val);
+        }
+
+        peptides.push(peptide);
+    }
+
+    // Charge state models are trained on one distribution per peptide, aggregated over its observed charges
+    if model_arch.contains("charge") {
+        return Ok((aggregate_charge_distributions(peptides), TargetNormalization::None));
+    }
+
+    // Detectability labels are probabilities and are never normalized
+    if model_arch.contains("detectability") {
+        return Ok((peptides, TargetNormalization::None));
     }
 
     match TargetNormalization::from_str(normalize_target) {
@@ -162,3 +190,38 @@ pub fn load_peptide_data<P: AsRef<Path>>(
         _ => Ok((peptides, TargetNormalization::None)),
     }
 }
+
+
+/// Collapse rows of the same modified peptide into a single peptide, with the fraction of rows
+/// observed at each charge state from 1 to 6 as its charge state distribution.
+///
+/// Peptides without any charge in the supported range get no distribution.
+fn aggregate_charge_distributions(peptides: Vec<PeptideData>) -> Vec<PeptideData> {
+    let mut order: Vec<Arc<[u8]>> = Vec::new();
+    let mut grouped: HashMap<Arc<[u8]>, (PeptideData, Vec<f32>)> = HashMap::new();
+
+    for peptide in peptides {
+        let charge = peptide.charge;
+        let (_, counts) = grouped
+            .entry(Arc::clone(&peptide.modified_sequence))
+            .or_insert_with(|| {
+                order.push(Arc::clone(&peptide.modified_sequence));
+                (peptide, vec![0.0; NUM_CHARGE_STATES])
+            });
+        if let Some(c) = charge.filter(|c| (1..=NUM_CHARGE_STATES as i32).contains(c)) {
+            counts[(c - 1) as usize] += 1.0;
+        }
+    }
+
+    order
+        .into_iter()
+        .filter_map(|sequence| grouped.remove(&sequence))
+        .map(|(mut peptide, counts)| {
+            let total: f32 = counts.iter().sum();
+            peptide.charge = None;
+            peptide.charge_distribution =
+                (total > 0.0).then(|| counts.iter().map(|c| c / total).collect());
+            peptide
+        })
+        .collect()
+}
@@ -2,3 +2,4 @@ pub mod train;
 pub mod inference;
 pub mod load_data;
 pub mod util;
+pub mod convert;
@@ -3,6 +3,7 @@ use std::fs;
 use std::path::PathBuf;
 use clap::ArgMatches;
 use anyhow::{Context, Result};
+use redeem_properties::utils::peptdeep_utils::{IonMobilityParams, DEFAULT_CCS_IM_COEF, DEFAULT_IM_GAS_MASS};
 
 use crate::properties::util::validate_tsv_or_csv_file;
 
@@ -24,6 +25,8 @@ pub struct PropertyTrainConfig {
     pub checkpoint_file: Option<String>,
     pub instrument: String,
     pub nce: i32,
+    pub im_gas_mass: f64,
+    pub ccs_im_coef: f64,
 }
 
 impl Default for PropertyTrainConfig {
@@ -44,6 +47,8 @@ impl Default for PropertyTrainConfig {
             checkpoint_file: None,
             instrument: String::from("QE"),
             nce: 20,
+            im_gas_mass: DEFAULT_IM_GAS_MASS,
+            ccs_im_coef: DEFAULT_CCS_IM_COEF,
         }
     }
 }
@@ -90,6 +95,8 @@ impl PropertyTrainConfig {
         load_or_default!(checkpoint_file);
         load_or_default!(instrument);
         load_or_default!(nce);
+        load_or_default!(im_gas_mass);
+        load_or_default!(ccs_im_coef);
 
         // Apply CLI overrides
         if let Some(train_data) = matches.get_one::<String>("train_data") {
@@ -110,4 +117,9 @@ impl PropertyTrainConfig {
 
         Ok(config)
     }
-}
\ No newline at end of file
+
+    /// Parameters used to convert between CCS and ion mobility (1/K0).
+    pub fn ion_mobility_params(&self) -> IonMobilityParams {
+        IonMobilityParams::new(self.im_gas_mass, self.ccs_im_coef)
+    }
+}
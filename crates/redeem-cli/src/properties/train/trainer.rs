@@ -3,6 +3,9 @@ use maud::{PreEscaped, html};
 use redeem_properties::models::model_interface::ModelInterface;
 use redeem_properties::models::{
     ccs_cnn_lstm_model::CCSCNNLSTMModel, ccs_cnn_tf_model::CCSCNNTFModel,
+    charge_cnn_lstm_model::ChargeCNNLSTMModel, charge_cnn_tf_model::ChargeCNNTFModel,
+    detectability_cnn_lstm_model::DetectabilityCNNLSTMModel,
+    detectability_cnn_tf_model::DetectabilityCNNTFModel, ensemble::ENSEMBLE_ARCH,
     rt_cnn_lstm_model::RTCNNLSTMModel, rt_cnn_transformer_model::RTCNNTFModel,
 };
 use redeem_properties::utils::data_handling::{PeptideData, TargetNormalization};
@@ -23,6 +26,13 @@ use load_data::load_peptide_data;
 use super::input;
 
 pub fn run_training(config: &PropertyTrainConfig) -> Result<()> {
+    // Ensemble members are trained individually and combined with a bundle manifest
+    if config.model_arch == ENSEMBLE_ARCH {
+        return Err(anyhow::anyhow!(
+            "Ensembles can't be trained, train each member model and list them in an ensemble manifest"
+        ));
+    }
+
     log::trace!("Loading modifications map");
     let modifications = load_modifications().context("Failed to load modifications")?;
 
@@ -34,6 +44,7 @@ pub fn run_training(config: &PropertyTrainConfig) -> Result<()> {
         Some(config.instrument.clone()),
         Some(config.normalization.clone().unwrap()),
         &modifications,
+        &config.ion_mobility_params(),
     )?;
     log::info!("Loaded {} training peptides", train_peptides.len());
 
@@ -46,6 +57,7 @@ pub fn run_training(config: &PropertyTrainConfig) -> Result<()> {
             Some(config.instrument.clone()),
             Some(config.normalization.clone().unwrap()),
             &modifications,
+            &config.ion_mobility_params(),
         )
         .context("Failed to load validation data")?;
         (Some(peptides), Some(norm))
@@ -108,6 +120,42 @@ pub fn run_training(config: &PropertyTrainConfig) -> Result<()> {
                     true,
                     device.clone(),
                 )?),
+                "detectability_cnn_lstm" => Box::new(DetectabilityCNNLSTMModel::new(
+                    checkpoint_path,
+                    None,
+                    0,
+                    8,
+                    4,
+                    true,
+                    device.clone(),
+                )?),
+                "detectability_cnn_tf" => Box::new(DetectabilityCNNTFModel::new(
+                    checkpoint_path,
+                    None,
+                    0,
+                    8,
+                    4,
+                    true,
+                    device.clone(),
+                )?),
+                "charge_cnn_lstm" => Box::new(ChargeCNNLSTMModel::new(
+                    checkpoint_path,
+                    None,
+                    0,
+                    8,
+                    4,
+                    true,
+                    device.clone(),
+                )?),
+                "charge_cnn_tf" => Box::new(ChargeCNNTFModel::new(
+                    checkpoint_path,
+                    None,
+                    0,
+                    8,
+                    4,
+                    true,
+                    device.clone(),
+                )?),
                 _ => {
                     return Err(anyhow::anyhow!(
                         "Unsupported model architecture: {}",
@@ -121,6 +169,10 @@ pub fn run_training(config: &PropertyTrainConfig) -> Result<()> {
             "rt_cnn_tf" => Box::new(RTCNNTFModel::new_untrained(device.clone())?),
             "ccs_cnn_lstm" => Box::new(CCSCNNLSTMModel::new_untrained(device.clone())?),
             "ccs_cnn_tf" => Box::new(CCSCNNTFModel::new_untrained(device.clone())?),
+            "detectability_cnn_lstm" => Box::new(DetectabilityCNNLSTMModel::new_untrained(device.clone())?),
+            "detectability_cnn_tf" => Box::new(DetectabilityCNNTFModel::new_untrained(device.clone())?),
+            "charge_cnn_lstm" => Box::new(ChargeCNNLSTMModel::new_untrained(device.clone())?),
+            "charge_cnn_tf" => Box::new(ChargeCNNTFModel::new_untrained(device.clone())?),
             _ => {
                 return Err(anyhow::anyhow!(
                     "Unsupported model architecture: {}",
@@ -237,6 +289,17 @@ pub fn run_training(config: &PropertyTrainConfig) -> Result<()> {
                         _ => None,
                   
                     }
+                } else if config.model_arch.contains("detectability") {
+                    match (true_pep.detectability, pred_pep.detectability) {
+                        (Some(t), Some(p)) => Some((t as f64, p as f64)),
+                        _ => None,
+                    }
+                } else if config.model_arch.contains("charge") {
+                    // Compare the expected (mean) charge of the observed and predicted distributions
+                    match (&true_pep.charge_distribution, &pred_pep.charge_distribution) {
+                        (Some(t), Some(p)) => Some((expected_charge(t), expected_charge(p))),
+                        _ => None,
+                    }
                 } else {
                     return None;
                 }
@@ -296,3 +359,12 @@ pub fn run_training(config: &PropertyTrainConfig) -> Result<()> {
 
     Ok(())
 }
+
+/// Expected (mean) charge state of a charge state distribution over charges 1 to 6.
+pub fn expected_charge(distribution: &[f32]) -> f64 {
+    distribution
+        .iter()
+        .enumerate()
+        .map(|(i, p)| (i + 1) as f64 * *p as f64)
+        .sum()
+}
@@ -1,4 +1,5 @@
 use anyhow::Result;
+use redeem_properties::models::ensemble::{EnsembleManifest, ENSEMBLE_ARCH};
 use std::{fs::File, io::Write, path::{Path, PathBuf}};
 
 
@@ -23,4 +24,18 @@ pub fn write_bytes_to_file(path: &str, bytes: &[u8]) -> std::io::Result<()> {
     let mut file = File::create(path)?;
     file.write_all(bytes)?;
     Ok(())
-}
\ No newline at end of file
+}
+/// Architecture used to load and report the peptide data of a model.
+///
+/// All members of an ensemble predict the same property, so an ensemble is handled like the first
+/// member of the manifest at `model_path`.
+pub fn data_model_arch(model_arch: &str, model_path: &str) -> Result<String> {
+    if model_arch != ENSEMBLE_ARCH {
+        return Ok(model_arch.to_string());
+    }
+    let manifest = EnsembleManifest::from_file(model_path)?;
+    match manifest.members.first() {
+        Some(member) => Ok(member.arch.clone()),
+        None => anyhow::bail!("Ensemble manifest has no members: {}", model_path),
+    }
+}
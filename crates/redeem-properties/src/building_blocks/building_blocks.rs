@@ -15,11 +15,6 @@ use crate::utils::utils::get_tensor_stats;
 
 use super::nn::TransformerEncoder;
 
-/// constants used by PeptDeep Models
-pub const MOD_FEATURE_SIZE: usize = 109; // TODO: derive from constants yaml
-pub const AA_EMBEDDING_SIZE: usize = 27; // TODO: derive from constants yaml
-const MAX_INSTRUMENT_NUM: usize = 8; // TODO: derive from constants yaml
-
 /// Decode w linear NN
 #[derive(Clone)]
 pub struct DecoderLinear {
@@ -97,15 +92,20 @@ struct AAEmbedding {
 }
 
 impl AAEmbedding {
-    fn new(hidden_size: usize, vb: &nn::VarBuilder) -> Result<Self> {
+    fn new(aa_embedding_size: usize, hidden_size: usize, vb: &nn::VarBuilder) -> Result<Self> {
         // Create the embedding layer
-        let embeddings = nn::embedding(AA_EMBEDDING_SIZE, hidden_size, vb.pp("embedding"))?;
+        let embeddings = nn::embedding(aa_embedding_size, hidden_size, vb.pp("embedding"))?;
 
         Ok(Self { embeddings })
     }
 
-    fn from_varstore(varstore: &nn::VarBuilder, hidden_size: usize, name: &str) -> Result<Self> {
-        let weight = varstore.get((AA_EMBEDDING_SIZE, hidden_size), name)?;
+    fn from_varstore(
+        varstore: &nn::VarBuilder,
+        aa_embedding_size: usize,
+        hidden_size: usize,
+        name: &str
+    ) -> Result<Self> {
+        let weight = varstore.get((aa_embedding_size, hidden_size), name)?;
         let embeddings = nn::Embedding::new(weight, hidden_size);
         Ok(Self { embeddings })
     }
@@ -207,7 +207,7 @@ impl ModEmbeddingFixFirstK {
         name: &str
     ) -> Result<Self> {
         let k = 6;
-        let weight = varstore.get((out_features - k, MOD_FEATURE_SIZE - k), name);
+        let weight = varstore.get((out_features - k, mod_feature_size - k), name);
         let nn = nn::Linear::new(weight.unwrap(), None);
         Ok(Self { k, nn })
     }
@@ -244,6 +244,8 @@ impl Input26aaModPositionalEncoding {
 
     pub fn from_varstore(
         varstore: &nn::VarBuilder,
+        mod_feature_size: usize,
+        aa_embedding_size: usize,
         out_features: usize,
         max_len: usize,
         names: Vec<&str>
@@ -252,11 +254,16 @@ impl Input26aaModPositionalEncoding {
         Ok(Self {
             mod_nn: ModEmbeddingFixFirstK::from_varstore(
                 varstore,
-                MOD_FEATURE_SIZE,
+                mod_feature_size,
                 mod_hidden,
                 names[0]
             )?,
-            aa_emb: AAEmbedding::from_varstore(varstore, out_features - mod_hidden, names[1])?,
+            aa_emb: AAEmbedding::from_varstore(
+                varstore,
+                aa_embedding_size,
+                out_features - mod_hidden,
+                names[1]
+            )?,
             pos_encoder: PositionalEncoding::from_varstore(
                 varstore,
                 out_features,
@@ -279,27 +286,29 @@ impl Input26aaModPositionalEncoding {
 #[derive(Debug, Clone)]
 pub struct MetaEmbedding {
     nn: nn::Linear,
+    max_instrument_num: usize,
 }
 
 impl MetaEmbedding {
-    fn _new(out_features: usize, device: &Device) -> Result<Self> {
+    fn _new(max_instrument_num: usize, out_features: usize, device: &Device) -> Result<Self> {
         let nn = nn::linear(
-            MAX_INSTRUMENT_NUM + 1,
+            max_instrument_num + 1,
             out_features - 1,
             nn::VarBuilder::zeros(DType::F32, device).pp("linear"),
         )?;
-        Ok(Self { nn })
+        Ok(Self { nn, max_instrument_num })
     }
 
     pub fn from_varstore(
         varstore: &nn::VarBuilder,
+        max_instrument_num: usize,
         out_features: usize,
         name: Vec<&str>
     ) -> Result<Self> {
-        let weight = varstore.get((out_features - 1, MAX_INSTRUMENT_NUM + 1), name[0]);
+        let weight = varstore.get((out_features - 1, max_instrument_num + 1), name[0]);
         let bias = varstore.get(out_features - 1, name[1]);
         let nn = nn::Linear::new(weight.unwrap(), Some(bias.unwrap()));
-        Ok(Self { nn })
+        Ok(Self { nn, max_instrument_num })
     }
 
     fn one_hot(&self, indices: &Tensor, num_classes: usize) -> Result<Tensor> {
@@ -339,7 +348,7 @@ impl MetaEmbedding {
 
 
         // One-hot encode the instrument indices
-        let inst_x = self.one_hot(&instrument_indices.to_dtype(DType::I64)?, MAX_INSTRUMENT_NUM)?;
+        let inst_x = self.one_hot(&instrument_indices.to_dtype(DType::I64)?, self.max_instrument_num)?;
 
         // Ensure all tensors are on the same device
         let charges = &charges.to_device(inst_x.device())?;
@@ -487,6 +496,16 @@ impl ModLossNN {
 
         Ok(Self { modules })
     }
+
+    /// Forward pass through the modloss transformer only (`modloss_nn[0]`).
+    pub fn forward_transformer(&self, xs: &Tensor) -> Result<Tensor> {
+        self.modules[0].forward(xs)
+    }
+
+    /// Forward pass through the modloss decoder only (`modloss_nn[-1]`).
+    pub fn forward_decoder(&self, xs: &Tensor) -> Result<Tensor> {
+        self.modules[self.modules.len() - 1].forward(xs)
+    }
 }
 
 impl Module for ModLossNN {
@@ -791,6 +810,7 @@ impl Module for SeqAttentionSum {
 /// Encode AAs (26 AA letters) and modifications by CNN and LSTM layers, then by 'SeqAttentionSum'.
 #[derive(Debug, Clone)]
 pub struct Encoder26aaModCnnLstmAttnSum {
+    aa_embedding_size: usize,
     mod_nn: ModEmbeddingFixFirstK,
     input_cnn: SeqCNN,
     input_lstm: SeqLSTM,
@@ -798,12 +818,34 @@ pub struct Encoder26aaModCnnLstmAttnSum {
 }
 
 impl Encoder26aaModCnnLstmAttnSum {
-    fn _new() -> Self {
-        unimplemented!();
+    /// Construct a CNN+LSTM+Attention encoder from scratch (no pretrained weights).
+    pub fn new(
+        varbuilder: &nn::VarBuilder,
+        mod_feature_size: usize,
+        aa_embedding_size: usize,
+        mod_hidden_dim: usize,
+        hidden_dim: usize,
+        num_layers: usize,
+    ) -> Result<Self> {
+        let input_dim = aa_embedding_size + mod_hidden_dim;
+        Ok(Self {
+            aa_embedding_size,
+            mod_nn: ModEmbeddingFixFirstK::new(mod_feature_size, mod_hidden_dim, &varbuilder.pp("mod_nn"))?,
+            input_cnn: SeqCNN::new(input_dim, &varbuilder.pp("input_cnn"))?,
+            input_lstm: SeqLSTM::from_varstore(
+                varbuilder.pp("hidden_nn"),
+                input_dim * 4,
+                hidden_dim,
+                num_layers,
+            )?,
+            attn_sum: SeqAttentionSum::new(hidden_dim * 2, &varbuilder.pp("attn_sum"))?,
+        })
     }
 
     pub fn from_varstore(
         varstore: &nn::VarBuilder,
+        mod_feature_size: usize,
+        aa_embedding_size: usize,
         mod_hidden_dim: usize,
         hidden_dim: usize,
         num_layers: usize,
@@ -813,11 +855,12 @@ impl Encoder26aaModCnnLstmAttnSum {
         lstm_pp: &str,
         names_attn_sum: Vec<&str>,
     ) -> Result<Self> {
-        let input_dim = AA_EMBEDDING_SIZE + mod_hidden_dim;
+        let input_dim = aa_embedding_size + mod_hidden_dim;
         Ok(Self {
+            aa_embedding_size,
             mod_nn: ModEmbeddingFixFirstK::from_varstore(
                 &varstore,
-                MOD_FEATURE_SIZE,
+                mod_feature_size,
                 mod_hidden_dim,
                 names_mod_nn[0],
             )?,
@@ -846,7 +889,7 @@ impl Encoder26aaModCnnLstmAttnSum {
 
         let additional_tensors: Vec<&Tensor> = vec![&mod_x];
 
-        let x = aa_one_hot(&aa_indices, &additional_tensors)
+        let x = aa_one_hot(&aa_indices, self.aa_embedding_size, &additional_tensors)
             .map_err(|e| candle_core::Error::Msg(e.to_string()))?;
 
         let (mean, min, max) = get_tensor_stats(&x)?;
@@ -869,6 +912,7 @@ impl Encoder26aaModCnnLstmAttnSum {
 /// Encode AAs (26 AA letters), modifications and charge by CNN and LSTM layers, then by 'SeqAttentionSum'.
 #[derive(Debug, Clone)]
 pub struct Encoder26aaModChargeCnnLstmAttnSum {
+    aa_embedding_size: usize,
     mod_nn: ModEmbeddingFixFirstK,
     input_cnn: SeqCNN,
     input_lstm: SeqLSTM,
@@ -882,6 +926,8 @@ impl Encoder26aaModChargeCnnLstmAttnSum {
 
     pub fn from_varstore(
         varstore: &nn::VarBuilder,
+        mod_feature_size: usize,
+        aa_embedding_size: usize,
         mod_hidden_dim: usize,
         hidden_dim: usize,
         num_layers: usize,
@@ -891,11 +937,12 @@ impl Encoder26aaModChargeCnnLstmAttnSum {
         lstm_pp: &str,
         names_attn_sum: Vec<&str>,
     ) -> Result<Self> {
-        let input_dim = AA_EMBEDDING_SIZE + mod_hidden_dim + 1;
+        let input_dim = aa_embedding_size + mod_hidden_dim + 1;
         Ok(Self {
+            aa_embedding_size,
             mod_nn: ModEmbeddingFixFirstK::from_varstore(
                 &varstore,
-                MOD_FEATURE_SIZE,
+                mod_feature_size,
                 mod_hidden_dim,
                 names_mod_nn[0],
             )?,
@@ -926,7 +973,7 @@ impl Encoder26aaModChargeCnnLstmAttnSum {
         let charges_repeated = charges.unsqueeze(1)?.repeat(&[1, mod_x.dim(1)?, 1])?;
         let additional_tensors: Vec<&Tensor> = vec![&mod_x, &charges_repeated];
         
-        let x = aa_one_hot(&aa_indices, &additional_tensors)
+        let x = aa_one_hot(&aa_indices, self.aa_embedding_size, &additional_tensors)
             .map_err(|e| candle_core::Error::Msg(e.to_string()))?;
 
         let (mean, min, max) = get_tensor_stats(&x)?;
@@ -946,6 +993,7 @@ impl Encoder26aaModChargeCnnLstmAttnSum {
 /// Encode AAs (26 AA letters) and modifications using CNN + Transformer + AttentionSum.
 #[derive(Debug, Clone)]
 pub struct Encoder26aaModCnnTransformerAttnSum {
+    aa_embedding_size: usize,
     mod_nn: ModEmbeddingFixFirstK,
     input_cnn: SeqCNN,
     proj_cnn_to_transformer: candle_nn::Linear,
@@ -956,6 +1004,8 @@ pub struct Encoder26aaModCnnTransformerAttnSum {
 impl Encoder26aaModCnnTransformerAttnSum {
     pub fn from_varstore(
         varstore: &nn::VarBuilder,
+        mod_feature_size: usize,
+        aa_embedding_size: usize,
         mod_hidden_dim: usize,
         hidden_dim: usize,
         ff_dim: usize,
@@ -970,11 +1020,12 @@ impl Encoder26aaModCnnTransformerAttnSum {
         names_attn_sum: Vec<&str>,
         device: &Device,
     ) -> Result<Self> {
-        let input_dim = AA_EMBEDDING_SIZE + mod_hidden_dim;
+        let input_dim = aa_embedding_size + mod_hidden_dim;
         Ok(Self {
+            aa_embedding_size,
             mod_nn: ModEmbeddingFixFirstK::from_varstore(
                 &varstore,
-                MOD_FEATURE_SIZE,
+                mod_feature_size,
                 mod_hidden_dim,
                 names_mod_nn[0],
             )?,
@@ -1010,6 +1061,8 @@ impl Encoder26aaModCnnTransformerAttnSum {
     /// Construct a CNN+Transformer+Attention encoder from scratch (no pretrained weights).
     pub fn new(
         varbuilder: &nn::VarBuilder,
+        mod_feature_size: usize,
+        aa_embedding_size: usize,
         mod_hidden_dim: usize,
         hidden_dim: usize,
         ff_dim: usize,
@@ -1019,9 +1072,10 @@ impl Encoder26aaModCnnTransformerAttnSum {
         dropout_prob: f32,
         device: &Device,
     ) -> Result<Self> {
-        let input_dim = AA_EMBEDDING_SIZE + mod_hidden_dim;
+        let input_dim = aa_embedding_size + mod_hidden_dim;
         Ok(Self {
-            mod_nn: ModEmbeddingFixFirstK::new(MOD_FEATURE_SIZE, mod_hidden_dim, &varbuilder.pp("mod_nn"))?,
+            aa_embedding_size,
+            mod_nn: ModEmbeddingFixFirstK::new(mod_feature_size, mod_hidden_dim, &varbuilder.pp("mod_nn"))?,
             input_cnn: SeqCNN::new(input_dim, &varbuilder.pp("input_cnn"))?,
             proj_cnn_to_transformer: candle_nn::linear_no_bias(input_dim * 4, hidden_dim, varbuilder.pp("proj_cnn_to_transformer"))?,
             input_transformer: SeqTransformer::new(
@@ -1039,12 +1093,17 @@ impl Encoder26aaModCnnTransformerAttnSum {
         })
     }
 
+    /// Enable or disable dropout in the transformer layers.
+    pub fn set_training(&mut self, training: bool) {
+        self.input_transformer.set_training(training);
+    }
+
     pub fn forward(&self, aa_indices: &Tensor, mod_x: &Tensor) -> Result<Tensor> {
         let mod_x = self.mod_nn.forward(mod_x)?;
 
         let additional_tensors: Vec<&Tensor> = vec![&mod_x];
 
-        let x = aa_one_hot(aa_indices, &additional_tensors)
+        let x = aa_one_hot(aa_indices, self.aa_embedding_size, &additional_tensors)
             .map_err(|e| candle_core::Error::Msg(e.to_string()))?;
 
         let (mean, min, max) = get_tensor_stats(&x)?;
@@ -1073,6 +1132,7 @@ impl Encoder26aaModCnnTransformerAttnSum {
 /// Encode AAs (26 AA letters), modifications and Charge state using CNN + Transformer + AttentionSum.
 #[derive(Debug, Clone)]
 pub struct Encoder26aaModChargeCnnTransformerAttnSum {
+    aa_embedding_size: usize,
     mod_nn: ModEmbeddingFixFirstK,
     input_cnn: SeqCNN,
     proj_cnn_to_transformer: candle_nn::Linear,
@@ -1083,6 +1143,8 @@ pub struct Encoder26aaModChargeCnnTransformerAttnSum {
 impl Encoder26aaModChargeCnnTransformerAttnSum {
     pub fn from_varstore(
         varstore: &nn::VarBuilder,
+        mod_feature_size: usize,
+        aa_embedding_size: usize,
         mod_hidden_dim: usize,
         hidden_dim: usize,
         ff_dim: usize,
@@ -1097,11 +1159,12 @@ impl Encoder26aaModChargeCnnTransformerAttnSum {
         names_attn_sum: Vec<&str>,
         device: &Device,
     ) -> Result<Self> {
-        let input_dim = AA_EMBEDDING_SIZE + mod_hidden_dim + 1;
+        let input_dim = aa_embedding_size + mod_hidden_dim + 1;
         Ok(Self {
+            aa_embedding_size,
             mod_nn: ModEmbeddingFixFirstK::from_varstore(
                 &varstore,
-                MOD_FEATURE_SIZE,
+                mod_feature_size,
                 mod_hidden_dim,
                 names_mod_nn[0],
             )?,
@@ -1136,6 +1199,8 @@ impl Encoder26aaModChargeCnnTransformerAttnSum {
     /// Construct a CNN+Transformer+Attention encoder from scratch (no pretrained weights).
     pub fn new(
         varbuilder: &nn::VarBuilder,
+        mod_feature_size: usize,
+        aa_embedding_size: usize,
         mod_hidden_dim: usize,
         hidden_dim: usize,
         ff_dim: usize,
@@ -1145,9 +1210,10 @@ impl Encoder26aaModChargeCnnTransformerAttnSum {
         dropout_prob: f32,
         device: &Device,
     ) -> Result<Self> {
-        let input_dim = AA_EMBEDDING_SIZE + mod_hidden_dim + 1;
+        let input_dim = aa_embedding_size + mod_hidden_dim + 1;
         Ok(Self {
-            mod_nn: ModEmbeddingFixFirstK::new(MOD_FEATURE_SIZE, mod_hidden_dim, &varbuilder.pp("mod_nn"))?,
+            aa_embedding_size,
+            mod_nn: ModEmbeddingFixFirstK::new(mod_feature_size, mod_hidden_dim, &varbuilder.pp("mod_nn"))?,
             input_cnn: SeqCNN::new(input_dim, &varbuilder.pp("input_cnn"))?,
             proj_cnn_to_transformer: candle_nn::linear_no_bias(input_dim*4, hidden_dim, varbuilder.pp("proj_cnn_to_transformer"))?,
             input_transformer: SeqTransformer::new(
@@ -1165,13 +1231,18 @@ impl Encoder26aaModChargeCnnTransformerAttnSum {
         })
     }
 
+    /// Enable or disable dropout in the transformer layers.
+    pub fn set_training(&mut self, training: bool) {
+        self.input_transformer.set_training(training);
+    }
+
     pub fn forward(&self, aa_indices: &Tensor, mod_x: &Tensor, charges: &Tensor) -> Result<Tensor> {
         let mod_x = self.mod_nn.forward(mod_x)?;
         let charges_repeated = charges.unsqueeze(1)?.repeat(&[1, mod_x.dim(1)?, 1])?;
 
         let additional_tensors: Vec<&Tensor> = vec![&mod_x, &charges_repeated];
 
-        let x = aa_one_hot(aa_indices, &additional_tensors)
+        let x = aa_one_hot(aa_indices, self.aa_embedding_size, &additional_tensors)
             .map_err(|e| candle_core::Error::Msg(e.to_string()))?;
 
         let (mean, min, max) = get_tensor_stats(&x)?;
@@ -1196,6 +1267,7 @@ mod tests {
     use std::path::PathBuf;
 
     use super::*;
+    use crate::utils::peptdeep_utils::ModelConstants;
 
     #[test]
     fn test_decoder_linear_new() -> Result<()> {
@@ -1320,9 +1392,10 @@ mod tests {
         let var_store =
             VarBuilder::from_pth(model_path, candle_core::DType::F32, &Device::Cpu).unwrap();
 
+        let constants = ModelConstants::default();
         let mod_nn = ModEmbeddingFixFirstK::from_varstore(
             &var_store,
-            MOD_FEATURE_SIZE,
+            constants.get_mod_feature_size(),
             8,
             "input_nn.mod_nn.nn.weight",
         )
@@ -1346,7 +1419,14 @@ mod tests {
         let var_store =
             VarBuilder::from_pth(model_path, candle_core::DType::F32, &Device::Cpu).unwrap();
 
-        let aa_emb = AAEmbedding::from_varstore(&var_store, 256, "input_nn.aa_emb.weight").unwrap();
+        let constants = ModelConstants::default();
+        let aa_emb = AAEmbedding::from_varstore(
+            &var_store,
+            constants.get_aa_embedding_size(),
+            256,
+            "input_nn.aa_emb.weight",
+        )
+        .unwrap();
 
         println!("aa_emb : {:?}", aa_emb);
     }
@@ -1388,8 +1468,11 @@ mod tests {
         let var_store =
             VarBuilder::from_pth(model_path, candle_core::DType::F32, &Device::Cpu).unwrap();
 
+        let constants = ModelConstants::default();
         let input_26aa_mod_pos_enc = Input26aaModPositionalEncoding::from_varstore(
             &var_store,
+            constants.get_mod_feature_size(),
+            constants.get_aa_embedding_size(),
             256 - 8,
             200,
             vec![
@@ -1418,8 +1501,10 @@ mod tests {
         let var_store =
             VarBuilder::from_pth(model_path, candle_core::DType::F32, &Device::Cpu).unwrap();
 
+        let constants = ModelConstants::default();
         let meta_emb = MetaEmbedding::from_varstore(
             &var_store,
+            constants.max_instrument_num,
             8,
             vec!["meta_nn.nn.weight", "meta_nn.nn.bias"],
         )
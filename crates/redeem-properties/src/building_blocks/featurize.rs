@@ -4,8 +4,6 @@ use candle_core::{DType, Device, Tensor};
 use rayon::prelude::*;
 use std::sync::atomic::{AtomicU32, Ordering};
 
-use crate::building_blocks::building_blocks::AA_EMBEDDING_SIZE;
-
 
 const VALID_AA: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
 
@@ -60,17 +58,17 @@ pub fn aa_indices_tensor_from_arc(seq: &Arc<[u8]>, device: &Device) -> Result<Te
     Ok(Tensor::from_slice(&indices, (1, indices.len()), device)?.to_dtype(DType::F32)?.unsqueeze(2)?)
 }
 
-/// One-hot encode amino acid indices and concatenate additional tensors.
-pub fn aa_one_hot(aa_indices: &Tensor, cat_others: &[&Tensor]) -> Result<Tensor> {
+/// One-hot encode amino acid indices into `num_classes` classes (the model's AA embedding size)
+/// and concatenate additional tensors.
+pub fn aa_one_hot(aa_indices: &Tensor, num_classes: usize, cat_others: &[&Tensor]) -> Result<Tensor> {
     let (batch_size, seq_len) = aa_indices.shape().dims2()?;
     log::trace!("[aa_one_hot] batch_size: {}, seq_len: {}", batch_size, seq_len);
-    let num_classes = AA_EMBEDDING_SIZE;
 
     let indices = aa_indices.to_vec2::<f32>()?;
 
     for (i, row) in indices.iter().enumerate() {
         for (j, val) in row.iter().enumerate() {
-            if !val.is_finite() || *val < 0.0 || *val > (AA_EMBEDDING_SIZE as f32) {
+            if !val.is_finite() || *val < 0.0 || *val > (num_classes as f32) {
                 log::error!(
                     "[aa_one_hot] Invalid index at batch {}, position {}: {}",
                     i, j, val
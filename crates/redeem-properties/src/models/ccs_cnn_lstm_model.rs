@@ -8,7 +8,7 @@ use std::sync::Arc;
 use std::{fmt, vec};
 
 use crate::building_blocks::building_blocks::{
-    DecoderLinear, Encoder26aaModChargeCnnLstmAttnSum, MOD_FEATURE_SIZE,
+    DecoderLinear, Encoder26aaModChargeCnnLstmAttnSum,
 };
 use crate::{
     models::model_interface::{
@@ -18,8 +18,6 @@ use crate::{
 };
 
 // Constants
-const CHARGE_FACTOR: f64 = 0.1;
-const NCE_FACTOR: f64 = 0.01;
 
 // Main Model Struct
 #[derive(Clone)]
@@ -47,42 +45,14 @@ pub struct CCSCNNLSTMModel {
 unsafe impl Send for CCSCNNLSTMModel {}
 unsafe impl Sync for CCSCNNLSTMModel {}
 
-// Code Model Implementation
-impl ModelInterface for CCSCNNLSTMModel {
-    fn property_type(&self) -> PropertyType {
-        PropertyType::CCS
-    }
-
-    fn model_arch(&self) -> &'static str {
-        "ccs_cnn_lstm"
-    }
-
-    fn new_untrained(_device: Device) -> Result<Self> {
-        unimplemented!("Untrained model creation is not implemented for this architecture.");
-    }
-
-    /// Create a new CCSCNNLSTMModel instance model from the given model and constants files.
-    fn new<P: AsRef<Path>>(
-        model_path: P,
-        constants_path: Option<P>,
-        fixed_sequence_len: usize,
-        num_frag_types: usize,
-        num_modloss_types: usize,
-        mask_modloss: bool,
-        device: Device,
-    ) -> Result<Self> {
-        let tensor_data = load_tensors_from_model(model_path.as_ref(), &device)?;
-
-        let mut varmap = candle_nn::VarMap::new();
-        create_var_map(&mut varmap, tensor_data, &device)?;
-
+impl CCSCNNLSTMModel {
+    /// Build the encoder and decoder from the AlphaPeptDeep tensor names in the given varmap.
+    ///
+    /// Tensors missing from the varmap are created, so an empty varmap gives the model's full
+    /// tensor layout.
+    pub(crate) fn from_varmap(varmap: VarMap, constants: ModelConstants, device: Device) -> Result<Self> {
         let var_store = candle_nn::VarBuilder::from_varmap(&varmap, DType::F32, &device);
 
-        let constants = match constants_path {
-            Some(path) => parse_model_constants(path.as_ref().to_str().unwrap())?,
-            None => ModelConstants::default(),
-        };
-
         // Load the mod_to_feature mapping
         let mod_to_feature = load_mod_to_feature_arc(&constants)?;
 
@@ -90,6 +60,8 @@ impl ModelInterface for CCSCNNLSTMModel {
 
         let ccs_encoder = Encoder26aaModChargeCnnLstmAttnSum::from_varstore(
             &var_store,
+            constants.get_mod_feature_size(),
+            constants.get_aa_embedding_size(),
             8,
             128,
             2,
@@ -127,10 +99,10 @@ impl ModelInterface for CCSCNNLSTMModel {
             varmap,
             constants,
             mod_to_feature,
-            fixed_sequence_len,
-            num_frag_types,
-            num_modloss_types,
-            mask_modloss,
+            fixed_sequence_len: 0,
+            num_frag_types: 8,
+            num_modloss_types: 4,
+            mask_modloss: true,
             device,
             is_training: false,
             dropout,
@@ -138,23 +110,68 @@ impl ModelInterface for CCSCNNLSTMModel {
             ccs_decoder,
         })
     }
+}
+
+// Code Model Implementation
+impl ModelInterface for CCSCNNLSTMModel {
+    fn property_type(&self) -> PropertyType {
+        PropertyType::CCS
+    }
+
+    fn model_arch(&self) -> &'static str {
+        "ccs_cnn_lstm"
+    }
+
+    fn new_untrained(_device: Device) -> Result<Self> {
+        unimplemented!("Untrained model creation is not implemented for this architecture.");
+    }
+
+    /// Create a new CCSCNNLSTMModel instance model from the given model and constants files.
+    fn new<P: AsRef<Path>>(
+        model_path: P,
+        constants_path: Option<P>,
+        fixed_sequence_len: usize,
+        num_frag_types: usize,
+        num_modloss_types: usize,
+        mask_modloss: bool,
+        device: Device,
+    ) -> Result<Self> {
+        let tensor_data = load_tensors_from_model(model_path.as_ref(), &device)?;
+
+        let mut varmap = candle_nn::VarMap::new();
+        create_var_map(&mut varmap, tensor_data, &device)?;
+
+        let constants = match constants_path {
+            Some(path) => parse_model_constants(path.as_ref().to_str().unwrap())?,
+            None => ModelConstants::default(),
+        };
+
+        Ok(CCSCNNLSTMModel {
+            fixed_sequence_len,
+            num_frag_types,
+            num_modloss_types,
+            mask_modloss,
+            ..Self::from_varmap(varmap, constants, device)?
+        })
+    }
 
     fn forward(&self, xs: &Tensor) -> Result<Tensor, candle_core::Error> {
         let (_batch_size, _seq_len, _) = xs.shape().dims3()?;
 
         // Separate input into aa_indices, mod_x, charge
+        let mod_feature_size = self.constants.get_mod_feature_size();
         let start_mod_x = 1;
-        let start_charge = start_mod_x + MOD_FEATURE_SIZE;
+        let start_charge = start_mod_x + mod_feature_size;
 
         let aa_indices_out = xs.i((.., .., 0))?;
-        let mod_x_out = xs.i((.., .., start_mod_x..start_mod_x + MOD_FEATURE_SIZE))?;
+        let mod_x_out = xs.i((.., .., start_mod_x..start_mod_x + mod_feature_size))?;
         let charge_out = xs.i((.., 0..1, start_charge..start_charge + 1))?;
         let charge_out = charge_out.squeeze(2)?;
 
         let x = self
             .ccs_encoder
             .forward(&aa_indices_out, &mod_x_out, &charge_out)?;
-        let x = self.dropout.forward(&x, true)?;
+        let x = self.dropout.forward(&x, self.is_training)?;
         let x = Tensor::cat(&[x, charge_out], 1)?;
         let x = self.ccs_decoder.forward(&x)?;
 
@@ -190,6 +207,10 @@ impl ModelInterface for CCSCNNLSTMModel {
         self.constants.mod_elements.len()
     }
 
+    fn get_model_constants(&self) -> &ModelConstants {
+        &self.constants
+    }
+
     fn get_mod_to_feature(&self) -> &HashMap<Arc<[u8]>, Vec<f32>> {
         &self.mod_to_feature
     }
@@ -6,7 +6,7 @@ use std::path::Path;
 use std::sync::Arc;
 
 use crate::building_blocks::building_blocks::{
-    DecoderLinear, Encoder26aaModChargeCnnTransformerAttnSum, MOD_FEATURE_SIZE,
+    DecoderLinear, Encoder26aaModChargeCnnTransformerAttnSum,
 };
 use crate::models::model_interface::{ModelInterface, PropertyType, load_tensors_from_model, create_var_map};
 use crate::utils::peptdeep_utils::{
@@ -16,8 +16,6 @@ use crate::utils::peptdeep_utils::{
 use crate::utils::utils::get_tensor_stats;
 
 // Constants
-const CHARGE_FACTOR: f64 = 0.1;
-const NCE_FACTOR: f64 = 0.01;
 
 // Main Model Struct
 
@@ -53,10 +51,13 @@ impl ModelInterface for CCSCNNTFModel {
     fn new_untrained(device: Device) -> Result<Self> {
         let mut varmap = VarMap::new();
         let varbuilder = VarBuilder::from_varmap(&varmap, DType::F32, &device);
+        let constants = ModelConstants::default();
 
         log::trace!("[CCSCNNTFModel] Initializing ccs_encoder");
         let ccs_encoder = Encoder26aaModChargeCnnTransformerAttnSum::new(
             &varbuilder.pp("ccs_encoder"),
+            constants.get_mod_feature_size(),
+            constants.get_aa_embedding_size(),
             8,     // mod_hidden_dim
             128,   // hidden_dim
             256,   // ff_dim
@@ -69,7 +70,6 @@ impl ModelInterface for CCSCNNTFModel {
 
         log::trace!("[CCSCNNTFModel] Initializing ccs_decoder");
         let ccs_decoder = DecoderLinear::new(129, 1, &varbuilder.pp("ccs_decoder"))?;
-        let constants = ModelConstants::default();
         let mod_to_feature = load_mod_to_feature_arc(&constants)?;
 
         Ok(Self {
@@ -110,6 +110,8 @@ impl ModelInterface for CCSCNNTFModel {
 
         let ccs_encoder = Encoder26aaModChargeCnnTransformerAttnSum::from_varstore(
             &var_store,
+            constants.get_mod_feature_size(),
+            constants.get_aa_embedding_size(),
             8,      // mod_hidden_dim
             128,    // hidden_dim
             256,    // ff_dim
@@ -142,7 +144,7 @@ impl ModelInterface for CCSCNNTFModel {
             vec!["ccs_decoder.nn.0.bias", "ccs_decoder.nn.2.bias"]
         )?;
 
-        Ok(Self {
+        let mut model = Self {
             var_store,
             varmap,
             constants,
@@ -152,21 +154,24 @@ impl ModelInterface for CCSCNNTFModel {
             ccs_encoder,
             ccs_decoder,
             is_training: false,
-        })
+        };
+        model.set_evaluation_mode();
+        Ok(model)
     }
 
     fn forward(&self, xs: &Tensor) -> Result<Tensor, candle_core::Error> {
         let (_batch_size, _seq_len, _) = xs.shape().dims3()?;
 
         // Separate input into aa_indices, mod_x, charge
+        let mod_feature_size = self.constants.get_mod_feature_size();
         let start_mod_x = 1;
-        let start_charge = start_mod_x + MOD_FEATURE_SIZE;
+        let start_charge = start_mod_x + mod_feature_size;
 
         let aa_indices_out = xs.i((.., .., 0))?;
         let (mean, min, max) = get_tensor_stats(&aa_indices_out)?;
         log::debug!("[CCSCNNTFModel] aa_indices_out stats - min: {min}, max: {max}, mean: {mean}");
         
-        let mod_x_out = xs.i((.., .., start_mod_x..start_mod_x + MOD_FEATURE_SIZE))?;
+        let mod_x_out = xs.i((.., .., start_mod_x..start_mod_x + mod_feature_size))?;
         let charge_out = xs.i((.., 0..1, start_charge..start_charge + 1))?;
         let charge_out = charge_out.squeeze(2)?;         
         
@@ -188,12 +193,14 @@ impl ModelInterface for CCSCNNTFModel {
     fn set_evaluation_mode(&mut self) {
         // println!("Setting evaluation mode");
         self.is_training = false;
+        self.ccs_encoder.set_training(false);
     }
 
     /// Set model to training mode for training
     /// This enables dropout and other training-specific layers.
     fn set_training_mode(&mut self) {
         self.is_training = true;
+        self.ccs_encoder.set_training(true);
     }
 
     fn get_property_type(&self) -> String {
@@ -212,6 +219,10 @@ impl ModelInterface for CCSCNNTFModel {
         self.constants.mod_elements.len()
     }
 
+    fn get_model_constants(&self) -> &ModelConstants {
+        &self.constants
+    }
+
     fn get_mod_to_feature(&self) -> &HashMap<Arc<[u8]>, Vec<f32>> {
         &self.mod_to_feature
     }
@@ -247,7 +258,7 @@ impl ModelInterface for CCSCNNTFModel {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::models::model_interface::ModelInterface;
+    use crate::models::model_interface::{ModelInterface, PredictionResult};
     use crate::models::ccs_cnn_tf_model::CCSCNNTFModel;
     use candle_core::Device;
     use std::path::PathBuf;
@@ -271,4 +282,50 @@ mod tests {
         println!("{:?}", result);
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_predict_with_uncertainty() {
+        let mut model = CCSCNNTFModel::new_untrained(Device::Cpu).unwrap();
+        model.set_evaluation_mode();
+
+        let peptides: Vec<Arc<[u8]>> = vec![
+            Arc::from(b"AGHCEWQMKYR".to_vec().into_boxed_slice()),
+            Arc::from(b"VSSLQAEPLPR".to_vec().into_boxed_slice()),
+        ];
+        let mods: Vec<Arc<[u8]>> = vec![
+            Arc::from(b"Carbamidomethyl@C;Oxidation@M".to_vec().into_boxed_slice()),
+            Arc::from(b"".to_vec().into_boxed_slice()),
+        ];
+        let mod_sites: Vec<Arc<[u8]>> = vec![
+            Arc::from(b"4;8".to_vec().into_boxed_slice()),
+            Arc::from(b"".to_vec().into_boxed_slice()),
+        ];
+        let charges = Some(vec![2, 3]);
+
+        // Evaluation mode disables all dropout, so predictions are deterministic
+        let first = model
+            .predict(&peptides, &mods, &mod_sites, charges.clone(), None, None)
+            .unwrap();
+        let second = model
+            .predict(&peptides, &mods, &mod_sites, charges.clone(), None, None)
+            .unwrap();
+        match (first, second) {
+            (PredictionResult::CCSResult(a), PredictionResult::CCSResult(b)) => assert_eq!(a, b),
+            _ => panic!("Unexpected prediction result type."),
+        }
+
+        let uncertainty = model
+            .predict_with_uncertainty(&peptides, &mods, &mod_sites, charges, None, None, 16)
+            .unwrap();
+        assert_eq!(uncertainty.n_samples, 16);
+        match (uncertainty.mean, uncertainty.std) {
+            (PredictionResult::CCSResult(mean), PredictionResult::CCSResult(std)) => {
+                assert_eq!(mean.len(), 2);
+                assert_eq!(std.len(), 2);
+                assert!(std.iter().all(|s| s.is_finite() && *s >= 0.0));
+                assert!(std.iter().any(|s| *s > 0.0));
+            }
+            _ => panic!("Unexpected prediction result type."),
+        }
+    }
 }
\ No newline at end of file
@@ -1,11 +1,15 @@
 use crate::models::ccs_cnn_lstm_model::CCSCNNLSTMModel;
 use crate::models::ccs_cnn_tf_model::CCSCNNTFModel;
-use crate::models::model_interface::{ModelInterface, PredictionResult};
-use crate::utils::data_handling::PeptideData;
-use crate::utils::peptdeep_utils::ModificationMap;
+use crate::models::ensemble::{EnsembleModel, ENSEMBLE_ARCH};
+use crate::models::model_interface::{
+    ModelInterface, PredictionResult, PredictionUncertainty, PropertyType,
+};
+use crate::utils::data_handling::{PeptideData, TargetNormalization};
+use crate::utils::peptdeep_utils::{IonMobilityParams, ModificationMap};
 use crate::utils::stats::TrainingStepMetrics;
 use anyhow::{anyhow, Result};
 use candle_core::Device;
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::path::Path;
 use std::sync::Arc;
@@ -17,17 +21,19 @@ pub enum CCSModelArch {
 }
 
 // Constants for different types of CCS models
-pub const CCSMODEL_ARCHS: &[&str] = &["ccs_cnn_lstm", "ccs_cnn_tf"];
+pub const CCSMODEL_ARCHS: &[&str] = &["ccs_cnn_lstm", "ccs_cnn_tf", ENSEMBLE_ARCH];
 
 // A wrapper struct for CCS models
 pub struct CCSModelWrapper {
     model: Box<dyn ModelInterface + Send + Sync>,
+    ion_mobility_params: IonMobilityParams,
 }
 
 impl Clone for CCSModelWrapper {
     fn clone(&self) -> Self {
         CCSModelWrapper {
             model: self.model.clone(),
+            ion_mobility_params: self.ion_mobility_params,
         }
     }
 }
@@ -58,10 +64,32 @@ impl CCSModelWrapper {
                 true,
                 device,
             )?),
+            // `model_path` is the ensemble's bundle manifest
+            ENSEMBLE_ARCH => {
+                let ensemble =
+                    EnsembleModel::new(model_path, Some(constants_path), 0, 8, 4, true, device)?;
+                if !matches!(ensemble.property_type(), PropertyType::CCS) {
+                    return Err(anyhow!("Ensemble does not predict CCS"));
+                }
+                Box::new(ensemble)
+            }
             _ => return Err(anyhow!("Unsupported CCS model architecture: {}", arch)),
         };
 
-        Ok(Self { model })
+        Ok(Self {
+            model,
+            ion_mobility_params: IonMobilityParams::default(),
+        })
+    }
+
+    /// Use `params` to convert between CCS and ion mobility (1/K0).
+    pub fn with_ion_mobility_params(mut self, params: IonMobilityParams) -> Self {
+        self.ion_mobility_params = params;
+        self
+    }
+
+    pub fn ion_mobility_params(&self) -> &IonMobilityParams {
+        &self.ion_mobility_params
     }
 
     pub fn predict(
@@ -75,6 +103,73 @@ impl CCSModelWrapper {
             .predict(peptide_sequence, mods, mod_sites, Some(charge), None, None)
     }
 
+    /// Predict ion mobility (1/K0) values from the predicted CCS and the precursor m/z and charge.
+    pub fn predict_ion_mobility(
+        &self,
+        peptide_sequence: &[Arc<[u8]>],
+        mods: &[Arc<[u8]>],
+        mod_sites: &[Arc<[u8]>],
+        charge: Vec<i32>,
+        precursor_mz: &[f32],
+    ) -> Result<Vec<f32>> {
+        if precursor_mz.len() != charge.len() {
+            return Err(anyhow!(
+                "Expected {} precursor m/z values, got {}",
+                charge.len(),
+                precursor_mz.len()
+            ));
+        }
+        let ccs = match self.predict(peptide_sequence, mods, mod_sites, charge.clone())? {
+            PredictionResult::CCSResult(ccs) => ccs,
+            _ => return Err(anyhow!("CCS model did not return CCS predictions")),
+        };
+        Ok(ccs
+            .iter()
+            .zip(charge.iter().zip(precursor_mz))
+            .map(|(&ccs, (&charge, &mz))| {
+                self.ion_mobility_params
+                    .ccs_to_mobility(ccs as f64, charge as f64, mz as f64) as f32
+            })
+            .collect())
+    }
+
+    /// Predict CCS for `inference_data`, and ion mobility (1/K0) for peptides with a precursor m/z and charge.
+    pub fn inference(
+        &self,
+        inference_data: &Vec<PeptideData>,
+        batch_size: usize,
+        modifications: HashMap<(String, Option<char>), ModificationMap>,
+        target_norm: TargetNormalization,
+    ) -> Result<Vec<PeptideData>> {
+        let mut results =
+            self.model
+                .inference(inference_data, batch_size, modifications, target_norm)?;
+        for peptide in &mut results {
+            peptide.ion_mobility = peptide.ccs_to_ion_mobility(&self.ion_mobility_params);
+        }
+        Ok(results)
+    }
+
+    /// Predict CCS values with Monte-Carlo dropout, returning the per-peptide mean and standard deviation.
+    pub fn predict_with_uncertainty(
+        &self,
+        peptide_sequence: &[Arc<[u8]>],
+        mods: &[Arc<[u8]>],
+        mod_sites: &[Arc<[u8]>],
+        charge: Vec<i32>,
+        n_samples: usize,
+    ) -> Result<PredictionUncertainty> {
+        self.model.predict_with_uncertainty(
+            peptide_sequence,
+            mods,
+            mod_sites,
+            Some(charge),
+            None,
+            None,
+            n_samples,
+        )
+    }
+
     pub fn train(
         &mut self,
         training_data: &Vec<PeptideData>,
@@ -86,9 +181,11 @@ impl CCSModelWrapper {
         epochs: usize,
         early_stopping_patience: usize,
     ) -> Result<TrainingStepMetrics> {
+        let training_data = self.with_ccs_targets(training_data);
+        let val_data = val_data.map(|data| self.with_ccs_targets(data));
         self.model.train(
-            training_data,
-            val_data,
+            &training_data,
+            val_data.as_deref(),
             modifications,
             batch_size,
             val_batch_size,
@@ -109,8 +206,9 @@ impl CCSModelWrapper {
         learning_rate: f64,
         epochs: usize,
     ) -> Result<()> {
+        let training_data = self.with_ccs_targets(training_data);
         self.model.fine_tune(
-            training_data,
+            &training_data,
             modifications,
             batch_size,
             learning_rate,
@@ -118,6 +216,24 @@ impl CCSModelWrapper {
         )
     }
 
+    /// Peptides with an ion mobility but no CCS get the CCS converted from their ion mobility as training target.
+    fn with_ccs_targets<'a>(&self, data: &'a Vec<PeptideData>) -> Cow<'a, Vec<PeptideData>> {
+        if data.iter().all(|peptide| peptide.ccs.is_some() || peptide.ion_mobility.is_none()) {
+            return Cow::Borrowed(data);
+        }
+        Cow::Owned(
+            data.iter()
+                .map(|peptide| {
+                    let mut peptide = peptide.clone();
+                    if peptide.ccs.is_none() {
+                        peptide.ccs = peptide.ion_mobility_to_ccs(&self.ion_mobility_params);
+                    }
+                    peptide
+                })
+                .collect(),
+        )
+    }
+
     pub fn set_evaluation_mode(&mut self) {
         self.model.set_evaluation_mode()
     }
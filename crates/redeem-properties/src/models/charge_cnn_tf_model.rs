@@ -0,0 +1,250 @@
+use anyhow::Result;
+use candle_core::{DType, Device, IndexOp, Tensor};
+use candle_nn::{Dropout, Module, VarBuilder, VarMap};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::building_blocks::building_blocks::{
+    DecoderLinear, Encoder26aaModCnnTransformerAttnSum,
+};
+use crate::models::model_interface::{
+    create_var_map, load_tensors_from_model, ModelInterface, PropertyType, NUM_CHARGE_STATES,
+};
+use crate::utils::peptdeep_utils::{
+    load_mod_to_feature_arc, parse_model_constants, ModelConstants,
+};
+use crate::utils::utils::get_tensor_stats;
+
+// Main Model Struct
+
+#[derive(Clone)]
+/// Represents a CNN-Transformer precursor charge state distribution model.
+///
+/// The model predicts the probability of observing a peptide at each charge state from 1 to 6,
+/// using a softmax head on top of the CNN-Transformer sequence encoder.
+pub struct ChargeCNNTFModel {
+    varmap: VarMap,
+    constants: ModelConstants,
+    device: Device,
+    mod_to_feature: HashMap<Arc<[u8]>, Vec<f32>>,
+    dropout: Dropout,
+    charge_encoder: Encoder26aaModCnnTransformerAttnSum,
+    charge_decoder: DecoderLinear,
+    is_training: bool,
+}
+
+// Automatically implement Send and Sync if all fields are Send and Sync
+unsafe impl Send for ChargeCNNTFModel {}
+unsafe impl Sync for ChargeCNNTFModel {}
+
+impl ChargeCNNTFModel {
+    /// Build the encoder and decoder on top of the given varmap.
+    ///
+    /// Variables already present in the varmap (e.g. loaded from a checkpoint) are reused,
+    /// missing ones are freshly initialized.
+    fn from_varmap(varmap: VarMap, constants: ModelConstants, device: Device) -> Result<Self> {
+        let var_store = VarBuilder::from_varmap(&varmap, DType::F32, &device);
+
+        log::trace!("[ChargeCNNTFModel] Initializing charge_encoder");
+        let charge_encoder = Encoder26aaModCnnTransformerAttnSum::new(
+            &var_store.pp("charge_encoder"),
+            constants.get_mod_feature_size(),
+            constants.get_aa_embedding_size(),
+            8,   // mod_hidden_dim
+            128, // hidden_dim
+            256, // ff_dim
+            4,   // num_heads
+            2,   // num_layers
+            100, // max_len
+            0.1, // dropout_prob
+            &device,
+        )?;
+
+        log::trace!("[ChargeCNNTFModel] Initializing charge_decoder");
+        let charge_decoder =
+            DecoderLinear::new(128, NUM_CHARGE_STATES, &var_store.pp("charge_decoder"))?;
+
+        let mod_to_feature = load_mod_to_feature_arc(&constants)?;
+
+        Ok(Self {
+            varmap,
+            constants,
+            device,
+            mod_to_feature,
+            dropout: Dropout::new(0.1),
+            charge_encoder,
+            charge_decoder,
+            is_training: true,
+        })
+    }
+}
+
+// Core Model Implementation
+
+impl ModelInterface for ChargeCNNTFModel {
+    fn property_type(&self) -> PropertyType {
+        PropertyType::Charge
+    }
+
+    fn model_arch(&self) -> &'static str {
+        "charge_cnn_tf"
+    }
+
+    /// Create a new ChargeCNNTFModel to train
+    fn new_untrained(device: Device) -> Result<Self> {
+        Self::from_varmap(VarMap::new(), ModelConstants::default(), device)
+    }
+
+    /// Create a new ChargeCNNTFModel from the given model and constants files.
+    fn new<P: AsRef<Path>>(
+        model_path: P,
+        constants_path: Option<P>,
+        _fixed_sequence_len: usize,
+        _num_frag_types: usize,
+        _num_modloss_types: usize,
+        _mask_modloss: bool,
+        device: Device,
+    ) -> Result<Self> {
+        let tensor_data = load_tensors_from_model(model_path.as_ref(), &device)?;
+
+        let mut varmap = VarMap::new();
+        create_var_map(&mut varmap, tensor_data, &device)?;
+
+        let constants = match constants_path {
+            Some(path) => parse_model_constants(path.as_ref().to_str().unwrap())?,
+            None => ModelConstants::default(),
+        };
+
+        let mut model = Self::from_varmap(varmap, constants, device)?;
+        model.set_evaluation_mode();
+        Ok(model)
+    }
+
+    fn forward(&self, xs: &Tensor) -> Result<Tensor, candle_core::Error> {
+        let aa_indices_out = xs.i((.., .., 0))?;
+        let (mean, min, max) = get_tensor_stats(&aa_indices_out)?;
+        log::debug!("[ChargeCNNTFModel] aa_indices_out stats - min: {min}, max: {max}, mean: {mean}");
+        let mod_x_out = xs.i((.., .., 1..1 + self.constants.get_mod_feature_size()))?;
+
+        let x = self
+            .charge_encoder
+            .forward(&aa_indices_out, &mod_x_out)?;
+
+        let x = self.dropout.forward(&x, self.is_training)?;
+
+        let x = self.charge_decoder.forward(&x)?;
+
+        candle_nn::ops::softmax(&x, 1)
+    }
+
+    /// Set model to evaluation mode for inference
+    /// This disables dropout and other training-specific layers.
+    fn set_evaluation_mode(&mut self) {
+        self.is_training = false;
+        self.charge_encoder.set_training(false);
+    }
+
+    /// Set model to training mode for training
+    /// This enables dropout and other training-specific layers.
+    fn set_training_mode(&mut self) {
+        self.is_training = true;
+        self.charge_encoder.set_training(true);
+    }
+
+    fn get_property_type(&self) -> String {
+        self.property_type().clone().as_str().to_string()
+    }
+
+    fn get_model_arch(&self) -> String {
+        self.model_arch().to_string()
+    }
+
+    fn get_device(&self) -> &Device {
+        &self.device
+    }
+
+    fn get_mod_element_count(&self) -> usize {
+        self.constants.mod_elements.len()
+    }
+
+    fn get_model_constants(&self) -> &ModelConstants {
+        &self.constants
+    }
+
+    fn get_mod_to_feature(&self) -> &HashMap<Arc<[u8]>, Vec<f32>> {
+        &self.mod_to_feature
+    }
+
+    fn get_min_pred_intensity(&self) -> f32 {
+        unimplemented!(
+            "Method not implemented for architecture: {}",
+            self.model_arch()
+        )
+    }
+
+    fn get_mut_varmap(&mut self) -> &mut VarMap {
+        &mut self.varmap
+    }
+
+    /// Print a summary of the model's constants.
+    fn print_summary(&self) {
+        println!("ChargeModel Summary:");
+        println!(
+            "AA Embedding Size: {:?}",
+            self.constants.aa_embedding_size
+        );
+        println!("Instruments: {:?}", self.constants.instruments);
+        println!("Max Instrument Num: {}", self.constants.max_instrument_num);
+        println!("Mod Elements: {:?}", self.constants.mod_elements);
+    }
+
+    /// Print the name and shape of each of the model's weights.
+    fn print_weights(&self) {
+        println!("ChargeModel Weights:");
+        let data = self.varmap.data().lock().unwrap();
+        let mut names: Vec<&String> = data.keys().collect();
+        names.sort();
+        for name in names {
+            println!("{}: {:?}", name, data[name].shape());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::model_interface::PredictionResult;
+
+    #[test]
+    fn test_untrained_prediction_is_distribution() {
+        let mut model = ChargeCNNTFModel::new_untrained(Device::Cpu).unwrap();
+        model.set_evaluation_mode();
+
+        let peptides: Vec<Arc<[u8]>> = vec![
+            Arc::from(b"AGHCEWQMKYR".to_vec().into_boxed_slice()),
+            Arc::from(b"VSSLQAEPLPR".to_vec().into_boxed_slice()),
+        ];
+        let mods: Vec<Arc<[u8]>> = vec![
+            Arc::from(b"Carbamidomethyl@C;Oxidation@M".to_vec().into_boxed_slice()),
+            Arc::from(b"".to_vec().into_boxed_slice()),
+        ];
+        let mod_sites: Vec<Arc<[u8]>> = vec![
+            Arc::from(b"4;8".to_vec().into_boxed_slice()),
+            Arc::from(b"".to_vec().into_boxed_slice()),
+        ];
+
+        match model.predict(&peptides, &mods, &mod_sites, None, None, None) {
+            Ok(PredictionResult::ChargeResult(distributions)) => {
+                assert_eq!(distributions.len(), 2);
+                for distribution in distributions {
+                    assert_eq!(distribution.len(), NUM_CHARGE_STATES);
+                    let total: f32 = distribution.iter().sum();
+                    assert!((total - 1.0).abs() < 1e-4);
+                }
+            }
+            Ok(_) => panic!("Unexpected prediction result type."),
+            Err(e) => panic!("Error during prediction: {:?}", e),
+        }
+    }
+}
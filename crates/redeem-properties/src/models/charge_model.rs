@@ -0,0 +1,219 @@
+// charge_model.rs
+
+use crate::models::charge_cnn_lstm_model::ChargeCNNLSTMModel;
+use crate::models::charge_cnn_tf_model::ChargeCNNTFModel;
+use crate::models::model_interface::{ModelInterface, PredictionResult};
+use crate::utils::data_handling::{PeptideData, TargetNormalization};
+use crate::utils::peptdeep_utils::ModificationMap;
+use crate::utils::stats::TrainingStepMetrics;
+use anyhow::{anyhow, Result};
+use candle_core::Device;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+// Enum for different types of charge state distribution models
+pub enum ChargeModelArch {
+    ChargeCNNLSTM,
+    ChargeCNNTF,
+}
+
+// Constants for different types of charge state distribution models
+pub const CHARGEMODEL_ARCHS: &[&str] = &["charge_cnn_lstm", "charge_cnn_tf"];
+
+// A wrapper struct for precursor charge state distribution models
+pub struct ChargeModelWrapper {
+    model: Box<dyn ModelInterface + Send + Sync>,
+}
+
+impl Clone for ChargeModelWrapper {
+    fn clone(&self) -> Self {
+        ChargeModelWrapper {
+            model: self.model.clone(),
+        }
+    }
+}
+
+impl ChargeModelWrapper {
+    pub fn new<P: AsRef<Path>>(
+        model_path: P,
+        constants_path: Option<P>,
+        arch: &str,
+        device: Device,
+    ) -> Result<Self> {
+        let model: Box<dyn ModelInterface> = match arch {
+            "charge_cnn_lstm" => Box::new(ChargeCNNLSTMModel::new(
+                model_path,
+                constants_path,
+                0,
+                8,
+                4,
+                true,
+                device,
+            )?),
+            "charge_cnn_tf" => Box::new(ChargeCNNTFModel::new(
+                model_path,
+                constants_path,
+                0,
+                8,
+                4,
+                true,
+                device,
+            )?),
+            _ => {
+                return Err(anyhow!(
+                    "Unsupported charge model architecture: {}",
+                    arch
+                ))
+            }
+        };
+
+        Ok(Self { model })
+    }
+
+    /// Create a new, untrained charge state distribution model to train from scratch.
+    pub fn new_untrained(arch: &str, device: Device) -> Result<Self> {
+        let model: Box<dyn ModelInterface> = match arch {
+            "charge_cnn_lstm" => Box::new(ChargeCNNLSTMModel::new_untrained(device)?),
+            "charge_cnn_tf" => Box::new(ChargeCNNTFModel::new_untrained(device)?),
+            _ => {
+                return Err(anyhow!(
+                    "Unsupported charge model architecture: {}",
+                    arch
+                ))
+            }
+        };
+
+        Ok(Self { model })
+    }
+
+    /// Predict the probability of each charge state from 1 to 6 for each peptide.
+    pub fn predict(
+        &self,
+        peptide_sequence: &[Arc<[u8]>],
+        mods: &[Arc<[u8]>],
+        mod_sites: &[Arc<[u8]>],
+    ) -> Result<PredictionResult> {
+        self.model
+            .predict(peptide_sequence, mods, mod_sites, None, None, None)
+    }
+
+    /// Predict which charge states to include for each peptide, e.g. for DIA library generation.
+    ///
+    /// # Arguments
+    /// * `min_probability` - Minimum predicted probability for a charge state to be included.
+    ///
+    /// # Returns
+    /// The charge states with a predicted probability of at least `min_probability`, per peptide.
+    pub fn predict_charge_states(
+        &self,
+        peptide_sequence: &[Arc<[u8]>],
+        mods: &[Arc<[u8]>],
+        mod_sites: &[Arc<[u8]>],
+        min_probability: f32,
+    ) -> Result<Vec<Vec<i32>>> {
+        match self.predict(peptide_sequence, mods, mod_sites)? {
+            PredictionResult::ChargeResult(distributions) => Ok(distributions
+                .iter()
+                .map(|distribution| {
+                    distribution
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, p)| **p >= min_probability)
+                        .map(|(i, _)| i as i32 + 1)
+                        .collect()
+                })
+                .collect()),
+            _ => Err(anyhow!("Unexpected prediction result type for charge model")),
+        }
+    }
+
+    /// Train the model on peptides with an observed charge state distribution
+    /// (`charge_distribution`, the fraction of observations at charges 1 to 6).
+    pub fn train(
+        &mut self,
+        training_data: &Vec<PeptideData>,
+        val_data: Option<&Vec<PeptideData>>,
+        modifications: HashMap<(String, Option<char>), ModificationMap>,
+        batch_size: usize,
+        val_batch_size: usize,
+        learning_rate: f64,
+        epochs: usize,
+        early_stopping_patience: usize,
+    ) -> Result<TrainingStepMetrics> {
+        self.model.train(
+            training_data,
+            val_data,
+            modifications,
+            batch_size,
+            val_batch_size,
+            learning_rate,
+            epochs,
+            early_stopping_patience,
+            "training",
+            true,
+            true,
+        )
+    }
+
+    pub fn fine_tune(
+        &mut self,
+        training_data: &Vec<PeptideData>,
+        modifications: HashMap<(String, Option<char>), ModificationMap>,
+        batch_size: usize,
+        learning_rate: f64,
+        epochs: usize,
+    ) -> Result<()> {
+        self.model.fine_tune(
+            training_data,
+            modifications,
+            batch_size,
+            learning_rate,
+            epochs,
+        )
+    }
+
+    pub fn inference(
+        &mut self,
+        inference_data: &Vec<PeptideData>,
+        batch_size: usize,
+        modifications: HashMap<(String, Option<char>), ModificationMap>,
+    ) -> Result<Vec<PeptideData>> {
+        self.model.inference(
+            inference_data,
+            batch_size,
+            modifications,
+            TargetNormalization::None,
+        )
+    }
+
+    pub fn set_evaluation_mode(&mut self) {
+        self.model.set_evaluation_mode()
+    }
+
+    pub fn set_training_mode(&mut self) {
+        self.model.set_training_mode()
+    }
+
+    pub fn print_summary(&self) {
+        self.model.print_summary()
+    }
+
+    pub fn print_weights(&self) {
+        self.model.print_weights()
+    }
+
+    pub fn save(&mut self, path: &str) -> Result<()> {
+        self.model.save(path)
+    }
+}
+
+// Public API Function to load a new charge state distribution model
+pub fn load_charge_model<P: AsRef<Path>>(
+    model_path: P,
+    constants_path: Option<P>,
+    arch: &str,
+    device: Device,
+) -> Result<ChargeModelWrapper> {
+    ChargeModelWrapper::new(model_path, constants_path, arch, device)
+}
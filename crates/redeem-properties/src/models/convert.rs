@@ -0,0 +1,336 @@
+// convert.rs
+
+use crate::models::ccs_cnn_lstm_model::CCSCNNLSTMModel;
+use crate::models::ccs_cnn_tf_model::CCSCNNTFModel;
+use crate::models::charge_cnn_lstm_model::ChargeCNNLSTMModel;
+use crate::models::charge_cnn_tf_model::ChargeCNNTFModel;
+use crate::models::detectability_cnn_lstm_model::DetectabilityCNNLSTMModel;
+use crate::models::detectability_cnn_tf_model::DetectabilityCNNTFModel;
+use crate::models::model_interface::{load_tensors_from_model, ModelInterface};
+use crate::models::ms2_bert_model::MS2BertModel;
+use crate::models::rt_cnn_lstm_model::RTCNNLSTMModel;
+use crate::models::rt_cnn_transformer_model::RTCNNTFModel;
+use crate::utils::fragment_types::default_fragment_types;
+use crate::utils::peptdeep_utils::ModelConstants;
+use anyhow::{anyhow, Context, Result};
+use candle_core::{DType, Device, Tensor};
+use candle_nn::VarMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::Path;
+
+// Prefixes added by PyTorch wrappers (DataParallel, torch.compile, Lightning modules)
+const WRAPPER_PREFIXES: &[&str] = &["module.", "_orig_mod.", "model."];
+
+/// Prefix rewrite rules mapping external checkpoint tensor names onto redeem's tensor names.
+///
+/// Rules are applied in order, each replacing a matching name prefix.
+#[derive(Debug, Clone, Default)]
+pub struct TensorNameMapping {
+    rules: Vec<(String, String)>,
+}
+
+impl TensorNameMapping {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Built-in rules for an architecture: strip PyTorch wrapper prefixes and map bare
+    /// `encoder.`/`decoder.` names onto the property specific names, e.g. `rt_encoder.`.
+    pub fn for_arch(arch: &str) -> Self {
+        let mut mapping = Self::new();
+        for prefix in WRAPPER_PREFIXES {
+            mapping = mapping.with_rule(*prefix, "");
+        }
+
+        match arch.split('_').next() {
+            Some(property @ ("rt" | "ccs" | "detectability" | "charge")) => mapping
+                .with_rule("encoder.", format!("{}_encoder.", property))
+                .with_rule("decoder.", format!("{}_decoder.", property)),
+            _ => mapping,
+        }
+    }
+
+    /// Add a rule replacing the name prefix `from` with `to`.
+    pub fn with_rule(mut self, from: impl Into<String>, to: impl Into<String>) -> Self {
+        self.rules.push((from.into(), to.into()));
+        self
+    }
+
+    /// Map an external tensor name onto redeem's tensor name.
+    pub fn map_name(&self, name: &str) -> String {
+        let mut name = name.to_string();
+        for (from, to) in &self.rules {
+            if let Some(rest) = name.strip_prefix(from.as_str()) {
+                name = format!("{}{}", to, rest);
+            }
+        }
+        name
+    }
+}
+
+/// A checkpoint tensor whose shape doesn't match the model definition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShapeMismatch {
+    pub name: String,
+    pub expected: Vec<usize>,
+    pub found: Vec<usize>,
+}
+
+/// Outcome of converting a checkpoint onto an architecture's tensor names.
+#[derive(Debug, Clone, Default)]
+pub struct ConversionReport {
+    pub arch: String,
+    /// `(checkpoint name, redeem name)` of each converted tensor.
+    pub converted: Vec<(String, String)>,
+    /// Tensors of the model definition not found in the checkpoint.
+    pub missing: Vec<String>,
+    /// Checkpoint tensors that don't map onto the model definition.
+    pub unexpected: Vec<String>,
+    pub shape_mismatches: Vec<ShapeMismatch>,
+}
+
+impl ConversionReport {
+    /// Whether every tensor of the model definition was converted and nothing was left over.
+    pub fn is_complete(&self) -> bool {
+        self.missing.is_empty() && self.unexpected.is_empty() && self.shape_mismatches.is_empty()
+    }
+}
+
+impl fmt::Display for ConversionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Converted {} tensors for {} ({} missing, {} unexpected, {} shape mismatches)",
+            self.converted.len(),
+            self.arch,
+            self.missing.len(),
+            self.unexpected.len(),
+            self.shape_mismatches.len()
+        )?;
+        for name in &self.missing {
+            writeln!(f, "  missing: {}", name)?;
+        }
+        for name in &self.unexpected {
+            writeln!(f, "  unexpected: {}", name)?;
+        }
+        for mismatch in &self.shape_mismatches {
+            writeln!(
+                f,
+                "  shape mismatch: {} expected {:?}, found {:?}",
+                mismatch.name, mismatch.expected, mismatch.found
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Name and shape of each tensor of an architecture's model definition.
+pub fn expected_tensor_shapes(arch: &str, device: &Device) -> Result<BTreeMap<String, Vec<usize>>> {
+    let mut model: Box<dyn ModelInterface> = match arch {
+        "rt_cnn_lstm" => Box::new(RTCNNLSTMModel::from_varmap(
+            VarMap::new(),
+            ModelConstants::default(),
+            device.clone(),
+        )?),
+        "rt_cnn_tf" => Box::new(RTCNNTFModel::new_untrained(device.clone())?),
+        "ccs_cnn_lstm" => Box::new(CCSCNNLSTMModel::from_varmap(
+            VarMap::new(),
+            ModelConstants::default(),
+            device.clone(),
+        )?),
+        "ccs_cnn_tf" => Box::new(CCSCNNTFModel::new_untrained(device.clone())?),
+        "ms2_bert" => Box::new(MS2BertModel::from_varmap(
+            VarMap::new(),
+            ModelConstants::default(),
+            0,
+            default_fragment_types(),
+            false,
+            device.clone(),
+        )?),
+        "detectability_cnn_lstm" => Box::new(DetectabilityCNNLSTMModel::new_untrained(device.clone())?),
+        "detectability_cnn_tf" => Box::new(DetectabilityCNNTFModel::new_untrained(device.clone())?),
+        "charge_cnn_lstm" => Box::new(ChargeCNNLSTMModel::new_untrained(device.clone())?),
+        "charge_cnn_tf" => Box::new(ChargeCNNTFModel::new_untrained(device.clone())?),
+        _ => return Err(anyhow!("Unsupported model architecture: {}", arch)),
+    };
+
+    let data = model.get_mut_varmap().data().lock().unwrap();
+    Ok(data
+        .iter()
+        .map(|(name, var)| (name.clone(), var.shape().dims().to_vec()))
+        .collect())
+}
+
+/// Read all tensors of a checkpoint.
+///
+/// For PyTorch checkpoints storing the weights under a key of a dictionary (e.g. `state_dict`),
+/// `key` selects that entry.
+pub fn read_checkpoint<P: AsRef<Path>>(
+    path: P,
+    key: Option<&str>,
+    device: &Device,
+) -> Result<Vec<(String, Tensor)>> {
+    let path = path.as_ref();
+    match key {
+        Some(key) => candle_core::pickle::read_all_with_key(path, Some(key))
+            .with_context(|| format!("Failed to read '{}' from PyTorch checkpoint: {:?}", key, path)),
+        None => load_tensors_from_model(path, device),
+    }
+}
+
+/// Map checkpoint tensors onto an architecture's tensor names and validate their shapes.
+///
+/// Returns the tensors that could be converted, as `f32`, along with a report of missing,
+/// unexpected and mis-shaped tensors.
+pub fn convert_tensors(
+    tensors: Vec<(String, Tensor)>,
+    arch: &str,
+    mapping: &TensorNameMapping,
+    device: &Device,
+) -> Result<(HashMap<String, Tensor>, ConversionReport)> {
+    let expected = expected_tensor_shapes(arch, device)?;
+
+    let mut report = ConversionReport {
+        arch: arch.to_string(),
+        ..Default::default()
+    };
+    let mut converted: HashMap<String, Tensor> = HashMap::new();
+    let mut sources: HashMap<String, String> = HashMap::new();
+
+    for (name, tensor) in tensors {
+        let target = mapping.map_name(&name);
+        let expected_shape = match expected.get(&target) {
+            Some(shape) => shape,
+            None => {
+                report.unexpected.push(name);
+                continue;
+            }
+        };
+
+        if let Some(previous) = sources.get(&target) {
+            return Err(anyhow!(
+                "Checkpoint tensors {} and {} both map to {}",
+                previous,
+                name,
+                target
+            ));
+        }
+        sources.insert(target.clone(), name.clone());
+
+        let shape = tensor.dims().to_vec();
+        if &shape != expected_shape {
+            report.shape_mismatches.push(ShapeMismatch {
+                name: target,
+                expected: expected_shape.clone(),
+                found: shape,
+            });
+            continue;
+        }
+
+        let tensor = tensor.to_dtype(DType::F32)?.to_device(device)?;
+        converted.insert(target.clone(), tensor);
+        report.converted.push((name, target));
+    }
+
+    report.missing = expected
+        .keys()
+        .filter(|name| !sources.contains_key(*name))
+        .cloned()
+        .collect();
+    report.unexpected.sort();
+
+    Ok((converted, report))
+}
+
+/// Convert an external checkpoint into a safetensors file loadable by the given architecture.
+///
+/// Unexpected tensors are dropped. Missing tensors are an error unless `allow_missing` is set, in
+/// which case they are left out of the output; shape mismatches are always an error. Nothing is
+/// written if the conversion fails.
+pub fn convert_checkpoint<P: AsRef<Path>, Q: AsRef<Path>>(
+    input_path: P,
+    output_path: Q,
+    arch: &str,
+    mapping: &TensorNameMapping,
+    key: Option<&str>,
+    allow_missing: bool,
+) -> Result<ConversionReport> {
+    let device = Device::Cpu;
+    let tensors = read_checkpoint(&input_path, key, &device)?;
+    let (converted, report) = convert_tensors(tensors, arch, mapping, &device)?;
+
+    if !report.shape_mismatches.is_empty() || (!allow_missing && !report.missing.is_empty()) {
+        return Err(anyhow!(
+            "Failed to convert {:?}:\n{}",
+            input_path.as_ref(),
+            report
+        ));
+    }
+    if !report.unexpected.is_empty() {
+        log::warn!(
+            "Dropping {} unexpected tensors from {:?}",
+            report.unexpected.len(),
+            input_path.as_ref()
+        );
+    }
+
+    candle_core::safetensors::save(&converted, output_path.as_ref())
+        .with_context(|| format!("Failed to write SafeTensors to: {:?}", output_path.as_ref()))?;
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn external_checkpoint(arch: &str) -> Vec<(String, Tensor)> {
+        expected_tensor_shapes(arch, &Device::Cpu)
+            .unwrap()
+            .into_iter()
+            .map(|(name, shape)| {
+                let name = format!("module.{}", name.replacen("rt_encoder.", "encoder.", 1));
+                (name, Tensor::zeros(shape, DType::F64, &Device::Cpu).unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_map_name() {
+        let mapping = TensorNameMapping::for_arch("rt_cnn_tf");
+        assert_eq!(mapping.map_name("module.encoder.attn_sum.attn.0.weight"), "rt_encoder.attn_sum.attn.0.weight");
+        assert_eq!(mapping.map_name("rt_decoder.nn.0.bias"), "rt_decoder.nn.0.bias");
+
+        let mapping = TensorNameMapping::new().with_rule("net.head.", "rt_decoder.");
+        assert_eq!(mapping.map_name("net.head.nn.0.bias"), "rt_decoder.nn.0.bias");
+    }
+
+    #[test]
+    fn test_convert_tensors() {
+        let arch = "rt_cnn_tf";
+        let expected = expected_tensor_shapes(arch, &Device::Cpu).unwrap();
+        let mapping = TensorNameMapping::for_arch(arch);
+
+        let (converted, report) =
+            convert_tensors(external_checkpoint(arch), arch, &mapping, &Device::Cpu).unwrap();
+        assert!(report.is_complete(), "{}", report);
+        assert_eq!(converted.len(), expected.len());
+        assert!(converted.values().all(|t| t.dtype() == DType::F32));
+
+        // Drop one tensor, reshape another and add an extra one
+        let mut tensors = external_checkpoint(arch);
+        let (dropped, _) = tensors.pop().unwrap();
+        tensors[0].1 = Tensor::zeros((1, 2, 3), DType::F32, &Device::Cpu).unwrap();
+        let reshaped = mapping.map_name(&tensors[0].0);
+        tensors.push(("module.extra.weight".to_string(), Tensor::zeros(1, DType::F32, &Device::Cpu).unwrap()));
+
+        let (converted, report) = convert_tensors(tensors, arch, &mapping, &Device::Cpu).unwrap();
+        assert!(!report.is_complete());
+        assert_eq!(converted.len(), expected.len() - 2);
+        assert_eq!(report.missing, vec![mapping.map_name(&dropped)]);
+        assert_eq!(report.unexpected, vec!["module.extra.weight".to_string()]);
+        assert_eq!(report.shape_mismatches.len(), 1);
+        assert_eq!(report.shape_mismatches[0].name, reshaped);
+    }
+}
@@ -0,0 +1,189 @@
+// detectability_model.rs
+
+use crate::models::detectability_cnn_lstm_model::DetectabilityCNNLSTMModel;
+use crate::models::detectability_cnn_tf_model::DetectabilityCNNTFModel;
+use crate::models::model_interface::{ModelInterface, PredictionResult};
+use crate::utils::data_handling::{PeptideData, TargetNormalization};
+use crate::utils::peptdeep_utils::ModificationMap;
+use crate::utils::stats::TrainingStepMetrics;
+use anyhow::{anyhow, Result};
+use candle_core::Device;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+// Enum for different types of detectability models
+pub enum DetectabilityModelArch {
+    DetectabilityCNNLSTM,
+    DetectabilityCNNTF,
+}
+
+// Constants for different types of detectability models
+pub const DETECTABILITYMODEL_ARCHS: &[&str] = &["detectability_cnn_lstm", "detectability_cnn_tf"];
+
+// A wrapper struct for peptide detectability models
+pub struct DetectabilityModelWrapper {
+    model: Box<dyn ModelInterface + Send + Sync>,
+}
+
+impl Clone for DetectabilityModelWrapper {
+    fn clone(&self) -> Self {
+        DetectabilityModelWrapper {
+            model: self.model.clone(),
+        }
+    }
+}
+
+impl DetectabilityModelWrapper {
+    pub fn new<P: AsRef<Path>>(
+        model_path: P,
+        constants_path: Option<P>,
+        arch: &str,
+        device: Device,
+    ) -> Result<Self> {
+        let model: Box<dyn ModelInterface> = match arch {
+            "detectability_cnn_lstm" => Box::new(DetectabilityCNNLSTMModel::new(
+                model_path,
+                constants_path,
+                0,
+                8,
+                4,
+                true,
+                device,
+            )?),
+            "detectability_cnn_tf" => Box::new(DetectabilityCNNTFModel::new(
+                model_path,
+                constants_path,
+                0,
+                8,
+                4,
+                true,
+                device,
+            )?),
+            _ => {
+                return Err(anyhow!(
+                    "Unsupported detectability model architecture: {}",
+                    arch
+                ))
+            }
+        };
+
+        Ok(Self { model })
+    }
+
+    /// Create a new, untrained detectability model to train from scratch.
+    pub fn new_untrained(arch: &str, device: Device) -> Result<Self> {
+        let model: Box<dyn ModelInterface> = match arch {
+            "detectability_cnn_lstm" => Box::new(DetectabilityCNNLSTMModel::new_untrained(device)?),
+            "detectability_cnn_tf" => Box::new(DetectabilityCNNTFModel::new_untrained(device)?),
+            _ => {
+                return Err(anyhow!(
+                    "Unsupported detectability model architecture: {}",
+                    arch
+                ))
+            }
+        };
+
+        Ok(Self { model })
+    }
+
+    /// Predict the probability that each peptide is observed.
+    pub fn predict(
+        &self,
+        peptide_sequence: &[Arc<[u8]>],
+        mods: &[Arc<[u8]>],
+        mod_sites: &[Arc<[u8]>],
+    ) -> Result<PredictionResult> {
+        self.model
+            .predict(peptide_sequence, mods, mod_sites, None, None, None)
+    }
+
+    /// Train the model on peptides labelled as observed (`detectability = Some(1.0)`) or
+    /// unobserved (`detectability = Some(0.0)`). Peptides without a label are rejected.
+    pub fn train(
+        &mut self,
+        training_data: &Vec<PeptideData>,
+        val_data: Option<&Vec<PeptideData>>,
+        modifications: HashMap<(String, Option<char>), ModificationMap>,
+        batch_size: usize,
+        val_batch_size: usize,
+        learning_rate: f64,
+        epochs: usize,
+        early_stopping_patience: usize,
+    ) -> Result<TrainingStepMetrics> {
+        self.model.train(
+            training_data,
+            val_data,
+            modifications,
+            batch_size,
+            val_batch_size,
+            learning_rate,
+            epochs,
+            early_stopping_patience,
+            "training",
+            true,
+            true,
+        )
+    }
+
+    pub fn fine_tune(
+        &mut self,
+        training_data: &Vec<PeptideData>,
+        modifications: HashMap<(String, Option<char>), ModificationMap>,
+        batch_size: usize,
+        learning_rate: f64,
+        epochs: usize,
+    ) -> Result<()> {
+        self.model.fine_tune(
+            training_data,
+            modifications,
+            batch_size,
+            learning_rate,
+            epochs,
+        )
+    }
+
+    pub fn inference(
+        &mut self,
+        inference_data: &Vec<PeptideData>,
+        batch_size: usize,
+        modifications: HashMap<(String, Option<char>), ModificationMap>,
+    ) -> Result<Vec<PeptideData>> {
+        self.model.inference(
+            inference_data,
+            batch_size,
+            modifications,
+            TargetNormalization::None,
+        )
+    }
+
+    pub fn set_evaluation_mode(&mut self) {
+        self.model.set_evaluation_mode()
+    }
+
+    pub fn set_training_mode(&mut self) {
+        self.model.set_training_mode()
+    }
+
+    pub fn print_summary(&self) {
+        self.model.print_summary()
+    }
+
+    pub fn print_weights(&self) {
+        self.model.print_weights()
+    }
+
+    pub fn save(&mut self, path: &str) -> Result<()> {
+        self.model.save(path)
+    }
+}
+
+// Public API Function to load a new detectability model
+pub fn load_detectability_model<P: AsRef<Path>>(
+    model_path: P,
+    constants_path: Option<P>,
+    arch: &str,
+    device: Device,
+) -> Result<DetectabilityModelWrapper> {
+    DetectabilityModelWrapper::new(model_path, constants_path, arch, device)
+}
@@ -0,0 +1,614 @@
+// ensemble.rs
+
+use anyhow::{anyhow, Context, Result};
+use candle_core::{Device, Tensor, Var};
+use candle_nn::VarMap;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::models::ccs_cnn_lstm_model::CCSCNNLSTMModel;
+use crate::models::ccs_cnn_tf_model::CCSCNNTFModel;
+use crate::models::charge_cnn_lstm_model::ChargeCNNLSTMModel;
+use crate::models::charge_cnn_tf_model::ChargeCNNTFModel;
+use crate::models::detectability_cnn_lstm_model::DetectabilityCNNLSTMModel;
+use crate::models::detectability_cnn_tf_model::DetectabilityCNNTFModel;
+use crate::models::model_interface::{ModelInterface, PropertyType};
+use crate::models::ms2_bert_model::MS2BertModel;
+use crate::models::rt_cnn_lstm_model::RTCNNLSTMModel;
+use crate::models::rt_cnn_transformer_model::RTCNNTFModel;
+use crate::utils::data_handling::TargetNormalization;
+use crate::utils::fragment_types::FragmentType;
+use crate::utils::peptdeep_utils::ModelConstants;
+
+/// Architecture name reported by ensembles and accepted by the model wrappers.
+pub const ENSEMBLE_ARCH: &str = "ensemble";
+
+fn default_weight() -> f32 {
+    1.0
+}
+
+/// A single member of an ensemble bundle manifest.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnsembleMemberSpec {
+    /// Model architecture, e.g. `rt_cnn_lstm`.
+    pub arch: String,
+    /// Model file (.pth or .safetensors), relative to the manifest's directory.
+    pub model_path: PathBuf,
+    /// Model constants file, relative to the manifest's directory.
+    #[serde(default)]
+    pub constants_path: Option<PathBuf>,
+    /// Relative weight of the member in the ensemble average.
+    #[serde(default = "default_weight")]
+    pub weight: f32,
+    /// Target normalization the member was trained with. Its predictions are mapped back to the
+    /// original scale before averaging.
+    #[serde(default)]
+    pub normalization: Option<TargetNormalization>,
+}
+
+/// Bundle manifest describing the members of an [`EnsembleModel`].
+///
+/// Manifests are YAML files, e.g.
+///
+/// ```yaml
+/// members:
+///   - arch: rt_cnn_lstm
+///     model_path: rt_cnn_lstm.safetensors
+///     weight: 0.6
+///   - arch: rt_cnn_tf
+///     model_path: rt_cnn_tf.safetensors
+///     weight: 0.4
+///     normalization: !min_max [0.0, 120.0]
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnsembleManifest {
+    pub members: Vec<EnsembleMemberSpec>,
+}
+
+impl EnsembleManifest {
+    /// Read a bundle manifest from a YAML file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let f = File::open(path)
+            .with_context(|| format!("Failed to open ensemble manifest {:?}", path))?;
+        serde_yaml::from_reader(f)
+            .map_err(|e| anyhow!("Failed to parse ensemble manifest {:?}: {}", path, e))
+    }
+
+    /// Write the bundle manifest to a YAML file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let f = File::create(path)
+            .with_context(|| format!("Failed to create ensemble manifest {:?}", path))?;
+        serde_yaml::to_writer(f, self)
+            .map_err(|e| anyhow!("Failed to write ensemble manifest {:?}: {}", path, e))
+    }
+}
+
+/// A member model of an ensemble together with its weight and normalization.
+#[derive(Clone)]
+pub struct EnsembleMember {
+    pub model: Box<dyn ModelInterface + Send + Sync>,
+    pub weight: f32,
+    pub normalization: TargetNormalization,
+}
+
+impl EnsembleMember {
+    pub fn new(model: Box<dyn ModelInterface + Send + Sync>, weight: f32) -> Self {
+        Self {
+            model,
+            weight,
+            normalization: TargetNormalization::None,
+        }
+    }
+
+    pub fn with_normalization(mut self, normalization: TargetNormalization) -> Self {
+        self.normalization = normalization;
+        self
+    }
+
+    /// Map the member's raw output back to the target's original scale.
+    fn denormalize(&self, output: &Tensor) -> Result<Tensor, candle_core::Error> {
+        match self.normalization {
+            TargetNormalization::ZScore(mean, std) => output.affine(std as f64, mean as f64),
+            TargetNormalization::MinMax(min, max) => {
+                output.affine((max - min) as f64, min as f64)
+            }
+            TargetNormalization::None => Ok(output.clone()),
+        }
+    }
+}
+
+/// Load a single model of the given architecture.
+fn load_member_model(
+    arch: &str,
+    model_path: &Path,
+    constants_path: Option<&Path>,
+    fixed_sequence_len: usize,
+    num_frag_types: usize,
+    num_modloss_types: usize,
+    mask_modloss: bool,
+    device: Device,
+) -> Result<Box<dyn ModelInterface + Send + Sync>> {
+    macro_rules! load {
+        ($model:ty) => {
+            Box::new(<$model>::new(
+                model_path,
+                constants_path,
+                fixed_sequence_len,
+                num_frag_types,
+                num_modloss_types,
+                mask_modloss,
+                device,
+            )?)
+        };
+    }
+
+    let model: Box<dyn ModelInterface + Send + Sync> = match arch {
+        "rt_cnn_lstm" => load!(RTCNNLSTMModel),
+        "rt_cnn_tf" => load!(RTCNNTFModel),
+        "ccs_cnn_lstm" => load!(CCSCNNLSTMModel),
+        "ccs_cnn_tf" => load!(CCSCNNTFModel),
+        "ms2_bert" => load!(MS2BertModel),
+        "detectability_cnn_lstm" => load!(DetectabilityCNNLSTMModel),
+        "detectability_cnn_tf" => load!(DetectabilityCNNTFModel),
+        "charge_cnn_lstm" => load!(ChargeCNNLSTMModel),
+        "charge_cnn_tf" => load!(ChargeCNNTFModel),
+        _ => return Err(anyhow!("Unsupported ensemble member architecture: {}", arch)),
+    };
+    Ok(model)
+}
+
+/// Weighted average of several models predicting the same property.
+///
+/// All members must predict the same [`PropertyType`] and share their featurization constants, so
+/// a peptide is encoded once and fed to every member. Members' outputs are denormalized with
+/// their own [`TargetNormalization`] and averaged with their normalized weights, so the ensemble
+/// predicts values on the original target scale.
+#[derive(Clone)]
+pub struct EnsembleModel {
+    members: Vec<EnsembleMember>,
+    // Normalized member weights, summing to 1
+    weights: Vec<f64>,
+    property_type: PropertyType,
+    // All member variables, prefixed with `members.{i}.`
+    varmap: VarMap,
+}
+
+impl EnsembleModel {
+    /// Build an ensemble from already loaded members.
+    pub fn from_members(mut members: Vec<EnsembleMember>) -> Result<Self> {
+        let first = members
+            .first()
+            .ok_or_else(|| anyhow!("An ensemble requires at least one member"))?;
+        let property_type = first.model.property_type();
+        let constants = first.model.get_model_constants().clone();
+
+        for (i, member) in members.iter().enumerate() {
+            if member.model.property_type().as_str() != property_type.as_str() {
+                return Err(anyhow!(
+                    "Ensemble member {} ({}) predicts {}, expected {}",
+                    i,
+                    member.model.get_model_arch(),
+                    member.model.property_type().as_str(),
+                    property_type.as_str()
+                ));
+            }
+            let member_constants = member.model.get_model_constants();
+            if member_constants.mod_elements != constants.mod_elements
+                || member_constants.instruments != constants.instruments
+                || member_constants.get_aa_embedding_size() != constants.get_aa_embedding_size()
+            {
+                return Err(anyhow!(
+                    "Ensemble member {} ({}) uses different featurization constants than member 0",
+                    i,
+                    member.model.get_model_arch()
+                ));
+            }
+            if !member.weight.is_finite() || member.weight <= 0.0 {
+                return Err(anyhow!(
+                    "Ensemble member {} has invalid weight {}, weights must be positive",
+                    i,
+                    member.weight
+                ));
+            }
+        }
+
+        let total: f64 = members.iter().map(|m| m.weight as f64).sum();
+        let weights = members.iter().map(|m| m.weight as f64 / total).collect();
+
+        // Variables are shared with the members, so training the ensemble updates every member
+        let varmap = VarMap::new();
+        {
+            let mut data = varmap.data().lock().unwrap();
+            for (i, member) in members.iter_mut().enumerate() {
+                let member_vars: Vec<(String, Var)> = member
+                    .model
+                    .get_mut_varmap()
+                    .data()
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .map(|(name, var)| (format!("members.{}.{}", i, name), var.clone()))
+                    .collect();
+                data.extend(member_vars);
+            }
+        }
+
+        Ok(Self {
+            members,
+            weights,
+            property_type,
+            varmap,
+        })
+    }
+
+    /// Load an ensemble from a bundle manifest.
+    ///
+    /// Member paths are resolved relative to the manifest's directory. `default_constants_path` is
+    /// used for members that don't specify their own constants file.
+    pub fn from_manifest<P: AsRef<Path>>(
+        manifest_path: P,
+        default_constants_path: Option<&Path>,
+        fixed_sequence_len: usize,
+        num_frag_types: usize,
+        num_modloss_types: usize,
+        mask_modloss: bool,
+        device: Device,
+    ) -> Result<Self> {
+        let manifest_path = manifest_path.as_ref();
+        let manifest = EnsembleManifest::from_file(manifest_path)?;
+        let base_dir = manifest_path.parent().unwrap_or_else(|| Path::new(""));
+        let resolve = |path: &Path| -> PathBuf {
+            if path.is_relative() {
+                base_dir.join(path)
+            } else {
+                path.to_path_buf()
+            }
+        };
+
+        let members = manifest
+            .members
+            .iter()
+            .map(|spec| {
+                let constants_path = spec
+                    .constants_path
+                    .as_deref()
+                    .map(|p| resolve(p))
+                    .or_else(|| default_constants_path.map(Path::to_path_buf));
+                let model = load_member_model(
+                    &spec.arch,
+                    &resolve(&spec.model_path),
+                    constants_path.as_deref(),
+                    fixed_sequence_len,
+                    num_frag_types,
+                    num_modloss_types,
+                    mask_modloss,
+                    device.clone(),
+                )
+                .with_context(|| {
+                    format!("Failed to load ensemble member {:?}", spec.model_path)
+                })?;
+                Ok(EnsembleMember::new(model, spec.weight)
+                    .with_normalization(spec.normalization.unwrap_or(TargetNormalization::None)))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Self::from_members(members)
+    }
+
+    pub fn members(&self) -> &[EnsembleMember] {
+        &self.members
+    }
+
+    /// Normalized member weights, in member order.
+    pub fn weights(&self) -> &[f64] {
+        &self.weights
+    }
+
+    fn first(&self) -> &(dyn ModelInterface + Send + Sync) {
+        self.members[0].model.as_ref()
+    }
+}
+
+impl ModelInterface for EnsembleModel {
+    fn property_type(&self) -> PropertyType {
+        self.property_type.clone()
+    }
+
+    fn model_arch(&self) -> &'static str {
+        ENSEMBLE_ARCH
+    }
+
+    fn new_untrained(_device: Device) -> Result<Self> {
+        Err(anyhow!(
+            "Ensembles are built from trained members, use EnsembleModel::from_manifest or EnsembleModel::from_members"
+        ))
+    }
+
+    /// Load an ensemble from the bundle manifest at `model_path`.
+    ///
+    /// `constants_path` is used for members that don't specify their own constants file.
+    fn new<P: AsRef<Path>>(
+        model_path: P,
+        constants_path: Option<P>,
+        fixed_sequence_len: usize,
+        num_frag_types: usize,
+        num_modloss_types: usize,
+        mask_modloss: bool,
+        device: Device,
+    ) -> Result<Self> {
+        Self::from_manifest(
+            model_path,
+            constants_path.as_ref().map(|p| p.as_ref()),
+            fixed_sequence_len,
+            num_frag_types,
+            num_modloss_types,
+            mask_modloss,
+            device,
+        )
+    }
+
+    fn forward(&self, input: &Tensor) -> Result<Tensor, candle_core::Error> {
+        let mut ensemble_output: Option<Tensor> = None;
+        for (member, &weight) in self.members.iter().zip(self.weights.iter()) {
+            let output = member.denormalize(&member.model.forward(input)?)?;
+            let weighted = (output * weight)?;
+            ensemble_output = Some(match ensemble_output {
+                Some(acc) => (acc + weighted)?,
+                None => weighted,
+            });
+        }
+        // from_members guarantees at least one member
+        Ok(ensemble_output.expect("ensemble has no members"))
+    }
+
+    /// Members' outputs are denormalized with their own normalization in `forward`.
+    fn denormalizes_output(&self) -> bool {
+        true
+    }
+
+    fn set_evaluation_mode(&mut self) {
+        for member in self.members.iter_mut() {
+            member.model.set_evaluation_mode();
+        }
+    }
+
+    fn set_training_mode(&mut self) {
+        for member in self.members.iter_mut() {
+            member.model.set_training_mode();
+        }
+    }
+
+    fn get_property_type(&self) -> String {
+        self.property_type().as_str().to_string()
+    }
+
+    fn get_model_arch(&self) -> String {
+        self.model_arch().to_string()
+    }
+
+    fn get_device(&self) -> &Device {
+        self.first().get_device()
+    }
+
+    fn get_mod_element_count(&self) -> usize {
+        self.first().get_mod_element_count()
+    }
+
+    fn get_model_constants(&self) -> &ModelConstants {
+        self.first().get_model_constants()
+    }
+
+    fn get_mod_to_feature(&self) -> &HashMap<Arc<[u8]>, Vec<f32>> {
+        self.first().get_mod_to_feature()
+    }
+
+    fn get_min_pred_intensity(&self) -> f32 {
+        self.first().get_min_pred_intensity()
+    }
+
+    fn get_fragment_types(&self) -> &[FragmentType] {
+        self.first().get_fragment_types()
+    }
+
+    fn get_mut_varmap(&mut self) -> &mut VarMap {
+        &mut self.varmap
+    }
+
+    fn print_summary(&self) {
+        println!("Ensemble Summary:");
+        println!("Property: {}", self.property_type.as_str());
+        for (i, (member, weight)) in self.members.iter().zip(self.weights.iter()).enumerate() {
+            println!(
+                "Member {}: {} (weight: {:.3}, normalization: {:?})",
+                i,
+                member.model.get_model_arch(),
+                weight,
+                member.normalization
+            );
+        }
+    }
+
+    fn print_weights(&self) {
+        for member in &self.members {
+            member.model.print_weights();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::model_interface::PredictionResult;
+    use crate::utils::data_handling::PeptideData;
+    use crate::utils::peptdeep_utils::load_modifications;
+
+    fn peptides() -> (Vec<Arc<[u8]>>, Vec<Arc<[u8]>>, Vec<Arc<[u8]>>) {
+        (
+            vec![
+                Arc::from(b"AGHCEWQMKYR".to_vec().into_boxed_slice()),
+                Arc::from(b"VSSLQAEPLPR".to_vec().into_boxed_slice()),
+            ],
+            vec![
+                Arc::from(b"Carbamidomethyl@C;Oxidation@M".to_vec().into_boxed_slice()),
+                Arc::from(b"".to_vec().into_boxed_slice()),
+            ],
+            vec![
+                Arc::from(b"4;8".to_vec().into_boxed_slice()),
+                Arc::from(b"".to_vec().into_boxed_slice()),
+            ],
+        )
+    }
+
+    fn rt_values(result: PredictionResult) -> Vec<f32> {
+        match result {
+            PredictionResult::RTResult(values) => values,
+            _ => panic!("Unexpected prediction result type."),
+        }
+    }
+
+    #[test]
+    fn test_weighted_average_with_normalization() {
+        let (seqs, mods, sites) = peptides();
+        let mut a = RTCNNTFModel::new_untrained(Device::Cpu).unwrap();
+        let mut b = RTCNNTFModel::new_untrained(Device::Cpu).unwrap();
+        a.set_evaluation_mode();
+        b.set_evaluation_mode();
+        let pred_a = rt_values(a.predict(&seqs, &mods, &sites, None, None, None).unwrap());
+        let pred_b = rt_values(b.predict(&seqs, &mods, &sites, None, None, None).unwrap());
+
+        let ensemble = EnsembleModel::from_members(vec![
+            EnsembleMember::new(Box::new(a), 3.0),
+            EnsembleMember::new(Box::new(b), 1.0)
+                .with_normalization(TargetNormalization::MinMax(10.0, 110.0)),
+        ])
+        .unwrap();
+        assert_eq!(ensemble.weights(), &[0.75, 0.25]);
+
+        let pred = rt_values(ensemble.predict(&seqs, &mods, &sites, None, None, None).unwrap());
+        for i in 0..pred.len() {
+            let expected = 0.75 * pred_a[i] + 0.25 * (pred_b[i] * 100.0 + 10.0);
+            assert!((pred[i] - expected).abs() < 1e-4, "{} != {}", pred[i], expected);
+        }
+    }
+
+    #[test]
+    fn test_inference_ignores_target_normalization() {
+        let (seqs, mods, sites) = peptides();
+        let mut a = RTCNNTFModel::new_untrained(Device::Cpu).unwrap();
+        let mut b = RTCNNTFModel::new_untrained(Device::Cpu).unwrap();
+        a.set_evaluation_mode();
+        b.set_evaluation_mode();
+        let pred_a = rt_values(a.predict(&seqs, &mods, &sites, None, None, None).unwrap());
+        let pred_b = rt_values(b.predict(&seqs, &mods, &sites, None, None, None).unwrap());
+
+        let ensemble = EnsembleModel::from_members(vec![
+            EnsembleMember::new(Box::new(a), 1.0)
+                .with_normalization(TargetNormalization::MinMax(0.0, 60.0)),
+            EnsembleMember::new(Box::new(b), 1.0)
+                .with_normalization(TargetNormalization::MinMax(10.0, 110.0)),
+        ])
+        .unwrap();
+
+        let data: Vec<PeptideData> = (0..seqs.len())
+            .map(|i| PeptideData {
+                modified_sequence: seqs[i].clone(),
+                naked_sequence: seqs[i].clone(),
+                mods: mods[i].clone(),
+                mod_sites: sites[i].clone(),
+                charge: None,
+                precursor_mass: None,
+                nce: None,
+                instrument: None,
+                retention_time: None,
+                ion_mobility: None,
+                ccs: None,
+                ms2_intensities: None,
+                detectability: None,
+                charge_distribution: None,
+            })
+            .collect();
+        let results = ensemble
+            .inference(
+                &data,
+                2,
+                load_modifications().unwrap(),
+                TargetNormalization::MinMax(0.0, 120.0),
+            )
+            .unwrap();
+
+        for (i, peptide) in results.iter().enumerate() {
+            let expected = 0.5 * (pred_a[i] * 60.0) + 0.5 * (pred_b[i] * 100.0 + 10.0);
+            let rt = peptide.retention_time.unwrap();
+            assert!((rt - expected).abs() < 1e-4, "{} != {}", rt, expected);
+        }
+    }
+
+    #[test]
+    fn test_rejects_mixed_property_types() {
+        let rt = RTCNNTFModel::new_untrained(Device::Cpu).unwrap();
+        let ccs = CCSCNNTFModel::new_untrained(Device::Cpu).unwrap();
+        let result = EnsembleModel::from_members(vec![
+            EnsembleMember::new(Box::new(rt), 1.0),
+            EnsembleMember::new(Box::new(ccs), 1.0),
+        ]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_load_from_manifest() {
+        let dir = std::env::temp_dir().join(format!("redeem_ensemble_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        for name in ["rt_a.safetensors", "rt_b.safetensors"] {
+            let mut model = RTCNNTFModel::new_untrained(Device::Cpu).unwrap();
+            model.save(dir.join(name).to_str().unwrap()).unwrap();
+        }
+
+        let manifest = EnsembleManifest {
+            members: vec![
+                EnsembleMemberSpec {
+                    arch: "rt_cnn_tf".into(),
+                    model_path: "rt_a.safetensors".into(),
+                    constants_path: None,
+                    weight: 1.0,
+                    normalization: None,
+                },
+                EnsembleMemberSpec {
+                    arch: "rt_cnn_tf".into(),
+                    model_path: "rt_b.safetensors".into(),
+                    constants_path: None,
+                    weight: 2.0,
+                    normalization: Some(TargetNormalization::ZScore(50.0, 10.0)),
+                },
+            ],
+        };
+        let manifest_path = dir.join("ensemble.yaml");
+        manifest.save(&manifest_path).unwrap();
+
+        let mut ensemble = EnsembleModel::new(
+            manifest_path.clone(),
+            None,
+            0,
+            8,
+            4,
+            true,
+            Device::Cpu,
+        )
+        .unwrap();
+        ensemble.set_evaluation_mode();
+
+        assert_eq!(ensemble.members().len(), 2);
+        assert_eq!(ensemble.get_model_arch(), ENSEMBLE_ARCH);
+        let (seqs, mods, sites) = peptides();
+        let pred = rt_values(ensemble.predict(&seqs, &mods, &sites, None, None, None).unwrap());
+        assert_eq!(pred.len(), 2);
+        assert!(pred.iter().all(|p| p.is_finite()));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -6,4 +6,13 @@ pub mod ccs_cnn_lstm_model;
 pub mod ccs_cnn_tf_model;
 pub mod ms2_bert_model;
 pub mod ms2_model;
+pub mod detectability_model;
+pub mod detectability_cnn_lstm_model;
+pub mod detectability_cnn_tf_model;
+pub mod charge_model;
+pub mod charge_cnn_lstm_model;
+pub mod charge_cnn_tf_model;
+pub mod convert;
+pub mod ensemble;
+pub mod model_registry;
 pub mod model_interface;
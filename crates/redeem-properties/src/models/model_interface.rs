@@ -3,13 +3,18 @@ use crate::{
         self, aa_indices_tensor, aa_indices_tensor_from_arc, get_mod_features_from_parsed,
         get_mod_features_from_parsed_arc,
     },
-    models::{ccs_model::CCSModelWrapper, ms2_model::MS2ModelWrapper, rt_model::RTModelWrapper},
+    models::{
+        ccs_model::CCSModelWrapper, charge_model::ChargeModelWrapper,
+        detectability_model::DetectabilityModelWrapper, ms2_model::MS2ModelWrapper,
+        rt_model::RTModelWrapper,
+    },
     utils::{
         data_handling::{PeptideBatchData, PeptideData, TargetNormalization},
+        fragment_types::FragmentType,
         logging::Progress,
         peptdeep_utils::{
-            get_modification_indices, get_modification_string, parse_instrument_index,
-            remove_mass_shift,
+            get_modification_indices, get_modification_string, remove_mass_shift,
+            ModelConstants,
         },
         stats::{compute_loss_stats, Metrics, TrainingPhase, TrainingStepMetrics},
         utils::{get_tensor_stats, CosineWithWarmup, LRScheduler},
@@ -28,9 +33,8 @@ use std::{
     sync::Arc,
 };
 
-// Constants
-const CHARGE_FACTOR: f64 = 0.1;
-const NCE_FACTOR: f64 = 0.01;
+/// Number of precursor charge states (1 to 6) predicted by charge state distribution models.
+pub const NUM_CHARGE_STATES: usize = 6;
 
 /// Load tensors from a model file.
 ///
@@ -82,6 +86,8 @@ pub enum PropertyType {
     RT,
     CCS,
     MS2,
+    Detectability,
+    Charge,
 }
 
 impl PropertyType {
@@ -90,6 +96,8 @@ impl PropertyType {
             PropertyType::RT => "RT",
             PropertyType::CCS => "CCS",
             PropertyType::MS2 => "MS2",
+            PropertyType::Detectability => "Detectability",
+            PropertyType::Charge => "Charge",
         }
     }
 }
@@ -137,12 +145,14 @@ impl Index<(usize, usize)> for PredictionValue {
 
 /// Represents the output of a model prediction.
 ///
-/// This enum is used to store the output of a model prediction, which can be a vector of retention times (RT), collision cross-sections (CCS), or a vector matrices of MS2 intensities.
+/// This enum is used to store the output of a model prediction, which can be a vector of retention times (RT), collision cross-sections (CCS), a vector matrices of MS2 intensities, a vector of detection probabilities, or a vector of charge state distributions (probabilities for charges 1 to 6).
 #[derive(Debug, Clone)]
 pub enum PredictionResult {
     RTResult(Vec<f32>),
     CCSResult(Vec<f32>),
     MS2Result(Vec<Vec<Vec<f32>>>),
+    DetectabilityResult(Vec<f32>),
+    ChargeResult(Vec<Vec<f32>>),
 }
 
 impl PredictionResult {
@@ -151,6 +161,8 @@ impl PredictionResult {
             PredictionResult::RTResult(vec) => vec.len(),
             PredictionResult::CCSResult(vec) => vec.len(),
             PredictionResult::MS2Result(vec) => vec.len(),
+            PredictionResult::DetectabilityResult(vec) => vec.len(),
+            PredictionResult::ChargeResult(vec) => vec.len(),
         }
     }
 
@@ -159,10 +171,36 @@ impl PredictionResult {
             PredictionResult::RTResult(vec) => PredictionValue::Single(vec[index].clone()),
             PredictionResult::CCSResult(vec) => PredictionValue::Single(vec[index].clone()),
             PredictionResult::MS2Result(vec) => PredictionValue::Matrix(vec[index].clone()),
+            PredictionResult::DetectabilityResult(vec) => {
+                PredictionValue::Single(vec[index].clone())
+            }
+            PredictionResult::ChargeResult(vec) => PredictionValue::Matrix(vec![vec[index].clone()]),
         }
     }
 }
 
+/// Monte-Carlo dropout prediction: mean and standard deviation over repeated stochastic forward passes.
+///
+/// `mean` and `std` have the same layout as a regular [`PredictionResult`], e.g. one value per peptide
+/// for RT and CCS, and one value per fragment for MS2.
+#[derive(Debug, Clone)]
+pub struct PredictionUncertainty {
+    pub mean: PredictionResult,
+    pub std: PredictionResult,
+    pub n_samples: usize,
+}
+
+/// Convert a (post-processed) model output tensor into the prediction result for `property_type`.
+fn output_to_prediction_result(property_type: PropertyType, output: &Tensor) -> Result<PredictionResult> {
+    match property_type {
+        PropertyType::RT => Ok(PredictionResult::RTResult(output.to_vec1()?)),
+        PropertyType::CCS => Ok(PredictionResult::CCSResult(output.to_vec1()?)),
+        PropertyType::MS2 => Ok(PredictionResult::MS2Result(output.to_vec3()?)),
+        PropertyType::Detectability => Ok(PredictionResult::DetectabilityResult(output.to_vec1()?)),
+        PropertyType::Charge => Ok(PredictionResult::ChargeResult(output.to_vec2()?)),
+    }
+}
+
 /// Populates a mutable `VarMap` instance with tensors.
 ///
 /// # Arguments
@@ -183,6 +221,42 @@ pub fn create_var_map(
     Ok(())
 }
 
+/// Binary cross-entropy between predicted probabilities and binary targets.
+///
+/// Probabilities are clamped away from 0 and 1 so that the loss stays finite.
+///
+/// # Arguments
+/// * `predicted` - Predicted probabilities, e.g. the output of a sigmoid head.
+/// * `target` - Target labels, 1.0 for positive and 0.0 for negative examples.
+///
+/// # Returns
+/// A scalar tensor with the mean loss over all elements.
+pub fn binary_cross_entropy(predicted: &Tensor, target: &Tensor) -> Result<Tensor, candle_core::Error> {
+    let eps = 1e-7;
+    let predicted = predicted.clamp(eps, 1.0 - eps)?;
+    let positive = target.mul(&predicted.log()?)?;
+    let negative = target
+        .affine(-1.0, 1.0)?
+        .mul(&predicted.affine(-1.0, 1.0)?.log()?)?;
+    positive.add(&negative)?.neg()?.mean_all()
+}
+
+/// Cross-entropy between predicted class probabilities and target class distributions.
+///
+/// Targets may be one-hot encoded labels or soft distributions, e.g. the fraction of
+/// observations of a peptide at each charge state.
+///
+/// # Arguments
+/// * `predicted` - Predicted probabilities of shape `(batch, num_classes)`, e.g. the output of a softmax head.
+/// * `target` - Target distributions of the same shape, each row summing to 1.
+///
+/// # Returns
+/// A scalar tensor with the loss averaged over the batch.
+pub fn categorical_cross_entropy(predicted: &Tensor, target: &Tensor) -> Result<Tensor, candle_core::Error> {
+    let predicted = predicted.clamp(1e-7, 1.0)?;
+    target.mul(&predicted.log()?)?.sum(1)?.neg()?.mean_all()
+}
+
 pub trait ModelClone {
     fn clone_box(&self) -> Box<dyn ModelInterface + Send + Sync>;
 }
@@ -271,27 +345,71 @@ pub trait ModelInterface: Send + Sync + ModelClone {
             )?
             .to_device(self.get_device())?;
 
-        let output = self.forward(&input_tensor)?;
+        let mut output = self.forward(&input_tensor)?;
+        if let PropertyType::MS2 = self.property_type() {
+            output = self.process_predictions(&output, self.get_min_pred_intensity())?;
+        }
 
-        match self.property_type() {
-            PropertyType::RT => {
-                let predictions: Vec<f32> = output.to_vec1()?;
-                Ok(PredictionResult::RTResult(predictions))
-            }
-            PropertyType::CCS => {
-                let predictions: Vec<f32> = output.to_vec1()?;
-                Ok(PredictionResult::CCSResult(predictions))
-            }
-            PropertyType::MS2 => {
-                let out = self.process_predictions(&output, self.get_min_pred_intensity())?;
-                let predictions: Vec<Vec<Vec<f32>>> = out.to_vec3()?;
-                Ok(PredictionResult::MS2Result(predictions))
-            }
-            _ => Err(anyhow::anyhow!(
-                "Unsupported property type: {:?}",
-                self.get_property_type()
-            )),
+        output_to_prediction_result(self.property_type(), &output)
+    }
+
+    /// Predict with Monte-Carlo dropout to estimate prediction uncertainty.
+    ///
+    /// Runs `n_samples` forward passes with dropout enabled on a copy of the model and returns the
+    /// mean and (population) standard deviation of the predictions. The model itself is not modified.
+    ///
+    /// # Arguments
+    /// Same as [`ModelInterface::predict`], plus:
+    /// * `n_samples` - Number of stochastic forward passes (must be at least 1).
+    fn predict_with_uncertainty(
+        &self,
+        peptide_sequences: &[Arc<[u8]>],
+        mods: &[Arc<[u8]>],
+        mod_sites: &[Arc<[u8]>],
+        charges: Option<Vec<i32>>,
+        nces: Option<Vec<i32>>,
+        instruments: Option<Vec<Option<Arc<[u8]>>>>,
+        n_samples: usize,
+    ) -> Result<PredictionUncertainty> {
+        if n_samples == 0 {
+            anyhow::bail!("Monte-Carlo dropout requires at least one sample");
         }
+
+        let input_tensor = self
+            .encode_peptides(
+                peptide_sequences,
+                mods,
+                mod_sites,
+                charges,
+                nces,
+                instruments,
+            )?
+            .to_device(self.get_device())?;
+
+        let mut sampler = self.clone_box();
+        sampler.set_training_mode();
+
+        let samples = (0..n_samples)
+            .map(|_| {
+                let output = sampler.forward(&input_tensor)?;
+                match self.property_type() {
+                    PropertyType::MS2 => {
+                        self.process_predictions(&output, self.get_min_pred_intensity())
+                    }
+                    _ => Ok(output),
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let samples = Tensor::stack(&samples, 0)?;
+        let mean = samples.mean(0)?;
+        let std = samples.broadcast_sub(&mean)?.sqr()?.mean(0)?.sqrt()?;
+
+        Ok(PredictionUncertainty {
+            mean: output_to_prediction_result(self.property_type(), &mean)?,
+            std: output_to_prediction_result(self.property_type(), &std)?,
+            n_samples,
+        })
     }
 
     /// Encode peptide sequence (plus modifications) into a tensor.
@@ -305,7 +423,8 @@ pub trait ModelInterface: Send + Sync + ModelClone {
         instrument: Option<&Arc<[u8]>>,
     ) -> Result<Tensor> {
         let device = self.get_device();
-        let mod_feature_size = self.get_mod_element_count();
+        let constants = self.get_model_constants();
+        let mod_feature_size = constants.get_mod_feature_size();
         let mod_to_feature = self.get_mod_to_feature();
 
         // log::trace!(
@@ -343,7 +462,7 @@ pub trait ModelInterface: Send + Sync + ModelClone {
 
         if let Some(c) = charge {
             let charge_tensor = Tensor::from_slice(
-                &vec![c as f64 * CHARGE_FACTOR; seq_len],
+                &vec![c as f32 * constants.get_charge_factor(); seq_len],
                 &[batch_size, seq_len, 1],
                 device,
             )?
@@ -353,7 +472,7 @@ pub trait ModelInterface: Send + Sync + ModelClone {
 
         if let Some(n) = nce {
             let nce_tensor = Tensor::from_slice(
-                &vec![n as f64 * NCE_FACTOR; seq_len],
+                &vec![n as f32 * constants.get_nce_factor(); seq_len],
                 &[batch_size, seq_len, 1],
                 device,
             )?
@@ -363,7 +482,7 @@ pub trait ModelInterface: Send + Sync + ModelClone {
 
         if let Some(instr) = instrument {
             let instr_str = std::str::from_utf8(instr).unwrap_or("");
-            let instr_idx = parse_instrument_index(instr_str) as u32;
+            let instr_idx = constants.get_instrument_index(instr_str) as u32;
             let instr_tensor =
                 Tensor::from_slice(&vec![instr_idx; seq_len], &[batch_size, seq_len, 1], device)?
                     .to_dtype(DType::F32)?;
@@ -500,6 +619,18 @@ pub trait ModelInterface: Send + Sync + ModelClone {
         save_checkpoints: bool,
         track_metrics: bool,
     ) -> Result<TrainingStepMetrics> {
+        // Unlabeled peptides must not be trained as unobserved
+        if matches!(self.property_type(), PropertyType::Detectability) {
+            for (name, data) in [("training", Some(training_data)), ("validation", validation_data)] {
+                if let Some(i) = data.and_then(|data| data.iter().position(|p| p.detectability.is_none())) {
+                    return Err(anyhow::anyhow!(
+                        "Missing detectability label for {} peptide at index {i}",
+                        name
+                    ));
+                }
+            }
+        }
+
         let num_batches = (training_data.len() + batch_size - 1) / batch_size;
         let total_steps = num_batches * epochs;
         let warmup_steps = total_steps / 10; 
@@ -553,7 +684,7 @@ pub trait ModelInterface: Send + Sync + ModelClone {
                         self.prepare_batch_inputs(batch_data, &modifications)?;
 
                     let predicted = self.forward(&input_batch)?;
-                    let loss = candle_nn::loss::mse(&predicted, &target_batch)?;
+                    let loss = self.compute_loss(&predicted, &target_batch)?;
                     opt.backward_step(&loss)?;
 
                     // Update learning rate after optimizer step
@@ -563,8 +694,8 @@ pub trait ModelInterface: Send + Sync + ModelClone {
                     let loss_val = loss.to_vec0::<f32>().unwrap_or(999.0);
                     batch_losses.push(loss_val);
 
-                    let predictions = predicted.to_vec1::<f32>()?;
-                    let targets = target_batch.to_vec1::<f32>()?;
+                    let predictions = predicted.flatten_all()?.to_vec1::<f32>()?;
+                    let targets = target_batch.flatten_all()?.to_vec1::<f32>()?;
 
                     let acc = match self.property_type() {
                         PropertyType::RT => Some(Metrics::accuracy(&predictions, &targets, 0.5)), // is predicted RT within 0.5 min of target RT?
@@ -572,8 +703,11 @@ pub trait ModelInterface: Send + Sync + ModelClone {
                             let tol: Vec<f32> = targets.iter().map(|t| t * 0.02).collect();
                             Some(Metrics::accuracy_dynamic(&predictions, &targets, &tol))
                         } // is predicted CCS within 2% of target CCS?
+                        PropertyType::Detectability => Some(Metrics::accuracy(&predictions, &targets, 0.5)), // is the predicted probability on the same side of 0.5 as the label?
+                        PropertyType::Charge => Some(Metrics::top1_accuracy(&predictions, &targets, NUM_CHARGE_STATES)), // is the most likely predicted charge the most observed charge?
                         _ => None,
                     };
+                    let (precision, recall) = self.classification_metrics(&predictions, &targets);
 
                     if track_metrics{
                         step_metrics.epochs.push(epoch);
@@ -584,8 +718,8 @@ pub trait ModelInterface: Send + Sync + ModelClone {
                         step_metrics.losses.push(loss_val);
                         step_metrics.phases.push(TrainingPhase::Train);
                         step_metrics.accuracies.push(acc);
-                        step_metrics.precisions.push(None);
-                        step_metrics.recalls.push(None);
+                        step_metrics.precisions.push(precision);
+                        step_metrics.recalls.push(recall);
                         step_idx += 1;
                     }
                     
@@ -612,18 +746,18 @@ pub trait ModelInterface: Send + Sync + ModelClone {
                 let val_batches =
                     (val_data.len() + validation_batch_size - 1) / validation_batch_size;
 
-                let val_results: Vec<(f32, usize, f64, Option<f32>)> = val_data
+                let val_results: Vec<(f32, usize, f64, Option<f32>, Option<f32>, Option<f32>)> = val_data
                     .par_chunks(validation_batch_size)
                     .enumerate()
                     .map(|(idx, batch_data)| {
                         let (input_val, target_val) =
                             self.prepare_batch_inputs(batch_data, &modifications)?;
                         let predicted = self.forward(&input_val)?;
-                        let val_loss = candle_nn::loss::mse(&predicted, &target_val)?;
+                        let val_loss = self.compute_loss(&predicted, &target_val)?;
                         let loss_val = val_loss.to_vec0::<f32>()?;
 
-                        let predictions = predicted.to_vec1::<f32>()?;
-                        let targets = target_val.to_vec1::<f32>()?;
+                        let predictions = predicted.flatten_all()?.to_vec1::<f32>()?;
+                        let targets = target_val.flatten_all()?.to_vec1::<f32>()?;
 
                         let acc = match self.property_type() {
                             PropertyType::RT => {
@@ -636,29 +770,46 @@ pub trait ModelInterface: Send + Sync + ModelClone {
                                 let tol: Vec<f32> = targets.iter().map(|t| t * 0.02).collect();
                                 Some(Metrics::accuracy_dynamic(&predictions, &targets, &tol))
                             }
+                            PropertyType::Detectability => {
+                                Some(Metrics::accuracy(&predictions, &targets, 0.5))
+                            }
+                            PropertyType::Charge => Some(Metrics::top1_accuracy(
+                                &predictions,
+                                &targets,
+                                NUM_CHARGE_STATES,
+                            )),
                             _ => None,
                         };
-
-                        Ok((loss_val, idx, lr_scheduler.get_last_lr(), acc))
+                        let (precision, recall) =
+                            self.classification_metrics(&predictions, &targets);
+
+                        Ok((
+                            loss_val,
+                            idx,
+                            lr_scheduler.get_last_lr(),
+                            acc,
+                            precision,
+                            recall,
+                        ))
                     })
                     .collect::<Result<_>>()?;
 
                 if track_metrics{
-                    for (val_loss, idx, lr, acc) in &val_results {
+                    for (val_loss, idx, lr, acc, precision, recall) in &val_results {
                         step_metrics.epochs.push(epoch);
                         step_metrics.steps.push(val_step_idx + idx);
                         step_metrics.learning_rates.push(*lr);
                         step_metrics.losses.push(*val_loss);
                         step_metrics.phases.push(TrainingPhase::Validation);
                         step_metrics.accuracies.push(*acc);
-                        step_metrics.precisions.push(None);
-                        step_metrics.recalls.push(None);
+                        step_metrics.precisions.push(*precision);
+                        step_metrics.recalls.push(*recall);
                     }
                     val_step_idx += val_results.len();
                 }
 
                 let val_losses: Vec<f32> =
-                    val_results.iter().map(|(loss, _, _, _)| *loss).collect();
+                    val_results.iter().map(|(loss, _, _, _, _, _)| *loss).collect();
                 let (avg_val_loss, std_val_loss): (f32, f32) = compute_loss_stats(&val_losses);
 
                 epoch_losses.push((
@@ -737,6 +888,13 @@ pub trait ModelInterface: Send + Sync + ModelClone {
         Ok(())
     }
 
+    /// Whether [`ModelInterface::forward`] already returns values on the original target scale.
+    ///
+    /// [`ModelInterface::inference`] ignores its target normalization for such models.
+    fn denormalizes_output(&self) -> bool {
+        false
+    }
+
     /// Perform inference over a batch of peptides.
     fn inference(
         &self,
@@ -755,6 +913,12 @@ pub trait ModelInterface: Send + Sync + ModelClone {
             num_batches
         );
     
+        let target_norm = if self.denormalizes_output() {
+            TargetNormalization::None
+        } else {
+            target_norm
+        };
+
         let progress = Progress::new(inference_data.len(), "[inference] Batch:");
         let mut result: Vec<Option<PeptideData>> = vec![None; inference_data.len()];
     
@@ -768,12 +932,15 @@ pub trait ModelInterface: Send + Sync + ModelClone {
                 let (input_tensor, _) = self.prepare_batch_inputs(batch_data, &modifications)?;
                 let predicted = self.forward(&input_tensor)?;
     
-                let predictions = predicted.to_vec1::<f32>()?;
+                // One value per peptide, or one row per peptide for charge state distributions
+                let predictions = predicted.flatten_all()?.to_vec1::<f32>()?;
+                let width = predictions.len() / batch_data.len();
     
                 let updated = predictions
-                    .into_iter()
+                    .chunks(width)
                     .enumerate()
-                    .map(|(i, pred)| {
+                    .map(|(i, values)| {
+                        let pred = values[0];
                         let mut peptide = batch_data[i].clone();
                         let value = match target_norm {
                             TargetNormalization::ZScore(mean, std) => pred * std + mean,
@@ -783,6 +950,11 @@ pub trait ModelInterface: Send + Sync + ModelClone {
                         match self.property_type() {
                             PropertyType::RT => peptide.retention_time = Some(value),
                             PropertyType::CCS => peptide.ccs = Some(value),
+                            // Probabilities are never normalized
+                            PropertyType::Detectability => peptide.detectability = Some(pred),
+                            PropertyType::Charge => {
+                                peptide.charge_distribution = Some(values.to_vec())
+                            }
                             _ => {}
                         }
                         (start_idx + i, peptide)
@@ -859,12 +1031,20 @@ pub trait ModelInterface: Send + Sync + ModelClone {
                 Tensor::new(target_values, &self.get_device())?
             }
             PropertyType::MS2 => {
+                let num_frag_types = self.get_fragment_types().len();
                 let mut targets = Vec::new();
                 for (i, opt_peptide) in batch.ms2_intensities.iter().enumerate() {
                     let peptide = opt_peptide.as_ref().ok_or_else(|| {
                         anyhow::anyhow!("Missing MS2 intensities for peptide at index {i}")
                     })?;
                     for row in peptide {
+                        if row.len() != num_frag_types {
+                            return Err(anyhow::anyhow!(
+                                "Expected {} fragment intensities per position for peptide at index {i}, got {}",
+                                num_frag_types,
+                                row.len()
+                            ));
+                        }
                         for val in row {
                             targets.push(*val);
                         }
@@ -876,15 +1056,77 @@ pub trait ModelInterface: Send + Sync + ModelClone {
                         .as_ref()
                         .ok_or_else(|| anyhow::anyhow!("Missing MS2 intensities in batch"))?
                         .len(),
-                    8,
+                    num_frag_types,
                 );
                 Tensor::from_vec(targets, shape, &self.get_device())?
             }
+            PropertyType::Detectability => {
+                // Only inference batches are unlabeled, `train` rejects peptides without a label
+                let target_values: Vec<f32> = batch
+                    .detectabilities
+                    .iter()
+                    .map(|v| v.unwrap_or(0.0))
+                    .collect();
+                Tensor::new(target_values, &self.get_device())?
+            }
+            PropertyType::Charge => {
+                let mut targets = Vec::with_capacity(batch.charge_distributions.len() * NUM_CHARGE_STATES);
+                for (i, distribution) in batch.charge_distributions.iter().enumerate() {
+                    // Peptides without an observed distribution (e.g. at inference) get an all-zero target
+                    let distribution = match distribution {
+                        Some(distribution) => distribution,
+                        None => {
+                            targets.extend_from_slice(&[0.0; NUM_CHARGE_STATES]);
+                            continue;
+                        }
+                    };
+                    if distribution.len() != NUM_CHARGE_STATES {
+                        anyhow::bail!(
+                            "Charge state distribution for peptide at index {i} has {} values, expected {}",
+                            distribution.len(),
+                            NUM_CHARGE_STATES
+                        );
+                    }
+                    targets.extend_from_slice(distribution);
+                }
+                Tensor::from_vec(
+                    targets,
+                    (batch.charge_distributions.len(), NUM_CHARGE_STATES),
+                    &self.get_device(),
+                )?
+            }
         };
 
         Ok((input_batch, target_tensor))
     }
 
+    /// Compute the training loss between predicted and target tensors.
+    ///
+    /// Detectability models output probabilities and are trained with binary cross-entropy,
+    /// charge state models output a distribution over charges and are trained with cross-entropy,
+    /// all other property types use the mean squared error.
+    fn compute_loss(&self, predicted: &Tensor, target: &Tensor) -> Result<Tensor, candle_core::Error> {
+        match self.property_type() {
+            PropertyType::Detectability => binary_cross_entropy(predicted, target),
+            PropertyType::Charge => categorical_cross_entropy(predicted, target),
+            _ => candle_nn::loss::mse(predicted, target),
+        }
+    }
+
+    /// Compute precision and recall at a 0.5 probability threshold for classification models.
+    ///
+    /// # Returns
+    /// A `(precision, recall)` tuple, both `None` for regression property types.
+    fn classification_metrics(&self, predictions: &[f32], targets: &[f32]) -> (Option<f32>, Option<f32>) {
+        match self.property_type() {
+            PropertyType::Detectability => (
+                Metrics::precision(predictions, targets, 0.5),
+                Metrics::recall(predictions, targets, 0.5),
+            ),
+            _ => (None, None),
+        }
+    }
+
     /// Set model to evaluation mode for inference
     /// This disables dropout and other training-specific layers.
     fn set_evaluation_mode(&mut self);
@@ -901,10 +1143,19 @@ pub trait ModelInterface: Send + Sync + ModelClone {
 
     fn get_mod_element_count(&self) -> usize;
 
+    /// Featurization constants (mod elements, instruments, scaling factors) the model was trained with.
+    fn get_model_constants(&self) -> &ModelConstants;
+
     fn get_mod_to_feature(&self) -> &HashMap<Arc<[u8]>, Vec<f32>>;
 
     fn get_min_pred_intensity(&self) -> f32;
 
+    /// Fragment type of each intensity channel predicted by MS2 models, in channel order.
+    /// Empty for models that don't predict fragment intensities.
+    fn get_fragment_types(&self) -> &[FragmentType] {
+        &[]
+    }
+
     fn get_mut_varmap(&mut self) -> &mut VarMap;
 
     fn print_summary(&self);
@@ -1025,7 +1276,7 @@ impl Parameters {
 /// Represents a collection of deep learning models for various property prediction tasks.
 ///
 /// This struct holds optional references to models for retention time (RT),
-/// collision cross-section (CCS), and MS2 intensity predictions. Each model
+/// collision cross-section (CCS), MS2 intensity, detectability and charge state predictions. Each model
 /// is wrapped in an Arc<Mutex<>> for thread-safe shared ownership.
 pub struct DLModels {
     /// Parameters for prediction models.
@@ -1039,6 +1290,12 @@ pub struct DLModels {
 
     /// Optional MS2 intensity prediction model.
     pub ms2_model: Option<MS2ModelWrapper>,
+
+    /// Optional peptide detectability prediction model.
+    pub detectability_model: Option<DetectabilityModelWrapper>,
+
+    /// Optional precursor charge state distribution prediction model.
+    pub charge_model: Option<ChargeModelWrapper>,
 }
 
 impl DLModels {
@@ -1062,6 +1319,8 @@ impl DLModels {
             rt_model: None,
             ccs_model: None,
             ms2_model: None,
+            detectability_model: None,
+            charge_model: None,
         }
     }
 
@@ -1081,6 +1340,10 @@ impl DLModels {
     /// assert!(models.is_not_empty());
     /// ```
     pub fn is_not_empty(&self) -> bool {
-        self.rt_model.is_some() || self.ccs_model.is_some() || self.ms2_model.is_some()
+        self.rt_model.is_some()
+            || self.ccs_model.is_some()
+            || self.ms2_model.is_some()
+            || self.detectability_model.is_some()
+            || self.charge_model.is_some()
     }
 }
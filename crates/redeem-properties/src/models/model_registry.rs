@@ -0,0 +1,502 @@
+// model_registry.rs
+
+use anyhow::{anyhow, Context, Result};
+use csv::{ReaderBuilder, WriterBuilder};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+
+/// Environment variable overriding the local pretrained model cache directory.
+pub const MODEL_DIR_ENV: &str = "REDEEM_MODEL_DIR";
+
+/// Default local pretrained model cache directory, relative to the working directory.
+pub const DEFAULT_MODEL_DIR: &str = "data/models";
+
+/// File name of the registry manifest in the model cache directory.
+pub const MANIFEST_FILE: &str = "registry.tsv";
+
+#[cfg(feature = "download")]
+const PRETRAINED_MODELS_URL: &str = "https://github.com/singjc/redeem/releases/download/v0.1.0-alpha/peptdeep_generic_pretrained_models.zip";
+
+/// Pinned SHA-256 of the release archive at `PRETRAINED_MODELS_URL`, as lowercase hex. The
+/// archive is verified before it is extracted; `None` until the checksum is recorded.
+#[cfg(feature = "download")]
+const PRETRAINED_MODELS_SHA256: Option<&str> = None;
+
+/// Pinned SHA-256 of the model files in the release archive, as lowercase hex, by file name.
+/// Files without a pinned checksum are not verified.
+const PEPTDEEP_GENERIC_SHA256: &[(&str, &str)] = &[];
+
+// Instruments supported by the AlphaPeptDeep generic MS2 model
+const PEPTDEEP_MS2_INSTRUMENTS: &[&str] = &["QE", "Lumos", "timsTOF", "SciexTOF"];
+
+/// A pretrained model available in the local model cache.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelEntry {
+    /// Unique name of the model, e.g. `alphapeptdeep_generic_rt`.
+    pub name: String,
+    /// Model architecture, e.g. `rt_cnn_lstm`.
+    pub arch: String,
+    /// Predicted property, e.g. `RT`.
+    pub property: String,
+    /// Instruments the model was trained for; empty for instrument independent models.
+    pub instruments: Vec<String>,
+    /// Model file, relative to the cache directory.
+    pub path: PathBuf,
+    /// Model constants file, relative to the cache directory.
+    pub constants_path: Option<PathBuf>,
+    /// Expected SHA-256 of the model file, as lowercase hex. Entries without a checksum are not
+    /// verified.
+    pub sha256: Option<String>,
+}
+
+impl ModelEntry {
+    /// Whether the model supports the given instrument (case-insensitive). Instrument independent
+    /// models support all instruments.
+    pub fn supports_instrument(&self, instrument: &str) -> bool {
+        self.instruments.is_empty()
+            || self
+                .instruments
+                .iter()
+                .any(|i| i.eq_ignore_ascii_case(instrument))
+    }
+}
+
+/// Paths of a pretrained model resolved from the model cache.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedModel {
+    pub model_path: PathBuf,
+    pub constants_path: Option<PathBuf>,
+}
+
+/// Registry of the pretrained models in a local model cache directory.
+///
+/// The models are listed in the `registry.tsv` manifest of the cache directory, or default to the
+/// AlphaPeptDeep generic models when there is no manifest. Resolving a model never touches the
+/// network; use [`ModelRegistry::fetch`] (feature `download`) to populate the cache.
+#[derive(Debug, Clone)]
+pub struct ModelRegistry {
+    cache_dir: PathBuf,
+    entries: Vec<ModelEntry>,
+}
+
+impl ModelRegistry {
+    /// Open the registry of the given cache directory.
+    pub fn new<P: AsRef<Path>>(cache_dir: P) -> Result<Self> {
+        let cache_dir = cache_dir.as_ref().to_path_buf();
+        let manifest = cache_dir.join(MANIFEST_FILE);
+        let entries = if manifest.exists() {
+            read_manifest(&manifest)?
+        } else {
+            default_entries()
+        };
+
+        Ok(Self { cache_dir, entries })
+    }
+
+    /// Open the registry of the cache directory given by `REDEEM_MODEL_DIR`, or `data/models` if
+    /// it isn't set.
+    pub fn from_env() -> Result<Self> {
+        let cache_dir = std::env::var_os(MODEL_DIR_ENV)
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_MODEL_DIR));
+        Self::new(cache_dir)
+    }
+
+    pub fn cache_dir(&self) -> &Path {
+        &self.cache_dir
+    }
+
+    /// All models of the registry.
+    pub fn list(&self) -> &[ModelEntry] {
+        &self.entries
+    }
+
+    /// Models of the registry whose files are present in the cache directory.
+    pub fn available(&self) -> Vec<&ModelEntry> {
+        self.entries
+            .iter()
+            .filter(|e| self.cache_dir.join(&e.path).exists())
+            .collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ModelEntry> {
+        self.entries.iter().find(|e| e.name == name)
+    }
+
+    /// First model of the given architecture supporting the instrument, if any.
+    pub fn find(&self, arch: &str, instrument: Option<&str>) -> Option<&ModelEntry> {
+        self.entries.iter().find(|e| {
+            e.arch == arch && instrument.map_or(true, |i| e.supports_instrument(i))
+        })
+    }
+
+    /// Add a model to the registry, replacing any model of the same name.
+    pub fn register(&mut self, entry: ModelEntry) {
+        self.entries.retain(|e| e.name != entry.name);
+        self.entries.push(entry);
+    }
+
+    /// Resolve the files of a model in the cache directory, verifying its checksum.
+    pub fn resolve(&self, name: &str) -> Result<ResolvedModel> {
+        let entry = self
+            .get(name)
+            .ok_or_else(|| anyhow!("Unknown pretrained model: {}", name))?;
+
+        let model_path = self.cache_dir.join(&entry.path);
+        if !model_path.exists() {
+            return Err(anyhow!(
+                "Pretrained model {} not found at {:?}. Set {} to the model cache directory, or fetch the models with the `download` feature.",
+                name,
+                model_path,
+                MODEL_DIR_ENV
+            ));
+        }
+
+        verify_sha256(&model_path, entry.sha256.as_deref())?;
+
+        let constants_path = match &entry.constants_path {
+            Some(path) => {
+                let path = self.cache_dir.join(path);
+                if !path.exists() {
+                    return Err(anyhow!("Model constants for {} not found at {:?}", name, path));
+                }
+                Some(path)
+            }
+            None => None,
+        };
+
+        Ok(ResolvedModel {
+            model_path,
+            constants_path,
+        })
+    }
+
+    /// Record the checksum of every present model file without one.
+    pub fn pin_checksums(&mut self) -> Result<()> {
+        for entry in &mut self.entries {
+            let path = self.cache_dir.join(&entry.path);
+            if entry.sha256.is_none() && path.exists() {
+                entry.sha256 = Some(sha256_file(&path)?);
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the registry to the manifest of the cache directory.
+    pub fn save_manifest(&self) -> Result<()> {
+        fs::create_dir_all(&self.cache_dir)?;
+        write_manifest(&self.cache_dir.join(MANIFEST_FILE), &self.entries)
+    }
+
+    /// Download and extract the AlphaPeptDeep generic models into the cache directory, unless the
+    /// registry's models are already present.
+    ///
+    /// The archive is downloaded to a temporary file, which is only moved into the cache once its
+    /// checksum matches, and the extracted models are verified against their checksums. A cached
+    /// archive that fails verification or cannot be opened is removed, so the next call downloads
+    /// it again.
+    #[cfg(feature = "download")]
+    pub fn fetch(&self) -> Result<()> {
+        if !self.entries.is_empty() && self.available().len() == self.entries.len() {
+            return Ok(());
+        }
+
+        fs::create_dir_all(&self.cache_dir)?;
+        let zip_path = self.cache_dir.join("peptdeep_generic_pretrained_models.zip");
+
+        if zip_path.exists() {
+            if let Err(e) = verify_sha256(&zip_path, PRETRAINED_MODELS_SHA256) {
+                log::warn!("Removing cached pretrained models: {}", e);
+                fs::remove_file(&zip_path)?;
+            }
+        }
+        if !zip_path.exists() {
+            log::info!("Downloading pretrained models...");
+            download_verified(PRETRAINED_MODELS_URL, &zip_path, PRETRAINED_MODELS_SHA256)?;
+        }
+
+        log::info!("Unzipping pretrained models...");
+        let extract_dir = self.cache_dir.join("alphapeptdeep");
+        let mut archive = match zip::ZipArchive::new(File::open(&zip_path)?) {
+            Ok(archive) => archive,
+            Err(e) => {
+                fs::remove_file(&zip_path)?;
+                return Err(anyhow!("Invalid pretrained model archive {:?}, removed it: {}", zip_path, e));
+            }
+        };
+        for i in 0..archive.len() {
+            let mut file = archive.by_index(i)?;
+            let outpath = extract_dir.join(file.mangled_name());
+
+            if file.name().ends_with('/') {
+                fs::create_dir_all(&outpath)?;
+            } else {
+                if let Some(parent) = outpath.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                // Extract under a temporary name, so an interrupted extraction leaves no truncated model
+                let part_path = part_path(&outpath);
+                let mut outfile = File::create(&part_path)?;
+                std::io::copy(&mut file, &mut outfile)?;
+                fs::rename(&part_path, &outpath)?;
+            }
+        }
+
+        for entry in &self.entries {
+            let path = self.cache_dir.join(&entry.path);
+            if path.exists() {
+                verify_sha256(&path, entry.sha256.as_deref())?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The AlphaPeptDeep generic models, as extracted by [`ModelRegistry::fetch`].
+pub fn default_entries() -> Vec<ModelEntry> {
+    let entry = |name: &str, arch: &str, property: &str, file: &str, instruments: &[&str]| ModelEntry {
+        name: name.to_string(),
+        arch: arch.to_string(),
+        property: property.to_string(),
+        instruments: instruments.iter().map(|i| i.to_string()).collect(),
+        path: PathBuf::from("alphapeptdeep/generic").join(file),
+        constants_path: Some(PathBuf::from("alphapeptdeep/generic").join(format!("{}.model_const.yaml", file))),
+        sha256: PEPTDEEP_GENERIC_SHA256
+            .iter()
+            .find(|(f, _)| *f == file)
+            .map(|(_, sha256)| sha256.to_string()),
+    };
+
+    vec![
+        entry("alphapeptdeep_generic_rt", "rt_cnn_lstm", "RT", "rt.pth", &[]),
+        entry("alphapeptdeep_generic_ccs", "ccs_cnn_lstm", "CCS", "ccs.pth", &[]),
+        entry("alphapeptdeep_generic_ms2", "ms2_bert", "MS2", "ms2.pth", PEPTDEEP_MS2_INSTRUMENTS),
+    ]
+}
+
+/// SHA-256 of a file, as lowercase hex.
+pub fn sha256_file<P: AsRef<Path>>(path: P) -> Result<String> {
+    let file = File::open(path.as_ref())
+        .with_context(|| format!("Failed to open file: {:?}", path.as_ref()))?;
+    let mut reader = BufReader::new(file);
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 8192];
+    loop {
+        let n = reader.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+    }
+    Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Check the SHA-256 of a file against the expected lowercase hex checksum, if any.
+fn verify_sha256(path: &Path, expected: Option<&str>) -> Result<()> {
+    if let Some(expected) = expected {
+        let found = sha256_file(path)?;
+        if !found.eq_ignore_ascii_case(expected) {
+            return Err(anyhow!(
+                "Checksum mismatch for {:?}: expected {}, found {}",
+                path,
+                expected,
+                found
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Temporary path a file is written to before it is moved to `path`.
+#[cfg(feature = "download")]
+fn part_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".part");
+    path.with_file_name(file_name)
+}
+
+/// Download `url` to `path` through a temporary file, which is moved to `path` only once the
+/// download is complete and its checksum matches `sha256`.
+#[cfg(feature = "download")]
+fn download_verified(url: &str, path: &Path, sha256: Option<&str>) -> Result<()> {
+    let part_path = part_path(path);
+    let mut response = reqwest::blocking::get(url)
+        .and_then(|r| r.error_for_status())
+        .with_context(|| format!("Failed to download {}", url))?;
+    let mut file = File::create(&part_path)?;
+    std::io::copy(&mut response, &mut file)
+        .with_context(|| format!("Failed to download {}", url))?;
+    file.sync_all()?;
+    drop(file);
+
+    if let Err(e) = verify_sha256(&part_path, sha256) {
+        fs::remove_file(&part_path)?;
+        return Err(e);
+    }
+    fs::rename(&part_path, path)?;
+    Ok(())
+}
+
+const MANIFEST_HEADER: &[&str] = &[
+    "name",
+    "arch",
+    "property",
+    "instruments",
+    "path",
+    "constants_path",
+    "sha256",
+];
+
+fn read_manifest(path: &Path) -> Result<Vec<ModelEntry>> {
+    let mut reader = ReaderBuilder::new()
+        .delimiter(b'\t')
+        .has_headers(true)
+        .from_path(path)
+        .with_context(|| format!("Failed to open model registry manifest: {:?}", path))?;
+
+    let mut entries = Vec::new();
+    for (i, record) in reader.records().enumerate() {
+        let record = record?;
+        let field = |idx: usize| record.get(idx).unwrap_or("").trim();
+        if field(0).is_empty() || field(1).is_empty() || field(4).is_empty() {
+            return Err(anyhow!(
+                "Missing name, arch or path in row {} of {:?}",
+                i + 1,
+                path
+            ));
+        }
+        let optional = |s: &str| (!s.is_empty()).then(|| s.to_string());
+
+        entries.push(ModelEntry {
+            name: field(0).to_string(),
+            arch: field(1).to_string(),
+            property: field(2).to_string(),
+            instruments: field(3)
+                .split(',')
+                .filter(|s| !s.is_empty())
+                .map(|s| s.to_string())
+                .collect(),
+            path: PathBuf::from(field(4)),
+            constants_path: optional(field(5)).map(PathBuf::from),
+            sha256: optional(field(6)),
+        });
+    }
+    Ok(entries)
+}
+
+fn write_manifest(path: &Path, entries: &[ModelEntry]) -> Result<()> {
+    let mut writer = WriterBuilder::new()
+        .delimiter(b'\t')
+        .from_path(path)
+        .with_context(|| format!("Failed to create model registry manifest: {:?}", path))?;
+
+    writer.write_record(MANIFEST_HEADER)?;
+    for entry in entries {
+        writer.write_record([
+            entry.name.as_str(),
+            entry.arch.as_str(),
+            entry.property.as_str(),
+            &entry.instruments.join(","),
+            &entry.path.to_string_lossy(),
+            &entry
+                .constants_path
+                .as_ref()
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            entry.sha256.as_deref().unwrap_or(""),
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_cache_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("redeem_registry_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write_model(dir: &Path, entry: &ModelEntry, contents: &[u8]) {
+        let path = dir.join(&entry.path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, contents).unwrap();
+        if let Some(constants) = &entry.constants_path {
+            fs::write(dir.join(constants), b"instruments: []\n").unwrap();
+        }
+    }
+
+    #[test]
+    fn test_sha256_file() {
+        let dir = temp_cache_dir("sha256");
+        let path = dir.join("abc.txt");
+        fs::write(&path, b"abc").unwrap();
+        assert_eq!(
+            sha256_file(&path).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_resolve_offline_and_verify_checksum() {
+        let dir = temp_cache_dir("resolve");
+        let mut registry = ModelRegistry::new(&dir).unwrap();
+        assert_eq!(registry.list().len(), 3);
+        assert!(registry.available().is_empty());
+        assert!(registry.resolve("alphapeptdeep_generic_rt").is_err());
+
+        let rt = registry.get("alphapeptdeep_generic_rt").unwrap().clone();
+        write_model(&dir, &rt, b"rt weights");
+        let resolved = registry.resolve("alphapeptdeep_generic_rt").unwrap();
+        assert_eq!(resolved.model_path, dir.join(&rt.path));
+
+        // Pinned checksums survive a round trip through the manifest
+        registry.pin_checksums().unwrap();
+        registry.save_manifest().unwrap();
+        let registry = ModelRegistry::new(&dir).unwrap();
+        assert!(registry.get("alphapeptdeep_generic_rt").unwrap().sha256.is_some());
+        assert!(registry.get("alphapeptdeep_generic_ms2").unwrap().sha256.is_none());
+        assert!(registry.resolve("alphapeptdeep_generic_rt").is_ok());
+
+        write_model(&dir, &rt, b"tampered weights");
+        assert!(registry.resolve("alphapeptdeep_generic_rt").is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_verify_sha256() {
+        let dir = temp_cache_dir("verify");
+        let path = dir.join("abc.txt");
+        fs::write(&path, b"abc").unwrap();
+        let sha256 = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        assert!(verify_sha256(&path, None).is_ok());
+        assert!(verify_sha256(&path, Some(sha256)).is_ok());
+        assert!(verify_sha256(&path, Some(&sha256.to_uppercase())).is_ok());
+
+        // A truncated file does not verify
+        fs::write(&path, b"ab").unwrap();
+        assert!(verify_sha256(&path, Some(sha256)).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_find_by_arch_and_instrument() {
+        let registry = ModelRegistry::new(temp_cache_dir("find")).unwrap();
+        assert_eq!(
+            registry.find("ms2_bert", Some("lumos")).map(|e| e.name.as_str()),
+            Some("alphapeptdeep_generic_ms2")
+        );
+        assert!(registry.find("ms2_bert", Some("Astral")).is_none());
+        assert!(registry.find("rt_cnn_lstm", Some("Astral")).is_some());
+        assert!(registry.find("charge_cnn_lstm", None).is_none());
+    }
+}
@@ -1,4 +1,4 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use candle_core::{DType, Device, IndexOp, Tensor};
 use candle_nn::{Dropout, Module, VarBuilder, VarMap};
 use std::fmt;
@@ -8,17 +8,16 @@ use std::{collections::HashMap, sync::Arc};
 use crate::{
     building_blocks::building_blocks::{
         DecoderLinear, HiddenHfaceTransformer, Input26aaModPositionalEncoding, MetaEmbedding,
-        ModLossNN, MOD_FEATURE_SIZE,
+        ModLossNN,
     },
     models::model_interface::{
         create_var_map, load_tensors_from_model, ModelInterface, PropertyType,
     },
+    utils::fragment_types::{default_fragment_types, FragmentType},
     utils::peptdeep_utils::{load_mod_to_feature_arc, parse_model_constants, ModelConstants},
 };
 
 // Constants
-const CHARGE_FACTOR: f64 = 0.1;
-const NCE_FACTOR: f64 = 0.01;
 
 // Main Model Struct
 #[derive(Clone)]
@@ -35,6 +34,8 @@ pub struct MS2BertModel {
     num_modloss_types: usize,
     // If True, the modloss layer will be disabled, by default True
     mask_modloss: bool,
+    // Fragment type of each output channel, non-modloss types first
+    fragment_types: Vec<FragmentType>,
     min_inten: f32,
     device: Device,
     is_training: bool,
@@ -43,34 +44,25 @@ pub struct MS2BertModel {
     meta_nn: MetaEmbedding,
     hidden_nn: HiddenHfaceTransformer,
     output_nn: DecoderLinear,
-    modloss_nn: ModLossNN,
+    // Only loaded when the modloss branch is enabled
+    modloss_nn: Option<ModLossNN>,
 }
 
 // Automatically implement Send and Sync if all fields are Send and Sync
 unsafe impl Send for MS2BertModel {}
 unsafe impl Sync for MS2BertModel {}
 
-// Code Model Implementation
-impl ModelInterface for MS2BertModel {
-    fn property_type(&self) -> PropertyType {
-        PropertyType::MS2
-    }
-
-    fn model_arch(&self) -> &'static str {
-        "ms2_bert"
-    }
-
-    fn new_untrained(_device: Device) -> Result<Self> {
-        unimplemented!("Untrained model creation is not implemented for this architecture.");
-    }
-
-    /// Create a new MS2BERT model from the given model and constants files.
-    fn new<P: AsRef<Path>>(
+impl MS2BertModel {
+    /// Create a new MS2BERT model predicting the given fragment types.
+    ///
+    /// The output head predicts the non-modloss fragment types and the modloss head the modloss
+    /// fragment types, so the checkpoint's heads must match the number of each. Output channels are
+    /// ordered with non-modloss types first, see [`ModelInterface::get_fragment_types`].
+    pub fn new_with_fragment_types<P: AsRef<Path>>(
         model_path: P,
         constants_path: Option<P>,
         fixed_sequence_len: usize,
-        num_frag_types: usize,
-        num_modloss_types: usize,
+        fragment_types: Vec<FragmentType>,
         mask_modloss: bool,
         device: Device,
     ) -> Result<Self> {
@@ -79,13 +71,47 @@ impl ModelInterface for MS2BertModel {
         let mut varmap = VarMap::new();
         create_var_map(&mut varmap, tensor_data, &device)?;
 
-        let var_store = VarBuilder::from_varmap(&varmap, DType::F32, &device);
-
         let constants = match constants_path {
             Some(path) => parse_model_constants(path.as_ref().to_str().unwrap())?,
             None => ModelConstants::default(),
         };
 
+        Self::from_varmap(
+            varmap,
+            constants,
+            fixed_sequence_len,
+            fragment_types,
+            mask_modloss,
+            device,
+        )
+    }
+
+    /// Build the model from the AlphaPeptDeep tensor names in the given varmap.
+    ///
+    /// Tensors missing from the varmap are created, so an empty varmap gives the model's full
+    /// tensor layout.
+    pub(crate) fn from_varmap(
+        varmap: VarMap,
+        constants: ModelConstants,
+        fixed_sequence_len: usize,
+        fragment_types: Vec<FragmentType>,
+        mask_modloss: bool,
+        device: Device,
+    ) -> Result<Self> {
+        if fragment_types.is_empty() {
+            return Err(anyhow!("At least one fragment type is required"));
+        }
+
+        // The output head predicts the regular fragment types, the modloss head the modloss ones
+        let (mut fragment_types, modloss_types): (Vec<_>, Vec<_>) =
+            fragment_types.into_iter().partition(|f| !f.is_modloss());
+        let num_output_types = fragment_types.len();
+        let num_modloss_types = modloss_types.len();
+        fragment_types.extend(modloss_types);
+        let num_frag_types = fragment_types.len();
+
+        let var_store = VarBuilder::from_varmap(&varmap, DType::F32, &device);
+
         // Load the mod_to_feature mapping
         let mod_to_feature = load_mod_to_feature_arc(&constants)?;
 
@@ -94,6 +120,8 @@ impl ModelInterface for MS2BertModel {
         let meta_dim = 8;
         let input_nn = Input26aaModPositionalEncoding::from_varstore(
             &var_store,
+            constants.get_mod_feature_size(),
+            constants.get_aa_embedding_size(),
             256 - 8,
             200,
             vec![
@@ -106,6 +134,7 @@ impl ModelInterface for MS2BertModel {
 
         let meta_nn = MetaEmbedding::from_varstore(
             &var_store,
+            constants.max_instrument_num,
             8,
             vec!["meta_nn.nn.weight", "meta_nn.nn.bias"],
         )
@@ -125,34 +154,36 @@ impl ModelInterface for MS2BertModel {
         let output_nn = DecoderLinear::from_varstore(
             &var_store,
             256,
-            4,
+            num_output_types,
             vec![
                 "output_nn.nn.0.weight",
                 "output_nn.nn.1.weight",
                 "output_nn.nn.2.weight",
             ],
             vec!["output_nn.nn.0.bias", "output_nn.nn.2.bias"],
-        )
-        .unwrap();
+        )?;
 
-        let modloss_nn = ModLossNN::from_varstore(
-            var_store.clone(),
-            256,
-            4,
-            8,
-            1,
-            0.1,
-            false,
-            4,
-            "modloss_nn.0.bert",
-            vec![
-                "modloss_nn.1.nn.0.weight",
-                "modloss_nn.1.nn.1.weight",
-                "modloss_nn.1.nn.2.weight",
-            ],
-            vec!["modloss_nn.1.nn.0.bias", "modloss_nn.1.nn.2.bias"],
-        )
-        .unwrap();
+        let modloss_nn = if num_modloss_types > 0 && !mask_modloss {
+            Some(ModLossNN::from_varstore(
+                var_store.clone(),
+                256,
+                4,
+                8,
+                1,
+                0.1,
+                false,
+                num_modloss_types,
+                "modloss_nn.0.bert",
+                vec![
+                    "modloss_nn.1.nn.0.weight",
+                    "modloss_nn.1.nn.1.weight",
+                    "modloss_nn.1.nn.2.weight",
+                ],
+                vec!["modloss_nn.1.nn.0.bias", "modloss_nn.1.nn.2.bias"],
+            )?)
+        } else {
+            None
+        };
 
         Ok(Self {
             var_store: var_store,
@@ -163,6 +194,7 @@ impl ModelInterface for MS2BertModel {
             num_frag_types: num_frag_types,
             num_modloss_types: num_modloss_types,
             mask_modloss: mask_modloss,
+            fragment_types,
             min_inten: 1e-4,
             device,
             is_training: false,
@@ -174,6 +206,57 @@ impl ModelInterface for MS2BertModel {
             modloss_nn: modloss_nn,
         })
     }
+}
+
+// Code Model Implementation
+impl ModelInterface for MS2BertModel {
+    fn property_type(&self) -> PropertyType {
+        PropertyType::MS2
+    }
+
+    fn model_arch(&self) -> &'static str {
+        "ms2_bert"
+    }
+
+    fn new_untrained(_device: Device) -> Result<Self> {
+        unimplemented!("Untrained model creation is not implemented for this architecture.");
+    }
+
+    /// Create a new MS2BERT model from the given model and constants files.
+    ///
+    /// The output channels are the default AlphaPeptDeep fragment types (b/y ions and their modloss
+    /// ions at charges 1 and 2), so `num_frag_types` and `num_modloss_types` must be 8 and 4. Use
+    /// [`MS2BertModel::new_with_fragment_types`] for other fragment types.
+    fn new<P: AsRef<Path>>(
+        model_path: P,
+        constants_path: Option<P>,
+        fixed_sequence_len: usize,
+        num_frag_types: usize,
+        num_modloss_types: usize,
+        mask_modloss: bool,
+        device: Device,
+    ) -> Result<Self> {
+        let fragment_types = default_fragment_types();
+        let default_modloss_types = fragment_types.iter().filter(|f| f.is_modloss()).count();
+        if num_frag_types != fragment_types.len() || num_modloss_types != default_modloss_types {
+            return Err(anyhow!(
+                "Expected {} fragment types ({} modloss) for the default fragment types, got {} ({} modloss). Use MS2BertModel::new_with_fragment_types for custom fragment types.",
+                fragment_types.len(),
+                default_modloss_types,
+                num_frag_types,
+                num_modloss_types
+            ));
+        }
+
+        Self::new_with_fragment_types(
+            model_path,
+            constants_path,
+            fixed_sequence_len,
+            fragment_types,
+            mask_modloss,
+            device,
+        )
+    }
 
     fn forward(&self, xs: &Tensor) -> Result<Tensor, candle_core::Error> {
         let (_batch_size, seq_len, _) = xs.shape().dims3()?;
@@ -181,14 +264,15 @@ impl ModelInterface for MS2BertModel {
         // Separate the input tensor into the different parts
 
         // Calculate starting indices
+        let mod_feature_size = self.constants.get_mod_feature_size();
         let start_mod_x = 1;
-        let start_charge = start_mod_x + MOD_FEATURE_SIZE;
+        let start_charge = start_mod_x + mod_feature_size;
         let start_nce = start_charge + 1;
         let start_instrument = start_nce + 1;
 
         // Extract tensors using indexing
         let aa_indices_out = xs.i((.., .., 0))?;
-        let mod_x_out = xs.i((.., .., start_mod_x..start_mod_x + MOD_FEATURE_SIZE))?;
+        let mod_x_out = xs.i((.., .., start_mod_x..start_mod_x + mod_feature_size))?;
         let charge_out = xs.i((.., 0..1, start_charge..start_charge + 1))?;
         let nce_out = xs.i((.., 0..1, start_nce..start_nce + 1))?;
         let instrument_out = xs.i((.., 0..1, start_instrument..start_instrument + 1))?;
@@ -272,8 +356,8 @@ impl ModelInterface for MS2BertModel {
         // }
 
         // Apply dropout and combine with input
-        let x_tmp = (hidden_x + combined_input * 0.2)?;
-        let hidden_output = self.dropout.forward(&x_tmp, true)?;
+        let x_tmp = (hidden_x + (&combined_input * 0.2)?)?;
+        let hidden_output = self.dropout.forward(&x_tmp, self.is_training)?;
         log::trace!(
             "[MS2BertModel::forward] hidden_output shape: {:?}, device: {:?}",
             hidden_output.shape(),
@@ -302,23 +386,21 @@ impl ModelInterface for MS2BertModel {
                 // Concatenate along the last dimension
                 out_x = Tensor::cat(&[out_x, zeros_tensor], 2)?;
             } else {
-                // // Forward pass through the first modloss neural network
-                // let modloss_output = self.modloss_nn[0].forward(in_x)?;
-
-                // // // If output attentions is enabled, save them
-                // // if self.output_attentions {
-                // //     self.modloss_attentions = Some(modloss_output.clone()); // Assuming you want to store a clone
-                // // }
-
-                // // Add hidden_x to the first output
-                // let modloss_combined = &modloss_output + hidden_x;
-
-                // // Forward pass through the last modloss neural network
-                // let modloss_final = self.modloss_nn.last().unwrap().forward(&modloss_combined)?;
+                let modloss_nn = self.modloss_nn.as_ref().ok_or_else(|| {
+                    candle_core::Error::Msg("modloss_nn is not loaded".to_string())
+                })?;
+
+                // modloss_nn[0](in_x) + hidden_x, as in AlphaPeptDeep
+                let modloss_x = (modloss_nn.forward_transformer(&combined_input)? + &hidden_output)?;
+                let modloss_x = modloss_nn.forward_decoder(&modloss_x)?;
+                log::trace!(
+                    "[MS2BertModel::forward] modloss_x shape: {:?}, device: {:?}",
+                    modloss_x.shape(),
+                    modloss_x.device()
+                );
 
-                // // Concatenate the outputs along the last dimension
-                // out_x = Tensor::cat(&[out_x, modloss_final], 2)?;
-                todo!();
+                // Concatenate along the last dimension
+                out_x = Tensor::cat(&[out_x, modloss_x], 2)?;
             }
         }
 
@@ -354,6 +436,10 @@ impl ModelInterface for MS2BertModel {
         self.constants.mod_elements.len()
     }
 
+    fn get_model_constants(&self) -> &ModelConstants {
+        &self.constants
+    }
+
     fn get_mod_to_feature(&self) -> &HashMap<Arc<[u8]>, Vec<f32>> {
         &self.mod_to_feature
     }
@@ -362,6 +448,10 @@ impl ModelInterface for MS2BertModel {
         self.min_inten
     }
 
+    fn get_fragment_types(&self) -> &[FragmentType] {
+        &self.fragment_types
+    }
+
     fn get_mut_varmap(&mut self) -> &mut VarMap {
         &mut self.varmap
     }
@@ -440,6 +530,72 @@ mod tests {
     use candle_core::Device;
     use std::path::PathBuf;
 
+    #[test]
+    fn test_untrained_modloss_channels() {
+        use crate::utils::fragment_types::get_charged_frag_types;
+
+        // b and y ions and b modloss ions at charge 1: two output channels and one modloss channel
+        let fragment_types = get_charged_frag_types(&["b", "y", "b_modloss"], 1).unwrap();
+        let num_modloss_types = fragment_types.iter().filter(|f| f.is_modloss()).count();
+        let num_output_types = fragment_types.len() - num_modloss_types;
+        assert_eq!((num_output_types, num_modloss_types), (2, 1));
+
+        // Both models share the same weights, one with the modloss head and one masking it
+        let varmap = VarMap::new();
+        let model = MS2BertModel::from_varmap(
+            varmap.clone(),
+            ModelConstants::default(),
+            0,
+            fragment_types.clone(),
+            false,
+            Device::Cpu,
+        )
+        .unwrap();
+        let masked = MS2BertModel::from_varmap(
+            varmap.clone(),
+            ModelConstants::default(),
+            0,
+            fragment_types,
+            true,
+            Device::Cpu,
+        )
+        .unwrap();
+        // Missing tensors are created as zeros, so give the weights random values
+        for var in varmap.all_vars() {
+            var.set(&Tensor::randn(0f32, 0.1, var.shape(), &Device::Cpu).unwrap()).unwrap();
+        }
+        assert!(model.modloss_nn.is_some());
+        assert!(masked.modloss_nn.is_none());
+
+        let peptides: Vec<Arc<[u8]>> = vec![Arc::from(b"AGHCEWQMKYR".to_vec().into_boxed_slice())];
+        let mods: Vec<Arc<[u8]>> = vec![Arc::from(b"Oxidation@M".to_vec().into_boxed_slice())];
+        let mod_sites: Vec<Arc<[u8]>> = vec![Arc::from(b"8".to_vec().into_boxed_slice())];
+        let input_tensor = model
+            .encode_peptides(
+                &peptides,
+                &mods,
+                &mod_sites,
+                Some(vec![2]),
+                Some(vec![20]),
+                Some(vec![Some(Arc::from(b"QE".to_vec().into_boxed_slice()))]),
+            )
+            .unwrap();
+
+        let output: Vec<Vec<Vec<f32>>> = model.forward(&input_tensor).unwrap().to_vec3().unwrap();
+        let masked_output: Vec<Vec<Vec<f32>>> = masked.forward(&input_tensor).unwrap().to_vec3().unwrap();
+
+        // One row per fragmentation site, with the output channels followed by the modloss channels
+        assert_eq!(output[0].len(), 10);
+        for (row, masked_row) in output[0].iter().zip(masked_output[0].iter()) {
+            assert_eq!(row.len(), num_output_types + num_modloss_types);
+            assert_eq!(row[..num_output_types], masked_row[..num_output_types]);
+            assert_eq!(masked_row[num_output_types], 0.0);
+        }
+        // The modloss head produces its own predictions instead of zeros
+        assert!(output[0].iter().any(|row| row[num_output_types] != 0.0));
+        assert!(output[0].iter().any(|row| row[0] != 0.0));
+    }
+
     #[test]
     fn test_parse_model_constants() {
         let path = "data/models/alphapeptdeep/generic/ms2.pth.model_const.yaml";
@@ -1,6 +1,7 @@
-use crate::models::model_interface::{ModelInterface, PredictionResult};
+use crate::models::model_interface::{ModelInterface, PredictionResult, PredictionUncertainty};
 use crate::models::ms2_bert_model::MS2BertModel;
 use crate::utils::data_handling::PeptideData;
+use crate::utils::fragment_types::{fragment_labels, FragmentType};
 use crate::utils::peptdeep_utils::ModificationMap;
 use anyhow::{anyhow, Result};
 use candle_core::{Device, Tensor};
@@ -54,6 +55,43 @@ impl MS2ModelWrapper {
         Ok(Self { model })
     }
 
+    /// Create an MS2 model predicting the given fragment types, e.g. c/z ions for ETD spectra.
+    ///
+    /// Modloss fragment types are predicted by the modloss head, which is only run when
+    /// `mask_modloss` is false; masked modloss channels are predicted as zero.
+    pub fn new_with_fragment_types<P: AsRef<Path>>(
+        model_path: P,
+        constants_path: P,
+        arch: &str,
+        fragment_types: Vec<FragmentType>,
+        mask_modloss: bool,
+        device: Device,
+    ) -> Result<Self> {
+        let model: Box<dyn ModelInterface> = match arch {
+            "ms2_bert" => Box::new(MS2BertModel::new_with_fragment_types(
+                model_path,
+                Some(constants_path),
+                0,
+                fragment_types,
+                mask_modloss,
+                device,
+            )?),
+            _ => return Err(anyhow!("Unsupported MS2 model architecture: {}", arch)),
+        };
+
+        Ok(Self { model })
+    }
+
+    /// Fragment type of each predicted intensity channel, in channel order.
+    pub fn fragment_types(&self) -> &[FragmentType] {
+        self.model.get_fragment_types()
+    }
+
+    /// Column label of each predicted intensity channel, e.g. `b_z1` or `y_modloss_z2`.
+    pub fn fragment_labels(&self) -> Vec<String> {
+        fragment_labels(self.model.get_fragment_types())
+    }
+
     pub fn predict(
         &self,
         peptide_sequence: &[Arc<[u8]>],
@@ -73,6 +111,29 @@ impl MS2ModelWrapper {
         )
     }
 
+    /// Predict fragment intensities with Monte-Carlo dropout, returning the per-fragment mean and
+    /// standard deviation.
+    pub fn predict_with_uncertainty(
+        &self,
+        peptide_sequence: &[Arc<[u8]>],
+        mods: &[Arc<[u8]>],
+        mod_sites: &[Arc<[u8]>],
+        charge: Vec<i32>,
+        nce: Vec<i32>,
+        instrument: Vec<Option<Arc<[u8]>>>,
+        n_samples: usize,
+    ) -> Result<PredictionUncertainty> {
+        self.model.predict_with_uncertainty(
+            peptide_sequence,
+            mods,
+            mod_sites,
+            Some(charge),
+            Some(nce),
+            Some(instrument),
+            n_samples,
+        )
+    }
+
     pub fn fine_tune(
         &mut self,
         training_data: &Vec<PeptideData>,
@@ -6,7 +6,7 @@ use std::path::Path;
 use std::sync::Arc;
 
 use crate::building_blocks::building_blocks::{
-    DecoderLinear, Encoder26aaModCnnLstmAttnSum, MOD_FEATURE_SIZE,
+    DecoderLinear, Encoder26aaModCnnLstmAttnSum,
 };
 use crate::models::model_interface::{
     create_var_map, load_tensors_from_model, ModelInterface, PropertyType,
@@ -36,42 +36,14 @@ pub struct RTCNNLSTMModel {
 unsafe impl Send for RTCNNLSTMModel {}
 unsafe impl Sync for RTCNNLSTMModel {}
 
-// Core Model Implementation
-
-impl ModelInterface for RTCNNLSTMModel {
-    fn property_type(&self) -> PropertyType {
-        PropertyType::RT
-    }
-
-    fn model_arch(&self) -> &'static str {
-        "rt_cnn_lstm"
-    }
-
-    fn new_untrained(_device: Device) -> Result<Self> {
-        unimplemented!("Untrained model creation is not implemented for this architecture.");
-    }
-
-    /// Create a new RTCNNLSTMModel from the given model and constants files.
-    fn new<P: AsRef<Path>>(
-        model_path: P,
-        constants_path: Option<P>,
-        _fixed_sequence_len: usize,
-        _num_frag_types: usize,
-        _num_modloss_types: usize,
-        _mask_modloss: bool,
-        device: Device,
-    ) -> Result<Self> {
-        let tensor_data = load_tensors_from_model(model_path.as_ref(), &device)?;
-
-        let mut varmap = candle_nn::VarMap::new();
-        create_var_map(&mut varmap, tensor_data, &device)?;
+impl RTCNNLSTMModel {
+    /// Build the encoder and decoder from the AlphaPeptDeep tensor names in the given varmap.
+    ///
+    /// Tensors missing from the varmap are created, so an empty varmap gives the model's full
+    /// tensor layout.
+    pub(crate) fn from_varmap(varmap: VarMap, constants: ModelConstants, device: Device) -> Result<Self> {
         let var_store = candle_nn::VarBuilder::from_varmap(&varmap, DType::F32, &device);
 
-        let constants = match constants_path {
-            Some(path) => parse_model_constants(path.as_ref().to_str().unwrap())?,
-            None => ModelConstants::default(),
-        };
-
         // Load the mod_to_feature mapping
         let mod_to_feature = load_mod_to_feature_arc(&constants)?;
 
@@ -80,6 +52,8 @@ impl ModelInterface for RTCNNLSTMModel {
 
         let rt_encoder = Encoder26aaModCnnLstmAttnSum::from_varstore(
             &var_store,
+            constants.get_mod_feature_size(),
+            constants.get_aa_embedding_size(),
             8,
             128,
             2,
@@ -121,9 +95,48 @@ impl ModelInterface for RTCNNLSTMModel {
             dropout,
             rt_encoder,
             rt_decoder,
-            is_training: true,
+            is_training: false,
         })
     }
+}
+
+// Core Model Implementation
+
+impl ModelInterface for RTCNNLSTMModel {
+    fn property_type(&self) -> PropertyType {
+        PropertyType::RT
+    }
+
+    fn model_arch(&self) -> &'static str {
+        "rt_cnn_lstm"
+    }
+
+    fn new_untrained(_device: Device) -> Result<Self> {
+        unimplemented!("Untrained model creation is not implemented for this architecture.");
+    }
+
+    /// Create a new RTCNNLSTMModel from the given model and constants files.
+    fn new<P: AsRef<Path>>(
+        model_path: P,
+        constants_path: Option<P>,
+        _fixed_sequence_len: usize,
+        _num_frag_types: usize,
+        _num_modloss_types: usize,
+        _mask_modloss: bool,
+        device: Device,
+    ) -> Result<Self> {
+        let tensor_data = load_tensors_from_model(model_path.as_ref(), &device)?;
+
+        let mut varmap = candle_nn::VarMap::new();
+        create_var_map(&mut varmap, tensor_data, &device)?;
+
+        let constants = match constants_path {
+            Some(path) => parse_model_constants(path.as_ref().to_str().unwrap())?,
+            None => ModelConstants::default(),
+        };
+
+        Self::from_varmap(varmap, constants, device)
+    }
 
     fn forward(&self, xs: &Tensor) -> Result<Tensor, candle_core::Error> {
         let (_batch_size, _seq_len, _) = xs.shape().dims3()?;
@@ -131,7 +144,7 @@ impl ModelInterface for RTCNNLSTMModel {
         let aa_indices_out = xs.i((.., .., 0))?;
         let (mean, min, max) = get_tensor_stats(&aa_indices_out)?;
         log::debug!("[RTCNNLSTMModel] aa_indices_out stats - min: {min}, max: {max}, mean: {mean}");
-        let mod_x_out = xs.i((.., .., 1..1 + MOD_FEATURE_SIZE))?;
+        let mod_x_out = xs.i((.., .., 1..1 + self.constants.get_mod_feature_size()))?;
 
         let x = self.rt_encoder.forward(&aa_indices_out, &mod_x_out)?;
 
@@ -173,6 +186,10 @@ impl ModelInterface for RTCNNLSTMModel {
         self.constants.mod_elements.len()
     }
 
+    fn get_model_constants(&self) -> &ModelConstants {
+        &self.constants
+    }
+
     fn get_mod_to_feature(&self) -> &HashMap<Arc<[u8]>, Vec<f32>> {
         &self.mod_to_feature
     }
@@ -344,6 +361,27 @@ mod tests {
         println!("{:?}", tensor_data);
     }
 
+    #[test]
+    fn test_from_varmap_starts_in_evaluation_mode() {
+        let varmap = VarMap::new();
+        let model = RTCNNLSTMModel::from_varmap(varmap.clone(), ModelConstants::default(), Device::Cpu).unwrap();
+        assert!(!model.is_training);
+
+        // Missing tensors are created as zeros, so give the weights random values
+        for var in varmap.all_vars() {
+            var.set(&Tensor::randn(0f32, 0.1, var.shape(), &Device::Cpu).unwrap()).unwrap();
+        }
+
+        // Dropout is disabled, so repeated predictions are identical
+        let peptides: Vec<Arc<[u8]>> = vec![Arc::from(b"AGHCEWQMKYR".to_vec().into_boxed_slice())];
+        let mods: Vec<Arc<[u8]>> = vec![Arc::from(b"Oxidation@M".to_vec().into_boxed_slice())];
+        let mod_sites: Vec<Arc<[u8]>> = vec![Arc::from(b"8".to_vec().into_boxed_slice())];
+        let input = model.encode_peptides(&peptides, &mods, &mod_sites, None, None, None).unwrap();
+        let first: Vec<f32> = model.forward(&input).unwrap().to_vec1().unwrap();
+        let second: Vec<f32> = model.forward(&input).unwrap().to_vec1().unwrap();
+        assert_eq!(first, second);
+    }
+
     #[test]
     fn test_parse_model_constants() {
         let path = "data/models/alphapeptdeep/generic/rt.pth.model_const.yaml";
@@ -6,7 +6,7 @@ use std::path::Path;
 use std::sync::Arc;
 
 use crate::building_blocks::building_blocks::{
-    DecoderLinear, Encoder26aaModCnnTransformerAttnSum, MOD_FEATURE_SIZE,
+    DecoderLinear, Encoder26aaModCnnTransformerAttnSum,
 };
 use crate::models::model_interface::{ModelInterface, PropertyType, load_tensors_from_model, create_var_map};
 use crate::utils::peptdeep_utils::{
@@ -51,10 +51,13 @@ impl ModelInterface for RTCNNTFModel {
     fn new_untrained(device: Device) -> Result<Self> {
         let mut varmap = VarMap::new();
         let varbuilder = VarBuilder::from_varmap(&varmap, DType::F32, &device);
+        let constants = ModelConstants::default();
 
         log::trace!("[RTCNNTFModel] Initializing rt_encoder");
         let rt_encoder = Encoder26aaModCnnTransformerAttnSum::new(
             &varbuilder.pp("rt_encoder"),
+            constants.get_mod_feature_size(),
+            constants.get_aa_embedding_size(),
             8,     // mod_hidden_dim
             128,   // hidden_dim
             256,   // ff_dim
@@ -67,7 +70,6 @@ impl ModelInterface for RTCNNTFModel {
 
         log::trace!("[RTCNNTFModel] Initializing rt_decoder");
         let rt_decoder = DecoderLinear::new(128, 1, &varbuilder.pp("rt_decoder"))?;
-        let constants = ModelConstants::default();
         let mod_to_feature = load_mod_to_feature_arc(&constants)?;
 
         Ok(Self {
@@ -109,6 +111,8 @@ impl ModelInterface for RTCNNTFModel {
 
         let rt_encoder = Encoder26aaModCnnTransformerAttnSum::from_varstore(
             &var_store,
+            constants.get_mod_feature_size(),
+            constants.get_aa_embedding_size(),
             8,      // mod_hidden_dim
             128,    // hidden_dim
             256,    // ff_dim
@@ -141,7 +145,7 @@ impl ModelInterface for RTCNNTFModel {
             vec!["rt_decoder.nn.0.bias", "rt_decoder.nn.2.bias"]
         )?;
 
-        Ok(Self {
+        let mut model = Self {
             var_store,
             varmap,
             constants,
@@ -151,14 +155,16 @@ impl ModelInterface for RTCNNTFModel {
             rt_encoder,
             rt_decoder,
             is_training: false,
-        })
+        };
+        model.set_evaluation_mode();
+        Ok(model)
     }
 
     fn forward(&self, xs: &Tensor) -> Result<Tensor, candle_core::Error> {
         let aa_indices_out = xs.i((.., .., 0))?;
         let (mean, min, max) = get_tensor_stats(&aa_indices_out)?;
         log::debug!("[RTCNNTFModel] aa_indices_out stats - min: {min}, max: {max}, mean: {mean}");
-        let mod_x_out = xs.i((.., .., 1..1 + MOD_FEATURE_SIZE))?;    
+        let mod_x_out = xs.i((.., .., 1..1 + self.constants.get_mod_feature_size()))?;    
 
         let x = self.rt_encoder.forward(&aa_indices_out, &mod_x_out)?;
         
@@ -174,12 +180,14 @@ impl ModelInterface for RTCNNTFModel {
     fn set_evaluation_mode(&mut self) {
         // println!("Setting evaluation mode");
         self.is_training = false;
+        self.rt_encoder.set_training(false);
     }
 
     /// Set model to training mode for training
     /// This enables dropout and other training-specific layers.
     fn set_training_mode(&mut self) {
         self.is_training = true;
+        self.rt_encoder.set_training(true);
     }
 
     fn get_property_type(&self) -> String {
@@ -198,6 +206,10 @@ impl ModelInterface for RTCNNTFModel {
         self.constants.mod_elements.len()
     }
 
+    fn get_model_constants(&self) -> &ModelConstants {
+        &self.constants
+    }
+
     fn get_mod_to_feature(&self) -> &HashMap<Arc<[u8]>, Vec<f32>> {
         &self.mod_to_feature
     }
@@ -1,10 +1,14 @@
 // rt_model.rs
 
-use crate::models::model_interface::{ModelInterface, PredictionResult};
+use crate::models::ensemble::{EnsembleModel, ENSEMBLE_ARCH};
+use crate::models::model_interface::{
+    ModelInterface, PredictionResult, PredictionUncertainty, PropertyType,
+};
 use crate::models::rt_cnn_lstm_model::RTCNNLSTMModel;
 use crate::models::rt_cnn_transformer_model::RTCNNTFModel;
 use crate::utils::data_handling::{PeptideData, TargetNormalization};
 use crate::utils::peptdeep_utils::ModificationMap;
+use crate::utils::rt_calibration::{CalibrationMethod, RTCalibration};
 use crate::utils::stats::TrainingStepMetrics;
 use anyhow::{anyhow, Result};
 use candle_core::{Device, Tensor};
@@ -21,7 +25,7 @@ pub enum RTModelArch {
 }
 
 // Constants for different types of retention time models
-pub const RTMODEL_ARCHS: &[&str] = &["rt_cnn_lstm", "rt_cnn_tf"];
+pub const RTMODEL_ARCHS: &[&str] = &["rt_cnn_lstm", "rt_cnn_tf", ENSEMBLE_ARCH];
 
 // A wrapper struct for RT models
 pub struct RTModelWrapper {
@@ -62,6 +66,15 @@ impl RTModelWrapper {
                 true,
                 device,
             )?),
+            // `model_path` is the ensemble's bundle manifest
+            ENSEMBLE_ARCH => {
+                let ensemble =
+                    EnsembleModel::new(model_path, constants_path, 0, 8, 4, true, device)?;
+                if !matches!(ensemble.property_type(), PropertyType::RT) {
+                    return Err(anyhow!("Ensemble does not predict RT"));
+                }
+                Box::new(ensemble)
+            }
             _ => return Err(anyhow!("Unsupported RT model architecture: {}", arch)),
         };
 
@@ -78,6 +91,43 @@ impl RTModelWrapper {
             .predict(peptide_sequence, mods, mod_sites, None, None, None)
     }
 
+    /// Fit a calibration from this model's predicted RTs onto the observed RTs of anchor peptides,
+    /// e.g. iRT peptides or high-confidence PSMs of a run.
+    pub fn calibrate(
+        &self,
+        peptide_sequence: &[Arc<[u8]>],
+        mods: &[Arc<[u8]>],
+        mod_sites: &[Arc<[u8]>],
+        observed_rts: &[f32],
+        method: CalibrationMethod,
+    ) -> Result<RTCalibration> {
+        match self.predict(peptide_sequence, mods, mod_sites)? {
+            PredictionResult::RTResult(predicted) => {
+                RTCalibration::fit(&predicted, observed_rts, method)
+            }
+            _ => Err(anyhow!("Expected RT predictions for calibration anchors")),
+        }
+    }
+
+    /// Predict retention times with Monte-Carlo dropout, returning the per-peptide mean and standard deviation.
+    pub fn predict_with_uncertainty(
+        &self,
+        peptide_sequence: &[Arc<[u8]>],
+        mods: &[Arc<[u8]>],
+        mod_sites: &[Arc<[u8]>],
+        n_samples: usize,
+    ) -> Result<PredictionUncertainty> {
+        self.model.predict_with_uncertainty(
+            peptide_sequence,
+            mods,
+            mod_sites,
+            None,
+            None,
+            None,
+            n_samples,
+        )
+    }
+
     pub fn train(
         &mut self,
         training_data: &Vec<PeptideData>,
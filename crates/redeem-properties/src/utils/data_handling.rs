@@ -1,7 +1,11 @@
 
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
-#[derive(Debug, Clone, Copy)]
+use crate::utils::peptdeep_utils::IonMobilityParams;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum TargetNormalization {
     ZScore(f32, f32),     // mean, std
     MinMax(f32, f32),     // min, max
@@ -26,13 +30,15 @@ pub struct PeptideData {
     pub mods: Arc<[u8]>,         // e.g., "Any_N-term;Oxidation@M"
     pub mod_sites: Arc<[u8]>,    // e.g., "0;1"
     pub charge: Option<i32>,
-    pub precursor_mass: Option<f32>,
+    pub precursor_mass: Option<f32>, // precursor m/z, used to convert between CCS and ion mobility
     pub nce: Option<i32>,
     pub instrument: Option<Arc<[u8]>>,
     pub retention_time: Option<f32>,
     pub ion_mobility: Option<f32>,
     pub ccs: Option<f32>,
     pub ms2_intensities: Option<Vec<Vec<f32>>>,
+    pub detectability: Option<f32>, // 1.0 if observed, 0.0 if not; predicted probability after inference
+    pub charge_distribution: Option<Vec<f32>>, // fraction of observations at charges 1 to 6
 }
 
 impl PeptideData {
@@ -63,6 +69,8 @@ impl PeptideData {
             ion_mobility,
             ccs,
             ms2_intensities,
+            detectability: None,
+            charge_distribution: None,
         }
     }
 
@@ -87,6 +95,30 @@ impl PeptideData {
             .as_ref()
             .map(|v| std::str::from_utf8(v).unwrap_or(""))
     }
+
+    /// Ion mobility (1/K0) of the peptide's CCS, using `precursor_mass` as the precursor m/z.
+    ///
+    /// Returns `None` if the CCS, charge or precursor m/z is missing.
+    pub fn ccs_to_ion_mobility(&self, params: &IonMobilityParams) -> Option<f32> {
+        match (self.ccs, self.charge, self.precursor_mass) {
+            (Some(ccs), Some(charge), Some(mz)) => {
+                Some(params.ccs_to_mobility(ccs as f64, charge as f64, mz as f64) as f32)
+            }
+            _ => None,
+        }
+    }
+
+    /// CCS of the peptide's ion mobility (1/K0), using `precursor_mass` as the precursor m/z.
+    ///
+    /// Returns `None` if the ion mobility, charge or precursor m/z is missing.
+    pub fn ion_mobility_to_ccs(&self, params: &IonMobilityParams) -> Option<f32> {
+        match (self.ion_mobility, self.charge, self.precursor_mass) {
+            (Some(mobility), Some(charge), Some(mz)) => {
+                Some(params.mobility_to_ccs(mobility as f64, charge as f64, mz as f64) as f32)
+            }
+            _ => None,
+        }
+    }
 }
 
 pub struct PeptideBatchData {
@@ -101,6 +133,8 @@ pub struct PeptideBatchData {
     pub ion_mobilities: Vec<Option<f32>>,
     pub ccs: Vec<Option<f32>>,
     pub ms2_intensities: Vec<Option<Vec<Vec<f32>>>>,
+    pub detectabilities: Vec<Option<f32>>,
+    pub charge_distributions: Vec<Option<Vec<f32>>>,
 }
 
 impl From<&[PeptideData]> for PeptideBatchData {
@@ -117,6 +151,8 @@ impl From<&[PeptideData]> for PeptideBatchData {
             ion_mobilities: slice.iter().map(|p| p.ion_mobility).collect(),
             ccs: slice.iter().map(|p| p.ccs).collect(),
             ms2_intensities: slice.iter().map(|p| p.ms2_intensities.clone()).collect(),
+            detectabilities: slice.iter().map(|p| p.detectability).collect(),
+            charge_distributions: slice.iter().map(|p| p.charge_distribution.clone()).collect(),
         }
     }
 }
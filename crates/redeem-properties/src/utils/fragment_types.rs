@@ -0,0 +1,215 @@
+use anyhow::{anyhow, Result};
+use std::fmt;
+use std::str::FromStr;
+
+/// Ion series of a peptide backbone fragment.
+///
+/// `a`, `b` and `c` ions carry the N-terminus, `x`, `y` and `z` ions the C-terminus. HCD spectra are
+/// dominated by b/y ions, while ETD/EThcD spectra mostly contain c/z ions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IonSeries {
+    A,
+    B,
+    C,
+    X,
+    Y,
+    Z,
+}
+
+impl IonSeries {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IonSeries::A => "a",
+            IonSeries::B => "b",
+            IonSeries::C => "c",
+            IonSeries::X => "x",
+            IonSeries::Y => "y",
+            IonSeries::Z => "z",
+        }
+    }
+
+    /// Whether the fragment contains the peptide N-terminus.
+    pub fn is_n_terminal(&self) -> bool {
+        matches!(self, IonSeries::A | IonSeries::B | IonSeries::C)
+    }
+}
+
+/// Neutral loss of a fragment ion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NeutralLoss {
+    /// Loss of water.
+    H2O,
+    /// Loss of ammonia.
+    NH3,
+    /// Modification specific loss, e.g. the H3PO4 loss of phosphorylated residues.
+    ModLoss,
+}
+
+impl NeutralLoss {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NeutralLoss::H2O => "H2O",
+            NeutralLoss::NH3 => "NH3",
+            NeutralLoss::ModLoss => "modloss",
+        }
+    }
+}
+
+/// A charged fragment type, i.e. one intensity channel of an MS2 prediction.
+///
+/// Labels follow the AlphaPeptDeep convention: `<series>[_<loss>]_z<charge>`, e.g. `b_z1`,
+/// `y_modloss_z2` or `c_z1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FragmentType {
+    pub series: IonSeries,
+    pub loss: Option<NeutralLoss>,
+    pub charge: u8,
+}
+
+impl FragmentType {
+    pub fn new(series: IonSeries, loss: Option<NeutralLoss>, charge: u8) -> Self {
+        Self {
+            series,
+            loss,
+            charge,
+        }
+    }
+
+    /// Whether this channel is predicted by the modloss head of an MS2 model.
+    pub fn is_modloss(&self) -> bool {
+        self.loss == Some(NeutralLoss::ModLoss)
+    }
+
+    /// Column label of this fragment type, e.g. `y_modloss_z2`.
+    pub fn label(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl fmt::Display for FragmentType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.loss {
+            Some(loss) => write!(f, "{}_{}_z{}", self.series.as_str(), loss.as_str(), self.charge),
+            None => write!(f, "{}_z{}", self.series.as_str(), self.charge),
+        }
+    }
+}
+
+impl FromStr for IonSeries {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "a" => Ok(IonSeries::A),
+            "b" => Ok(IonSeries::B),
+            "c" => Ok(IonSeries::C),
+            "x" => Ok(IonSeries::X),
+            "y" => Ok(IonSeries::Y),
+            "z" => Ok(IonSeries::Z),
+            _ => Err(anyhow!("Unknown ion series: {}", s)),
+        }
+    }
+}
+
+impl FromStr for NeutralLoss {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "H2O" => Ok(NeutralLoss::H2O),
+            "NH3" => Ok(NeutralLoss::NH3),
+            "modloss" => Ok(NeutralLoss::ModLoss),
+            _ => Err(anyhow!("Unknown neutral loss: {}", s)),
+        }
+    }
+}
+
+impl FromStr for FragmentType {
+    type Err = anyhow::Error;
+
+    /// Parse a charged fragment type label such as `b_z1` or `y_modloss_z2`.
+    fn from_str(s: &str) -> Result<Self> {
+        let (frag_type, charge) = s
+            .rsplit_once("_z")
+            .ok_or_else(|| anyhow!("Fragment type label is missing its charge: {}", s))?;
+        let charge: u8 = charge
+            .parse()
+            .map_err(|_| anyhow!("Invalid charge in fragment type label: {}", s))?;
+        if charge == 0 {
+            return Err(anyhow!("Fragment charge must be positive: {}", s));
+        }
+
+        let (series, loss) = match frag_type.split_once('_') {
+            Some((series, loss)) => (series, Some(loss.parse()?)),
+            None => (frag_type, None),
+        };
+
+        Ok(FragmentType::new(series.parse()?, loss, charge))
+    }
+}
+
+/// Expand uncharged fragment types (e.g. `["b", "y", "b_modloss"]`) into charged fragment types
+/// from charge 1 to `max_frag_charge`, in the same order as AlphaBase's `get_charged_frag_types`.
+pub fn get_charged_frag_types(frag_types: &[&str], max_frag_charge: u8) -> Result<Vec<FragmentType>> {
+    let mut charged = Vec::with_capacity(frag_types.len() * max_frag_charge as usize);
+    for frag_type in frag_types {
+        for charge in 1..=max_frag_charge {
+            charged.push(format!("{}_z{}", frag_type, charge).parse()?);
+        }
+    }
+    Ok(charged)
+}
+
+/// Fragment types of the pretrained AlphaPeptDeep MS2 model: b/y ions and their modloss ions at
+/// charges 1 and 2.
+pub fn default_fragment_types() -> Vec<FragmentType> {
+    get_charged_frag_types(&["b", "y", "b_modloss", "y_modloss"], 2)
+        .expect("default fragment types are valid")
+}
+
+/// Fragment types for ETD spectra: c/z ions at charges 1 and 2.
+pub fn etd_fragment_types() -> Vec<FragmentType> {
+    get_charged_frag_types(&["c", "z"], 2).expect("ETD fragment types are valid")
+}
+
+/// Column labels of the given fragment types.
+pub fn fragment_labels(fragment_types: &[FragmentType]) -> Vec<String> {
+    fragment_types.iter().map(|f| f.label()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_fragment_type_labels() {
+        assert_eq!(
+            fragment_labels(&default_fragment_types()),
+            vec![
+                "b_z1",
+                "b_z2",
+                "y_z1",
+                "y_z2",
+                "b_modloss_z1",
+                "b_modloss_z2",
+                "y_modloss_z1",
+                "y_modloss_z2",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_fragment_type_label() {
+        let frag: FragmentType = "y_modloss_z2".parse().unwrap();
+        assert_eq!(frag, FragmentType::new(IonSeries::Y, Some(NeutralLoss::ModLoss), 2));
+        assert!(frag.is_modloss());
+
+        let frag: FragmentType = "c_z1".parse().unwrap();
+        assert_eq!(frag, FragmentType::new(IonSeries::C, None, 1));
+        assert_eq!(frag.label(), "c_z1");
+
+        assert!("b".parse::<FragmentType>().is_err());
+        assert!("q_z1".parse::<FragmentType>().is_err());
+        assert!("b_z0".parse::<FragmentType>().is_err());
+    }
+}
@@ -2,4 +2,6 @@ pub mod peptdeep_utils;
 pub mod logging;
 pub mod utils;
 pub mod data_handling;
-pub mod stats;
\ No newline at end of file
+pub mod stats;
+pub mod fragment_types;
+pub mod rt_calibration;
\ No newline at end of file
@@ -1,40 +1,25 @@
 use anyhow::{Context, Result, Error};
-use std::fs::File;
 use std::ops::Index;
 use std::path::PathBuf;
-use std::io;
-use std::fs;
 use std::sync::Arc;
-use log::info;
 use csv::ReaderBuilder;
-use reqwest;
 use regex::Regex;
 use std::collections::HashMap;
 use serde::Deserialize;
-use zip::ZipArchive;
 use once_cell::sync::Lazy;
 
-const MODIFICATIONS_TSV_BYTES: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"),"/assets/modification.tsv"));
-
+#[cfg(feature = "download")]
+use crate::models::model_registry::ModelRegistry;
 
-const PRETRAINED_MODELS_URL: &str = "https://github.com/singjc/redeem/releases/download/v0.1.0-alpha/peptdeep_generic_pretrained_models.zip";
-const PRETRAINED_MODELS_ZIP: &str = "data/peptdeep_generic_pretrained_models.zip";
-const PRETRAINED_MODELS_PATH: &str = "data/peptdeep_generic_pretrained_models";
+const MODIFICATIONS_TSV_BYTES: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"),"/assets/modification.tsv"));
 
 
 // Constants and Utility Structs
 
-const INSTRUMENT_DICT: &[(&str, usize)] = &[
-    ("QE", 0),
-    ("LUMOS", 1),
-    ("TIMSTOF", 2),
-    ("SCIEXTOF", 3),
-    ("THERMOTOF", 4),
-];
-
-const MAX_INSTRUMENT_NUM: usize = 8;
-
-const UNKNOWN_INSTRUMENT_NUM: usize = MAX_INSTRUMENT_NUM - 1;
+/// Fallbacks for featurization constants that older constants files may omit.
+const DEFAULT_AA_EMBEDDING_SIZE: usize = 27;
+const DEFAULT_CHARGE_FACTOR: f32 = 0.1;
+const DEFAULT_NCE_FACTOR: f32 = 0.01;
 
 
 #[derive(Debug, Clone)]
@@ -119,8 +104,8 @@ struct ModFeature {
 impl Default for ModelConstants {
     fn default() -> Self {
         Self {
-            aa_embedding_size: Some(27),
-            charge_factor: Some(0.1),
+            aa_embedding_size: Some(DEFAULT_AA_EMBEDDING_SIZE),
+            charge_factor: Some(DEFAULT_CHARGE_FACTOR),
             instruments: vec![
                 "QE".into(),
                 "Lumos".into(),
@@ -142,11 +127,46 @@ impl Default for ModelConstants {
             .into_iter()
             .map(String::from)
             .collect(),
-            nce_factor: Some(0.01),
+            nce_factor: Some(DEFAULT_NCE_FACTOR),
         }
     }
 }
 
+impl ModelConstants {
+    /// Number of amino acid classes in the one-hot / embedding input.
+    pub fn get_aa_embedding_size(&self) -> usize {
+        self.aa_embedding_size.unwrap_or(DEFAULT_AA_EMBEDDING_SIZE)
+    }
+
+    /// Length of the per-residue modification feature vector (one slot per mod element).
+    pub fn get_mod_feature_size(&self) -> usize {
+        self.mod_elements.len()
+    }
+
+    /// Scale applied to the precursor charge before it is fed to the model.
+    pub fn get_charge_factor(&self) -> f32 {
+        self.charge_factor.unwrap_or(DEFAULT_CHARGE_FACTOR)
+    }
+
+    /// Scale applied to the NCE before it is fed to the model.
+    pub fn get_nce_factor(&self) -> f32 {
+        self.nce_factor.unwrap_or(DEFAULT_NCE_FACTOR)
+    }
+
+    /// Index of `instrument` in the model's instrument list (case-insensitive).
+    ///
+    /// Unknown instruments, and instruments listed beyond `max_instrument_num`, map to the
+    /// last instrument slot, as in AlphaPeptDeep.
+    pub fn get_instrument_index(&self, instrument: &str) -> usize {
+        let unknown = self.max_instrument_num.saturating_sub(1);
+        self.instruments
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(instrument))
+            .filter(|&index| index < self.max_instrument_num)
+            .unwrap_or(unknown)
+    }
+}
+
 /// Parse the model constants from a YAML file.
 pub fn parse_model_constants(path: &str) -> Result<ModelConstants> {
     let f = std::fs::File::open(path).map_err(|e| Error::msg(e.to_string()))?;
@@ -526,69 +546,87 @@ pub fn get_modification_string(
 }
 
 
-pub fn download_pretrained_models_exist() -> Result<PathBuf, io::Error> {
-    let zip_path = PathBuf::from(PRETRAINED_MODELS_ZIP);
-    let extract_dir = PathBuf::from(PRETRAINED_MODELS_PATH);
+/// Download the AlphaPeptDeep generic pretrained models into the model cache if they are
+/// missing, and return the cache directory.
+///
+/// The cache directory is `REDEEM_MODEL_DIR`, or `data/models` if it isn't set. See
+/// [`ModelRegistry`] to resolve pretrained models without network access.
+#[cfg(feature = "download")]
+pub fn download_pretrained_models_exist() -> Result<PathBuf> {
+    let registry = ModelRegistry::from_env()?;
+    registry.fetch()?;
+    Ok(registry.cache_dir().to_path_buf())
+}
 
-    // Ensure the parent directory exists
-    if let Some(parent) = zip_path.parent() {
-        fs::create_dir_all(parent)?;
-    }
+/// Index of `instrument` in the default AlphaPeptDeep instrument list.
+///
+/// Models should use [`ModelConstants::get_instrument_index`] so custom instrument lists are respected.
+pub fn parse_instrument_index(instrument: &str) -> usize {
+    ModelConstants::default().get_instrument_index(instrument)
+}
 
-    // Download the zip file if it doesn't exist
-    if !zip_path.exists() {
-        info!("Downloading pretrained models...");
-        let mut response = reqwest::blocking::get(PRETRAINED_MODELS_URL)
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
-        let mut file = File::create(&zip_path)?;
-        io::copy(&mut response, &mut file)?;
-    }
 
-    // Unzip the file if the target directory doesn't exist
-    if !extract_dir.exists() {
-        info!("Unzipping pretrained models...");
-        let file = File::open(&zip_path)?;
-        let mut archive = ZipArchive::new(file)?;
 
-        for i in 0..archive.len() {
-            let mut file = archive.by_index(i)?;
-            let outpath = extract_dir.join(file.mangled_name());
+/// Default mass of the drift gas (N2) used for CCS and mobility conversion.
+pub const DEFAULT_IM_GAS_MASS: f64 = 28.0;
+/// Default coefficient of the Mason-Schamp equation for Bruker (timsTOF) instruments.
+pub const DEFAULT_CCS_IM_COEF: f64 = 1059.62245;
 
-            if file.name().ends_with('/') {
-                // Create directory
-                fs::create_dir_all(&outpath)?;
-            } else {
-                // Write file
-                if let Some(parent) = outpath.parent() {
-                    fs::create_dir_all(parent)?;
-                }
-                let mut outfile = File::create(&outpath)?;
-                io::copy(&mut file, &mut outfile)?;
-            }
+/// Parameters of the Mason-Schamp conversion between CCS and reduced ion mobility (1/K0).
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct IonMobilityParams {
+    /// Mass of the drift gas.
+    pub gas_mass: f64,
+    /// Conversion coefficient between CCS and 1/K0.
+    pub ccs_im_coef: f64,
+}
+
+impl Default for IonMobilityParams {
+    fn default() -> Self {
+        Self {
+            gas_mass: DEFAULT_IM_GAS_MASS,
+            ccs_im_coef: DEFAULT_CCS_IM_COEF,
         }
     }
-
-    Ok(extract_dir)
 }
 
-pub fn parse_instrument_index(instrument: &str) -> usize {
-    let upper_instrument = instrument.to_uppercase();
-    
-    INSTRUMENT_DICT.iter()
-        .find(|&&(name, _)| name == upper_instrument)
-        .map_or(UNKNOWN_INSTRUMENT_NUM, |&(_, index)| index)
-}
+impl IonMobilityParams {
+    pub fn new(gas_mass: f64, ccs_im_coef: f64) -> Self {
+        Self {
+            gas_mass,
+            ccs_im_coef,
+        }
+    }
 
+    /// Reduced mass of the precursor ion and the drift gas.
+    ///
+    /// reduced_mass = (precursor_mz * charge * gas_mass) / (precursor_mz * charge + gas_mass)
+    pub fn reduced_mass(&self, precursor_mz: f64, charge: f64) -> f64 {
+        let reduced_mass = precursor_mz * charge;
+        reduced_mass * self.gas_mass / (reduced_mass + self.gas_mass)
+    }
 
+    /// Convert a CCS value to ion mobility (1/K0).
+    ///
+    /// mobility = (ccs_value * sqrt(reduced_mass)) / (charge * ccs_im_coef)
+    pub fn ccs_to_mobility(&self, ccs_value: f64, charge: f64, precursor_mz: f64) -> f64 {
+        let reduced_mass = self.reduced_mass(precursor_mz, charge);
+        ccs_value * f64::sqrt(reduced_mass) / (charge * self.ccs_im_coef)
+    }
 
-// TODO: Derive from PeptDep constants yaml
-const IM_GAS_MASS: f64 = 28.0; 
-const CCS_IM_COEF: f64 = 1059.62245; 
+    /// Convert an ion mobility (1/K0) value to CCS.
+    ///
+    /// ccs_value = (mobility * charge * ccs_im_coef) / sqrt(reduced_mass)
+    pub fn mobility_to_ccs(&self, mobility: f64, charge: f64, precursor_mz: f64) -> f64 {
+        let reduced_mass = self.reduced_mass(precursor_mz, charge);
+        (mobility * charge * self.ccs_im_coef) / f64::sqrt(reduced_mass)
+    }
+}
 
 /// Calculates the reduced mass for CCS and mobility calculation.
 ///
-/// This function computes the reduced mass using the formula:
-/// reduced_mass = (precursor_mz * charge * IM_GAS_MASS) / (precursor_mz * charge + IM_GAS_MASS)
+/// This function computes the reduced mass with the default [`IonMobilityParams`] using the formula:
+/// reduced_mass = (precursor_mz * charge * DEFAULT_IM_GAS_MASS) / (precursor_mz * charge + DEFAULT_IM_GAS_MASS)
 ///
 /// # Arguments
 ///
@@ -599,14 +637,13 @@ const CCS_IM_COEF: f64 = 1059.62245;
 ///
 /// The calculated reduced mass as a f64 value
 pub fn get_reduced_mass(precursor_mz: f64, charge: f64) -> f64 {
-    let reduced_mass = precursor_mz * charge;
-    reduced_mass * IM_GAS_MASS / (reduced_mass + IM_GAS_MASS)
+    IonMobilityParams::default().reduced_mass(precursor_mz, charge)
 }
 
 /// Converts CCS (Collision Cross Section) to mobility for Bruker (timsTOF) instruments.
 ///
-/// This function calculates the mobility using the formula:
-/// mobility = (ccs_value * sqrt(reduced_mass)) / (charge * CCS_IM_COEF)
+/// This function calculates the mobility with the default [`IonMobilityParams`] using the formula:
+/// mobility = (ccs_value * sqrt(reduced_mass)) / (charge * DEFAULT_CCS_IM_COEF)
 ///
 /// # Arguments
 ///
@@ -618,15 +655,14 @@ pub fn get_reduced_mass(precursor_mz: f64, charge: f64) -> f64 {
 ///
 /// The calculated mobility as a f64 value
 pub fn ccs_to_mobility_bruker(ccs_value: f64, charge: f64, precursor_mz: f64) -> f64 {
-    let reduced_mass = get_reduced_mass(precursor_mz, charge);
-    ccs_value * f64::sqrt(reduced_mass) / (charge * CCS_IM_COEF)
+    IonMobilityParams::default().ccs_to_mobility(ccs_value, charge, precursor_mz)
 }
 
 
 /// Converts mobility to CCS (Collision Cross Section) for Bruker (timsTOF) instruments.
 /// 
-/// This function calculates the CCS using the formula:
-/// ccs_value = (mobility * charge * CCS_IM_COEF) / sqrt(reduced_mass)
+/// This function calculates the CCS with the default [`IonMobilityParams`] using the formula:
+/// ccs_value = (mobility * charge * DEFAULT_CCS_IM_COEF) / sqrt(reduced_mass)
 /// 
 /// # Arguments
 /// 
@@ -638,8 +674,7 @@ pub fn ccs_to_mobility_bruker(ccs_value: f64, charge: f64, precursor_mz: f64) ->
 /// 
 /// The calculated CCS value as a f64
 pub fn ion_mobility_to_ccs_bruker(mobility: f64, charge: i32, precursor_mz: f64) -> f32 {
-    let reduced_mass = get_reduced_mass(precursor_mz, charge as f64);
-    ((mobility * (charge as f64) * CCS_IM_COEF) / f64::sqrt(reduced_mass)) as f32
+    IonMobilityParams::default().mobility_to_ccs(mobility, charge as f64, precursor_mz) as f32
 }
 
 
@@ -649,11 +684,87 @@ mod tests {
     use regex::Regex;
 
     #[test]
+    #[cfg(feature = "download")]
+    #[ignore = "requires network access"]
     fn test_ensure_pretrained_models_exist() {
         let result = download_pretrained_models_exist();
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_instrument_index_from_constants() {
+        let defaults = ModelConstants::default();
+        assert_eq!(defaults.get_instrument_index("Lumos"), 1);
+        assert_eq!(defaults.get_instrument_index("TIMSTOF"), 2);
+        assert_eq!(defaults.get_instrument_index("Astral"), 7);
+        assert_eq!(parse_instrument_index("qe"), 0);
+
+        let custom = ModelConstants {
+            instruments: vec!["Astral".into(), "QE".into()],
+            max_instrument_num: 4,
+            ..ModelConstants::default()
+        };
+        assert_eq!(custom.get_instrument_index("astral"), 0);
+        assert_eq!(custom.get_instrument_index("QE"), 1);
+        assert_eq!(custom.get_instrument_index("Lumos"), 3);
+    }
+
+    #[test]
+    fn test_model_from_non_default_constants() {
+        use crate::models::model_interface::ModelInterface;
+        use crate::models::ms2_bert_model::MS2BertModel;
+        use crate::utils::fragment_types::default_fragment_types;
+        use candle_core::Device;
+        use candle_nn::VarMap;
+
+        let custom = ModelConstants {
+            mod_elements: ["C", "H", "N", "O", "P", "S"].iter().map(|e| e.to_string()).collect(),
+            max_instrument_num: 4,
+            ..ModelConstants::default()
+        };
+
+        let peptides: Vec<Arc<[u8]>> = vec![Arc::from(b"AGHCEWQMKYR".to_vec().into_boxed_slice())];
+        let mods: Vec<Arc<[u8]>> = vec![Arc::from(b"Oxidation@M".to_vec().into_boxed_slice())];
+        let mod_sites: Vec<Arc<[u8]>> = vec![Arc::from(b"8".to_vec().into_boxed_slice())];
+        let instruments = Some(vec![Some(Arc::from(b"QE".to_vec().into_boxed_slice()))]);
+
+        let mut widths = Vec::new();
+        let mut meta_shapes = Vec::new();
+        for constants in [ModelConstants::default(), custom.clone()] {
+            let mut model = MS2BertModel::from_varmap(
+                VarMap::new(),
+                constants.clone(),
+                0,
+                default_fragment_types(),
+                true,
+                Device::Cpu,
+            )
+            .unwrap();
+            let input = model
+                .encode_peptides(&peptides, &mods, &mod_sites, Some(vec![2]), Some(vec![20]), instruments.clone())
+                .unwrap();
+            let (_, seq_len, width) = input.shape().dims3().unwrap();
+
+            // Amino acid index, modification elements, charge, NCE and instrument
+            assert_eq!(width, 1 + constants.mod_elements.len() + 3);
+            assert_eq!(model.forward(&input).unwrap().shape().dims3().unwrap().1, seq_len - 3);
+
+            // Oxygen is an element of both, so the oxidation is encoded at the modified site
+            let oxygen = 1 + constants.mod_elements.iter().position(|e| e == "O").unwrap();
+            let features: Vec<Vec<Vec<f32>>> = input.to_vec3().unwrap();
+            assert_eq!(features[0][8][oxygen], 1.0);
+
+            widths.push(width);
+            let data = model.get_mut_varmap().data().lock().unwrap();
+            meta_shapes.push(data["meta_nn.nn.weight"].as_tensor().dims().to_vec());
+        }
+
+        assert_eq!(widths[0] - widths[1], ModelConstants::default().mod_elements.len() - 6);
+        // The instrument one-hot encoding and the NCE are the inputs of the metadata embedding
+        assert_eq!(meta_shapes[0][1], ModelConstants::default().max_instrument_num + 1);
+        assert_eq!(meta_shapes[1][1], custom.max_instrument_num + 1);
+    }
+
     #[test]
     fn test_extract_unimod_annotations() {
         let peptide = "AC(UniMod:4)DE(UniMod:7)FG";
@@ -800,4 +911,18 @@ mod tests {
 
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_ion_mobility_params_round_trip() {
+        let params = IonMobilityParams::new(4.0, 1100.0);
+        let (ccs, charge, precursor_mz) = (450.0, 2.0, 762.329553);
+
+        let mobility = params.ccs_to_mobility(ccs, charge, precursor_mz);
+        assert!((params.mobility_to_ccs(mobility, charge, precursor_mz) - ccs).abs() < 1e-9);
+
+        // A lighter drift gas gives a different mobility than the N2 defaults
+        let default_mobility = IonMobilityParams::default().ccs_to_mobility(ccs, charge, precursor_mz);
+        assert!((mobility - default_mobility).abs() > 1e-3);
+        assert!(default_mobility > 0.5 && default_mobility < 1.5);
+    }
+
+}
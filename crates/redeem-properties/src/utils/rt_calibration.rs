@@ -0,0 +1,484 @@
+// rt_calibration.rs
+
+use anyhow::{anyhow, Result};
+use std::fmt;
+
+use crate::models::model_interface::PredictionResult;
+
+/// Biognosys iRT kit peptides and their iRT values, commonly spiked in as calibration anchors.
+pub const IRT_PEPTIDES: &[(&str, f32)] = &[
+    ("LGGNEQVTR", -24.92),
+    ("GAGSSEPVTGLDAK", 0.00),
+    ("VEATFGVDESNAK", 12.39),
+    ("YILAGVENSK", 19.79),
+    ("TPVISGGPYEYR", 28.71),
+    ("TPVITGAPYEYR", 33.38),
+    ("DGLDAASYYAPVR", 42.26),
+    ("ADVTPADFSEWSK", 54.62),
+    ("GTFIIDPGGVIR", 70.52),
+    ("GTFIIDPAAVIR", 87.23),
+    ("LFLQFGAQGSPFLK", 100.00),
+];
+
+/// Method used to map predicted RTs onto observed RTs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CalibrationMethod {
+    /// Least squares line.
+    Linear,
+    /// Locally weighted linear regression, using the `frac` nearest anchors for each fit and
+    /// `iterations` robustifying passes that down-weight outlying anchors.
+    Lowess { frac: f32, iterations: usize },
+    /// Continuous piecewise linear least squares fit with `segments` segments, whose breakpoints
+    /// are placed at quantiles of the predicted RTs.
+    Piecewise { segments: usize },
+}
+
+impl CalibrationMethod {
+    /// LOWESS with the usual defaults (2/3 of the anchors per local fit, 3 robustifying iterations).
+    pub fn lowess() -> Self {
+        CalibrationMethod::Lowess { frac: 2.0 / 3.0, iterations: 3 }
+    }
+}
+
+/// Summary statistics of the residuals (observed - calibrated) of a calibration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResidualStats {
+    pub n: usize,
+    pub mean: f32,
+    pub std: f32,
+    /// Mean absolute error.
+    pub mae: f32,
+    pub rmse: f32,
+    pub median_abs: f32,
+    /// 95th percentile of the absolute residuals, e.g. for a run-specific extraction window.
+    pub q95_abs: f32,
+    /// Coefficient of determination of the calibrated against the observed RTs.
+    pub r_squared: f32,
+}
+
+impl ResidualStats {
+    /// Compute residual statistics of `calibrated` against `observed` RTs.
+    pub fn compute(calibrated: &[f32], observed: &[f32]) -> Result<Self> {
+        if calibrated.len() != observed.len() {
+            return Err(anyhow!(
+                "Got {} calibrated but {} observed RTs",
+                calibrated.len(),
+                observed.len()
+            ));
+        }
+        if observed.is_empty() {
+            return Err(anyhow!("Cannot compute residual statistics without any RTs"));
+        }
+
+        let n = observed.len() as f64;
+        let residuals: Vec<f64> = observed
+            .iter()
+            .zip(calibrated)
+            .map(|(&o, &c)| o as f64 - c as f64)
+            .collect();
+
+        let mean = residuals.iter().sum::<f64>() / n;
+        let std = (residuals.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / n).sqrt();
+        let mae = residuals.iter().map(|r| r.abs()).sum::<f64>() / n;
+        let sse = residuals.iter().map(|r| r * r).sum::<f64>();
+        let rmse = (sse / n).sqrt();
+
+        let mut abs_residuals: Vec<f64> = residuals.iter().map(|r| r.abs()).collect();
+        abs_residuals.sort_by(|a, b| a.total_cmp(b));
+
+        let observed_mean = observed.iter().map(|&o| o as f64).sum::<f64>() / n;
+        let sst = observed
+            .iter()
+            .map(|&o| (o as f64 - observed_mean).powi(2))
+            .sum::<f64>();
+        let r_squared = if sst > 0.0 { 1.0 - sse / sst } else { 1.0 };
+
+        Ok(Self {
+            n: observed.len(),
+            mean: mean as f32,
+            std: std as f32,
+            mae: mae as f32,
+            rmse: rmse as f32,
+            median_abs: quantile(&abs_residuals, 0.5) as f32,
+            q95_abs: quantile(&abs_residuals, 0.95) as f32,
+            r_squared: r_squared as f32,
+        })
+    }
+}
+
+impl fmt::Display for ResidualStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "n = {}, mean = {:.4}, std = {:.4}, MAE = {:.4}, RMSE = {:.4}, median |res| = {:.4}, 95% |res| = {:.4}, R² = {:.4}",
+            self.n, self.mean, self.std, self.mae, self.rmse, self.median_abs, self.q95_abs, self.r_squared
+        )
+    }
+}
+
+/// Fitted calibration curve.
+#[derive(Debug, Clone)]
+enum CalibrationCurve {
+    Linear { slope: f64, intercept: f64 },
+    /// Linear interpolation between knots sorted by x, extrapolated with the outer segments.
+    Knots { xs: Vec<f64>, ys: Vec<f64> },
+}
+
+impl CalibrationCurve {
+    fn apply(&self, x: f64) -> f64 {
+        match self {
+            CalibrationCurve::Linear { slope, intercept } => slope * x + intercept,
+            CalibrationCurve::Knots { xs, ys } => {
+                if xs.len() == 1 {
+                    return ys[0];
+                }
+                // Index of the segment containing x, clamped to the outer segments
+                let i = xs.partition_point(|&k| k <= x).clamp(1, xs.len() - 1);
+                let (x0, x1, y0, y1) = (xs[i - 1], xs[i], ys[i - 1], ys[i]);
+                y0 + (x - x0) * (y1 - y0) / (x1 - x0)
+            }
+        }
+    }
+}
+
+/// Mapping from predicted RTs onto a run's observed RTs (or an iRT scale), fitted on anchor peptides.
+///
+/// # Example
+/// ```ignore
+/// let calibration = RTCalibration::fit(&anchor_predicted, &anchor_observed, CalibrationMethod::lowess())?;
+/// log::info!("Anchor residuals: {}", calibration.residual_stats());
+/// let calibrated = calibration.apply_all(&predicted);
+/// ```
+#[derive(Debug, Clone)]
+pub struct RTCalibration {
+    method: CalibrationMethod,
+    curve: CalibrationCurve,
+    residual_stats: ResidualStats,
+}
+
+impl RTCalibration {
+    /// Fit a calibration between the `predicted` and `observed` RTs of anchor peptides.
+    pub fn fit(predicted: &[f32], observed: &[f32], method: CalibrationMethod) -> Result<Self> {
+        if predicted.len() != observed.len() {
+            return Err(anyhow!(
+                "Got {} predicted but {} observed anchor RTs",
+                predicted.len(),
+                observed.len()
+            ));
+        }
+        if predicted.iter().chain(observed).any(|v| !v.is_finite()) {
+            return Err(anyhow!("Anchor RTs must be finite"));
+        }
+
+        // Sort anchors by predicted RT
+        let mut anchors: Vec<(f64, f64)> = predicted
+            .iter()
+            .zip(observed)
+            .map(|(&p, &o)| (p as f64, o as f64))
+            .collect();
+        anchors.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let xs: Vec<f64> = anchors.iter().map(|a| a.0).collect();
+        let ys: Vec<f64> = anchors.iter().map(|a| a.1).collect();
+
+        let distinct = xs.windows(2).filter(|w| w[1] > w[0]).count() + usize::from(!xs.is_empty());
+        if distinct < 2 {
+            return Err(anyhow!(
+                "At least two anchors with distinct predicted RTs are required, got {}",
+                distinct
+            ));
+        }
+
+        let curve = match method {
+            CalibrationMethod::Linear => {
+                let (slope, intercept) = linear_fit(&xs, &ys, None);
+                CalibrationCurve::Linear { slope, intercept }
+            }
+            CalibrationMethod::Lowess { frac, iterations } => {
+                if !(frac > 0.0 && frac <= 1.0) {
+                    return Err(anyhow!("LOWESS fraction must be in (0, 1], got {}", frac));
+                }
+                lowess_fit(&xs, &ys, frac as f64, iterations)
+            }
+            CalibrationMethod::Piecewise { segments } => {
+                if segments == 0 {
+                    return Err(anyhow!("Piecewise calibration requires at least one segment"));
+                }
+                if distinct < segments + 1 {
+                    return Err(anyhow!(
+                        "Piecewise calibration with {} segments requires at least {} anchors with distinct predicted RTs, got {}",
+                        segments,
+                        segments + 1,
+                        distinct
+                    ));
+                }
+                piecewise_fit(&xs, &ys, segments)?
+            }
+        };
+
+        let calibrated: Vec<f32> = predicted.iter().map(|&p| curve.apply(p as f64) as f32).collect();
+        let residual_stats = ResidualStats::compute(&calibrated, observed)?;
+
+        Ok(Self { method, curve, residual_stats })
+    }
+
+    pub fn method(&self) -> CalibrationMethod {
+        self.method
+    }
+
+    /// Residual statistics of the calibration on its anchor peptides.
+    pub fn residual_stats(&self) -> &ResidualStats {
+        &self.residual_stats
+    }
+
+    /// Map a single predicted RT onto the calibrated scale.
+    pub fn apply(&self, predicted: f32) -> f32 {
+        self.curve.apply(predicted as f64) as f32
+    }
+
+    /// Map predicted RTs onto the calibrated scale.
+    pub fn apply_all(&self, predicted: &[f32]) -> Vec<f32> {
+        predicted.iter().map(|&p| self.apply(p)).collect()
+    }
+
+    /// Map an RT prediction result onto the calibrated scale.
+    pub fn apply_to_prediction(&self, prediction: &PredictionResult) -> Result<PredictionResult> {
+        match prediction {
+            PredictionResult::RTResult(rts) => Ok(PredictionResult::RTResult(self.apply_all(rts))),
+            _ => Err(anyhow!("RT calibration can only be applied to RT predictions")),
+        }
+    }
+
+    /// Residual statistics of the calibration on held-out peptides.
+    pub fn evaluate(&self, predicted: &[f32], observed: &[f32]) -> Result<ResidualStats> {
+        ResidualStats::compute(&self.apply_all(predicted), observed)
+    }
+}
+
+/// Quantile of sorted values, with linear interpolation.
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    let pos = q * (sorted.len() - 1) as f64;
+    let (lo, hi) = (pos.floor() as usize, pos.ceil() as usize);
+    sorted[lo] + (pos - lo as f64) * (sorted[hi] - sorted[lo])
+}
+
+/// Weighted least squares line, returns (slope, intercept).
+fn linear_fit(xs: &[f64], ys: &[f64], weights: Option<&[f64]>) -> (f64, f64) {
+    let w = |i: usize| weights.map_or(1.0, |w| w[i]);
+    let sum_w: f64 = (0..xs.len()).map(w).sum();
+    if sum_w <= 0.0 {
+        return (0.0, ys.iter().sum::<f64>() / ys.len() as f64);
+    }
+    let mean_x = (0..xs.len()).map(|i| w(i) * xs[i]).sum::<f64>() / sum_w;
+    let mean_y = (0..xs.len()).map(|i| w(i) * ys[i]).sum::<f64>() / sum_w;
+    let sxx: f64 = (0..xs.len()).map(|i| w(i) * (xs[i] - mean_x).powi(2)).sum();
+    let sxy: f64 = (0..xs.len()).map(|i| w(i) * (xs[i] - mean_x) * (ys[i] - mean_y)).sum();
+    // Degenerate neighbourhoods (all x equal) fall back to a weighted mean
+    let slope = if sxx > f64::EPSILON * sum_w { sxy / sxx } else { 0.0 };
+    (slope, mean_y - slope * mean_x)
+}
+
+/// LOWESS smoothing of anchors sorted by x, evaluated at each distinct x.
+///
+/// Based on Cleveland (1979): tricube-weighted local lines over the nearest `frac * n` anchors,
+/// followed by `iterations` bisquare robustifying passes.
+fn lowess_fit(xs: &[f64], ys: &[f64], frac: f64, iterations: usize) -> CalibrationCurve {
+    let n = xs.len();
+    let k = ((frac * n as f64).ceil() as usize).clamp(2, n);
+    let mut robustness = vec![1.0; n];
+    let mut fitted = vec![0.0; n];
+
+    for iteration in 0..=iterations {
+        for i in 0..n {
+            // Window of the k nearest anchors to xs[i]
+            let mut lo = i.saturating_sub(k - 1);
+            let mut hi = lo + k - 1;
+            if hi >= n {
+                hi = n - 1;
+                lo = n - k;
+            }
+            while lo < i && hi + 1 < n && xs[i] - xs[lo] > xs[hi + 1] - xs[i] {
+                lo += 1;
+                hi += 1;
+            }
+            while hi > i && lo > 0 && xs[hi] - xs[i] > xs[i] - xs[lo - 1] {
+                lo -= 1;
+                hi -= 1;
+            }
+            let max_dist = (xs[i] - xs[lo]).max(xs[hi] - xs[i]);
+
+            let weights: Vec<f64> = (lo..=hi)
+                .map(|j| {
+                    let tricube = if max_dist > 0.0 {
+                        (1.0 - ((xs[j] - xs[i]).abs() / max_dist).powi(3)).powi(3)
+                    } else {
+                        1.0
+                    };
+                    tricube * robustness[j]
+                })
+                .collect();
+            let (slope, intercept) = linear_fit(&xs[lo..=hi], &ys[lo..=hi], Some(&weights));
+            fitted[i] = slope * xs[i] + intercept;
+        }
+
+        if iteration == iterations {
+            break;
+        }
+
+        // Bisquare weights from the residuals, scaled by six median absolute residuals
+        let residuals: Vec<f64> = (0..n).map(|i| ys[i] - fitted[i]).collect();
+        let mut abs_residuals: Vec<f64> = residuals.iter().map(|r| r.abs()).collect();
+        abs_residuals.sort_by(|a, b| a.total_cmp(b));
+        let scale = 6.0 * quantile(&abs_residuals, 0.5);
+        if scale <= f64::EPSILON {
+            break;
+        }
+        for (w, r) in robustness.iter_mut().zip(&residuals) {
+            let u = r / scale;
+            *w = if u.abs() < 1.0 { (1.0 - u * u).powi(2) } else { 0.0 };
+        }
+    }
+
+    knots_from_sorted(xs, &fitted)
+}
+
+/// Continuous piecewise linear least squares fit with breakpoints at quantiles of xs.
+fn piecewise_fit(xs: &[f64], ys: &[f64], segments: usize) -> Result<CalibrationCurve> {
+    let mut knot_xs: Vec<f64> = (0..=segments)
+        .map(|s| quantile(xs, s as f64 / segments as f64))
+        .collect();
+    knot_xs.dedup();
+
+    // Hat basis functions, one per knot, so the coefficients are the knot values
+    let m = knot_xs.len();
+    let basis = |x: f64| -> Vec<f64> {
+        let mut row = vec![0.0; m];
+        let i = knot_xs.partition_point(|&k| k <= x).clamp(1, m - 1);
+        let t = (x - knot_xs[i - 1]) / (knot_xs[i] - knot_xs[i - 1]);
+        row[i - 1] = 1.0 - t;
+        row[i] = t;
+        row
+    };
+
+    // Normal equations
+    let mut ata = vec![vec![0.0; m]; m];
+    let mut atb = vec![0.0; m];
+    for (&x, &y) in xs.iter().zip(ys) {
+        let row = basis(x);
+        for a in 0..m {
+            atb[a] += row[a] * y;
+            for b in 0..m {
+                ata[a][b] += row[a] * row[b];
+            }
+        }
+    }
+    let knot_ys = solve_linear_system(ata, atb)
+        .ok_or_else(|| anyhow!("Piecewise calibration is underdetermined, use fewer segments"))?;
+
+    Ok(CalibrationCurve::Knots { xs: knot_xs, ys: knot_ys })
+}
+
+/// Solve `a * x = b` by Gaussian elimination with partial pivoting.
+fn solve_linear_system(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    let n = b.len();
+    for col in 0..n {
+        let pivot = (col..n).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        for row in col + 1..n {
+            let factor = a[row][col] / a[col][col];
+            for k in col..n {
+                a[row][k] -= factor * a[col][k];
+            }
+            b[row] -= factor * b[col];
+        }
+    }
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let sum: f64 = (row + 1..n).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - sum) / a[row][row];
+    }
+    Some(x)
+}
+
+/// Knots from values fitted at sorted xs, averaging the fits of tied xs.
+fn knots_from_sorted(xs: &[f64], fitted: &[f64]) -> CalibrationCurve {
+    let mut knot_xs: Vec<f64> = Vec::new();
+    let mut knot_ys: Vec<f64> = Vec::new();
+    let mut i = 0;
+    while i < xs.len() {
+        let mut j = i;
+        while j + 1 < xs.len() && xs[j + 1] == xs[i] {
+            j += 1;
+        }
+        knot_xs.push(xs[i]);
+        knot_ys.push(fitted[i..=j].iter().sum::<f64>() / (j - i + 1) as f64);
+        i = j + 1;
+    }
+    CalibrationCurve::Knots { xs: knot_xs, ys: knot_ys }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_linear_calibration() {
+        let predicted: Vec<f32> = (0..20).map(|i| i as f32 * 0.05).collect();
+        let observed: Vec<f32> = predicted.iter().map(|p| 120.0 * p + 5.0).collect();
+
+        let calibration = RTCalibration::fit(&predicted, &observed, CalibrationMethod::Linear).unwrap();
+        assert!((calibration.apply(0.5) - 65.0).abs() < 1e-3);
+        // Linear calibrations extrapolate beyond the anchors
+        assert!((calibration.apply(1.5) - 185.0).abs() < 1e-3);
+
+        let stats = calibration.residual_stats();
+        assert_eq!(stats.n, 20);
+        assert!(stats.rmse < 1e-3);
+        assert!((stats.r_squared - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_nonlinear_calibration() {
+        // Curved gradient with one outlier
+        let predicted: Vec<f32> = (0..50).map(|i| i as f32 / 49.0).collect();
+        let mut observed: Vec<f32> = predicted.iter().map(|p| 10.0 + 80.0 * p * p).collect();
+        observed[25] += 30.0;
+
+        let linear = RTCalibration::fit(&predicted, &observed, CalibrationMethod::Linear).unwrap();
+        let lowess = RTCalibration::fit(&predicted, &observed, CalibrationMethod::Lowess {
+            frac: 0.3,
+            iterations: 3,
+        })
+        .unwrap();
+        let piecewise =
+            RTCalibration::fit(&predicted, &observed, CalibrationMethod::Piecewise { segments: 5 }).unwrap();
+
+        // The robust fit ignores the outlier, so the typical residual is small
+        assert!(lowess.residual_stats().median_abs < 0.5);
+        assert!(lowess.residual_stats().median_abs < linear.residual_stats().median_abs);
+        assert!(piecewise.residual_stats().median_abs < linear.residual_stats().median_abs);
+        assert!((lowess.apply(0.5) - 30.0).abs() < 1.0);
+
+        // Held-out peptides without the outlier
+        let held_out: Vec<f32> = vec![0.1, 0.45, 0.9];
+        let held_out_observed: Vec<f32> = held_out.iter().map(|p| 10.0 + 80.0 * p * p).collect();
+        let stats = lowess.evaluate(&held_out, &held_out_observed).unwrap();
+        assert!(stats.mae < 1.0);
+    }
+
+    #[test]
+    fn test_invalid_anchors() {
+        assert!(RTCalibration::fit(&[1.0, 2.0], &[1.0], CalibrationMethod::Linear).is_err());
+        assert!(RTCalibration::fit(&[1.0, 1.0], &[1.0, 2.0], CalibrationMethod::Linear).is_err());
+        assert!(RTCalibration::fit(&[1.0, f32::NAN], &[1.0, 2.0], CalibrationMethod::Linear).is_err());
+        assert!(RTCalibration::fit(
+            &[1.0, 2.0, 3.0],
+            &[1.0, 2.0, 3.0],
+            CalibrationMethod::Piecewise { segments: 3 }
+        )
+        .is_err());
+    }
+}
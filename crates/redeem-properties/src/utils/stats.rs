@@ -178,6 +178,25 @@ impl Metrics {
             .filter(|((p, t), tol)| (*p - *t).abs() <= **tol)
             .count() as f32 / pred.len() as f32
     }
+
+    /// Computes top-1 accuracy for flattened class distributions, i.e. the proportion of rows
+    /// where the most likely predicted class is also the most likely target class.
+    pub fn top1_accuracy(pred: &[f32], target: &[f32], num_classes: usize) -> f32 {
+        fn argmax(row: &[f32]) -> usize {
+            row.iter()
+                .enumerate()
+                .max_by(|a, b| a.1.partial_cmp(b.1).unwrap_or(std::cmp::Ordering::Equal))
+                .map(|(i, _)| i)
+                .unwrap_or(0)
+        }
+        let rows = pred.len() / num_classes;
+        let correct = pred
+            .chunks(num_classes)
+            .zip(target.chunks(num_classes))
+            .filter(|(p, t)| argmax(p) == argmax(t))
+            .count();
+        correct as f32 / rows as f32
+    }
    
     /// Computes precision as TP / (TP + FP), based on a binary threshold.
     pub fn precision(pred: &[f32], target: &[f32], threshold: f32) -> Option<f32> {